@@ -0,0 +1,267 @@
+//! Generates the `core::modbus::regs` constants from `ModbusMap.csv`
+//! and the profile overrides of `ModbusProfiles.csv`.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    env,
+    fmt::Write as _,
+    fs,
+    path::Path,
+};
+
+const MAP_PATH: &str = "ModbusMap.csv";
+const PROFILES_PATH: &str = "ModbusProfiles.csv";
+
+/// Maximum number of words of a description that make up a constant name.
+const MAX_NAME_WORDS: usize = 8;
+
+struct Row {
+    addr: u16,
+    variant: String,
+    value_type: &'static str,
+    description: String,
+    specific: bool,
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed={}", MAP_PATH);
+    println!("cargo:rerun-if-changed={}", PROFILES_PATH);
+
+    let overrides = read_overrides(PROFILES_PATH);
+    let rows = read_rows(MAP_PATH, &overrides);
+    let out_path = Path::new(&env::var("OUT_DIR").unwrap()).join("regs.rs");
+    fs::write(out_path, generate(&rows)).unwrap();
+}
+
+/// The addresses and descriptions of the definitions with a curated
+/// profile, see `RegisterMap::apply_profiles()`.
+fn read_overrides(path: &str) -> HashSet<(u16, String)> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .flexible(true)
+        .comment(Some(b'#'))
+        .from_path(path)
+        .unwrap_or_else(|err| panic!("Failed to open {}: {}", path, err));
+    rdr.records()
+        .map(|record| {
+            let record = record.unwrap_or_else(|err| panic!("Invalid record in {}: {}", path, err));
+            let addr = record.get(0).unwrap_or_default().trim();
+            let addr = addr
+                .parse()
+                .unwrap_or_else(|_| panic!("Invalid address {:?} in {}", addr, path));
+            (addr, record.get(1).unwrap_or_default().trim().to_string())
+        })
+        .collect()
+}
+
+fn read_rows(path: &str, overrides: &HashSet<(u16, String)>) -> Vec<Row> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_path(path)
+        .unwrap_or_else(|err| panic!("Failed to open {}: {}", path, err));
+    // Only the first generic definition of an address is used, in
+    // accordance with `RegisterMap::get()` without a device profile.
+    let mut rows: BTreeMap<u16, Row> = BTreeMap::new();
+    for (i, record) in rdr.records().enumerate() {
+        let record = record.unwrap_or_else(|err| panic!("Invalid record in {}: {}", path, err));
+        let addr = match record.get(1).map(str::trim) {
+            Some(addr) if !addr.is_empty() => match holding_register(addr) {
+                Some(Ok(addr)) => addr,
+                // Coils, discrete inputs and input registers
+                Some(Err(())) => continue,
+                None => panic!("Invalid address {:?} in line {} of {}", addr, i + 2, path),
+            },
+            _ => continue,
+        };
+        let reg_type = record.get(0).map(str::trim).unwrap_or_default();
+        let (variant, value_type) = parse_reg_type(reg_type).unwrap_or_else(|| {
+            panic!(
+                "Invalid register type {:?} in line {} of {}",
+                reg_type,
+                i + 2,
+                path
+            )
+        });
+        let description = record.get(2).unwrap_or_default().trim().to_string();
+        // Inferred profiles only matter for addresses with more than one
+        // definition, which is the only case in which `specific` is used
+        let specific = record.get(3).is_some_and(|p| !p.trim().is_empty())
+            || overrides.contains(&(addr, description.clone()))
+            || is_device_specific(&description);
+        let row = Row {
+            addr,
+            variant,
+            value_type,
+            description,
+            specific,
+        };
+        match rows.get(&addr) {
+            None => {
+                rows.insert(addr, row);
+            }
+            Some(prev) if prev.specific && !row.specific => {
+                rows.insert(addr, row);
+            }
+            Some(_) => {}
+        }
+    }
+    rows.into_values().collect()
+}
+
+/// Mirrors `Table::parse_address()` and returns `Err` for references
+/// to other tables than the holding registers.
+fn holding_register(addr: &str) -> Option<Result<u16, ()>> {
+    if !addr.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    // Only digits remain, so the first byte is a char boundary
+    let (prefix, number) = match addr.len() {
+        6 => addr.split_at(1),
+        5 if addr.starts_with('0') => addr.split_at(1),
+        // Either a holding register or a reference
+        5 if addr.starts_with(['1', '3', '4']) => return None,
+        _ => ("4", addr),
+    };
+    match (prefix, number.parse::<u16>()) {
+        (_, Ok(0)) | (_, Err(_)) => None,
+        ("4", Ok(addr)) => Some(Ok(addr)),
+        ("0" | "1" | "3", Ok(_)) => Some(Err(())),
+        _ => None,
+    }
+}
+
+/// Mirrors `DeviceProfile::infer()`, which ignores clauses that only
+/// state the access mode, e.g. "(Read only for RFT9739)" or "[5700 RO]".
+fn is_device_specific(description: &str) -> bool {
+    const MODES: [&str; 7] = [
+        "read only",
+        "read-only",
+        "ro",
+        "write only",
+        "write-only",
+        "wo",
+        "rw",
+    ];
+    description
+        .split(['(', ')', '[', ']', ';', ','])
+        .map(|clause| clause.trim().to_lowercase())
+        .filter(|clause| {
+            !MODES.iter().any(|mode| {
+                clause
+                    .strip_prefix(mode)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with(" for "))
+                    || clause.strip_suffix(mode).is_some_and(|device| {
+                        let device =
+                            device.trim_end_matches(|c: char| c.is_whitespace() || c == '-');
+                        [
+                            "rft9739",
+                            "9739",
+                            "2700",
+                            "series2700",
+                            "5700",
+                            "gen5",
+                            "orion",
+                            "fork",
+                        ]
+                        .contains(&device)
+                    })
+            })
+        })
+        .any(|clause| {
+            ["9739", "gen5", "5700", "orion", "fork", "2700"]
+                .iter()
+                .any(|kw| clause.contains(kw))
+        })
+}
+
+/// Mirrors `RegType::from_str()` and returns the enum variant
+/// together with the Rust type of the decoded value.
+fn parse_reg_type(s: &str) -> Option<(String, &'static str)> {
+    if s.is_empty() {
+        return Some(("U8".to_string(), "u8"));
+    }
+    let mut chars = s.chars();
+    let kind = chars.next()?;
+    let bits: u16 = chars.as_str().parse().ok()?;
+    let res = match (kind.to_ascii_uppercase(), bits) {
+        ('U', 8) => ("U8".to_string(), "u8"),
+        ('U', 16) => ("U16".to_string(), "u16"),
+        ('U', 24) => ("U24".to_string(), "u32"),
+        ('U', 32) => ("U32".to_string(), "u32"),
+        ('U', 64) => ("U64".to_string(), "u64"),
+        ('F', 32) => ("F32".to_string(), "f32"),
+        ('F', 64) => ("F64".to_string(), "f64"),
+        ('A', len) if len > 0 => (format!("Ascii({})", len), "str"),
+        _ => return None,
+    };
+    Some(res)
+}
+
+fn const_name(addr: u16, description: &str) -> String {
+    let words: Vec<String> = description
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .take(MAX_NAME_WORDS)
+        .map(str::to_ascii_uppercase)
+        .collect();
+    let name = words.join("_");
+    if name.is_empty() {
+        format!("REG_{}", addr)
+    } else if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("REG_{}", name)
+    } else {
+        name
+    }
+}
+
+fn generate(rows: &[Row]) -> String {
+    let names: Vec<String> = rows
+        .iter()
+        .map(|row| const_name(row.addr, &row.description))
+        .collect();
+    // The register with the lowest address keeps the plain name
+    let mut used: HashSet<&str> = HashSet::new();
+
+    let mut out = String::new();
+    out.push_str("// @generated by build.rs from ModbusMap.csv\n\n");
+    for (row, name) in rows.iter().zip(&names) {
+        let name = if used.insert(name.as_str()) {
+            name.clone()
+        } else {
+            format!("{}_{}", name, row.addr)
+        };
+        writeln!(
+            out,
+            "#[doc = {:?}]",
+            format!(
+                "Register {} ({}): {}",
+                row.addr,
+                row.variant.replace("Ascii(", "A").replace(')', ""),
+                row.description
+            )
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "pub const {}: RegDef<{}> = RegDef::new({}, RegType::{}, {:?});",
+            name, row.value_type, row.addr, row.variant, row.description
+        )
+        .unwrap();
+    }
+    writeln!(
+        out,
+        "\n/// All registers of the Modbus map in ascending address order.\npub static ALL: [RegInfo; {}] = [",
+        rows.len()
+    )
+    .unwrap();
+    for row in rows {
+        writeln!(
+            out,
+            "    RegInfo {{ addr: {}, reg_type: RegType::{}, description: {:?} }},",
+            row.addr, row.variant, row.description
+        )
+        .unwrap();
+    }
+    out.push_str("];\n");
+    out
+}
@@ -1,7 +1,8 @@
+// The control loop is handed back with every error
+#![allow(clippy::result_large_err)]
+
 use coriolis::core::modbus::*;
 //{FW_REG_COUNT, decode_any_reg, decode_generic_reg};
-use std::collections::HashMap;
-use tokio_modbus::slave;
 //#[cfg(feature = "modbus-rtu")]
 pub fn main() {
     use chrono::{DateTime, Utc};
@@ -11,19 +12,19 @@ pub fn main() {
     use stream_cancel::{StreamExt, Tripwire};
     use tokio::timer::Interval;
     use tokio_core::reactor::{Core, Handle};
+    use tokio_modbus::client;
     use tokio_modbus::prelude::{client::util::*, *};
 
-    use coriolis::{buildmap::build_hashmap, modbus, *};
+    use coriolis::{buildmap::RegisterMap, modbus, *};
 
-    use csv::{Reader, Writer, WriterBuilder};
+    use csv::Writer;
 
     use std::fs::File;
     use std::fs::OpenOptions;
     // Open a file to write the CSV data to
-    let file = File::create("data.csv").expect("hay problemo");
-    // Build the HashMap from CSV
+    File::create("data.csv").expect("hay problemo");
+    // Path of the register map CSV
     let path = String::from("ModbusMap.csv");
-    //let my_hmap: HashMap<u16, String> = build_hashmap(&path);
 
     let mut logger_builder = LoggerBuilder::new();
     logger_builder.filter_level(log::LevelFilter::Info);
@@ -54,16 +55,18 @@ pub fn main() {
         cycle_time: Duration,
         timeout: Duration,
         read_index: usize,
-        regs: Vec<u16>,
-        hmap: HashMap<u16, String>,
+        regs: Vec<(u16, RegType)>,
     }
     impl SlaveConfig {
         fn next(&mut self) {
             self.read_index = (self.read_index + 1) % self.regs.len();
         }
-        fn add_regs(&mut self, regs: Vec<u16>) {
+        fn add_regs(&mut self, regs: Vec<u16>, map: &RegisterMap) {
             for reg in regs {
-                self.regs.push(reg);
+                match map.get(reg) {
+                    Some(def) => self.regs.push((reg, def.reg_type)),
+                    None => log::warn!("Skipping register {} missing in the Modbus map", reg),
+                }
             }
         }
     }
@@ -88,13 +91,24 @@ pub fn main() {
         timeout: Duration::from_millis(timeout),
         read_index: 0,
         regs: Vec::new(),
-        hmap: build_hashmap(&path),
+    };
+    // Build the register map from CSV
+    let map = match RegisterMap::from_path(&path) {
+        Ok(map) => map,
+        Err(err) => {
+            log::error!("Failed to load Modbus map {}: {}", path, err);
+            std::process::exit(1);
+        }
     };
     // TODO: Get these regs from user input
 
     //let regs: Vec<u16> = vec![103, 95, 154, 119];
     //let regs: Vec<u16> = vec![5523, 119, 121, 126];
-    slave_config.add_regs(regs);
+    slave_config.add_regs(regs, &map);
+    if slave_config.regs.is_empty() {
+        log::error!("No registers to read");
+        std::process::exit(1);
+    }
 
     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     struct Measurement<T> {
@@ -154,26 +168,16 @@ pub fn main() {
 
         //lets make this handle any reg type
         pub fn measure_any(mut self) -> impl Future<Item = Self, Error = (Error, Self)> {
-            let reg_start = self.config.regs[self.config.read_index];
-            //use HashMap lookup to get reg_count and reg_type
-            let map_value = self.config.hmap.get(&reg_start).unwrap().as_str();
-            let reg_type: char = map_value.chars().nth(0).unwrap();
-            //cut reg_count/2 if reg_type == 'A'
-            let reg_count = count_calc(&reg_type, map_value[1..].parse::<u16>().unwrap());
+            let (reg_start, reg_type) = self.config.regs[self.config.read_index];
             //println!("reg: {:?}", &reg_start);
             self.proxy
-                .read_generic(
-                    Some(self.config.timeout),
-                    reg_start - 1,
-                    reg_count,
-                    reg_type,
-                )
+                .read_generic(Some(self.config.timeout), reg_start - 1, reg_type)
                 //move into closure and do the decode for each type
                 .then(move |res| match res {
-                    Ok(val) => {
-                        if reg_type == 'A' {
+                    Ok(val) => match reg_type {
+                        RegType::Ascii(_) => {
                             //println!("got a 'A'");
-                            let d = decode_generic_reg(val);
+                            let d = decode_generic_reg(&val);
                             match d {
                                 Ok(res) => {
                                     self.measurements.generic =
@@ -182,9 +186,10 @@ pub fn main() {
                                 Err(e) => println!("decode error {:?}", e),
                             }
                             Ok(self)
-                        } else if reg_type == 'U' {
+                        }
+                        RegType::U8 | RegType::U16 | RegType::U24 | RegType::U32 | RegType::U64 => {
                             //println!("got a 'U'");
-                            let d = decode_u_reg(val);
+                            let d = decode_u_reg(&val);
                             match d {
                                 Ok(res) => {
                                     self.measurements.register =
@@ -193,9 +198,10 @@ pub fn main() {
                                 Err(e) => println!("decode error {:?}", e),
                             }
                             Ok(self)
-                        } else if reg_type == 'F' {
+                        }
+                        RegType::F32 | RegType::F64 => {
                             //println!("got a 'F'");
-                            let d = decode_f_reg(val);
+                            let d = decode_f_reg(&val);
                             match d {
                                 Ok(res) => {
                                     self.measurements.float = Some(Measurement::new(res, reg_start))
@@ -203,12 +209,8 @@ pub fn main() {
                                 Err(e) => println!("decode error {:?}", e),
                             }
                             Ok(self)
-                        } else {
-                            println!("got a 'something else'");
-                            //self.measurements.vec = None;
-                            Ok(self)
                         }
-                    }
+                    },
                     Err(err) => {
                         println!("error in read_generic");
                         //self.measurements.vec = None;
@@ -248,6 +250,7 @@ pub fn main() {
         );
         core.run(ctrl_loop.broadcast_slave()).unwrap();
     }
+    #[allow(dead_code)]
     fn write_to_csv(data: Measurements) -> std::result::Result<(), Box<dyn std::error::Error>> {
         let file = OpenOptions::new()
             .append(true)
@@ -1,3 +1,8 @@
+// The control loop is handed back with every error
+#![allow(clippy::result_large_err)]
+
+use coriolis::core::modbus::*;
+
 //#[cfg(feature = "modbus-rtu")]
 pub fn main() {
     use chrono::{DateTime, Utc};
@@ -7,16 +12,17 @@ pub fn main() {
     use stream_cancel::{StreamExt, Tripwire};
     use tokio::timer::Interval;
     use tokio_core::reactor::{Core, Handle};
+    use tokio_modbus::client;
     use tokio_modbus::prelude::{client::util::*, *};
 
     use coriolis::{modbus, *};
 
-    use csv::{Reader, Writer, WriterBuilder};
+    use csv::Writer;
 
     use std::fs::File;
     use std::fs::OpenOptions;
     // Open a file to write the CSV data to
-    let file = File::create("data.csv").expect("hay problemo");
+    File::create("data.csv").expect("hay problemo");
 
     let mut logger_builder = LoggerBuilder::new();
     logger_builder.filter_level(log::LevelFilter::Info);
@@ -33,7 +39,7 @@ pub fn main() {
     struct ContextConfig {
         handle: Handle,
         tty_path: String,
-    };
+    }
 
     impl NewContext for ContextConfig {
         fn new_context(&self) -> Box<dyn Future<Item = client::Context, Error = Error>> {
@@ -46,7 +52,7 @@ pub fn main() {
         slave: Slave,
         cycle_time: Duration,
         timeout: Duration,
-    };
+    }
 
     // TODO: Parse parameters and options from command-line arguments
     let context_config = ContextConfig {
@@ -98,7 +104,7 @@ pub fn main() {
         config: SlaveConfig,
         proxy: modbus::SlaveProxy,
         measurements: Measurements,
-    };
+    }
 
     impl ControlLoop {
         pub fn new(config: SlaveConfig, new_context: Box<dyn NewContext>) -> Self {
@@ -116,43 +122,43 @@ pub fn main() {
             self.proxy.reconnect()
         }
 
+        #[allow(dead_code)]
         pub fn measure_temperature(mut self) -> impl Future<Item = Self, Error = (Error, Self)> {
             self.proxy
-                .read_temperature(Some(self.config.timeout))
-                .then(move |res| match res {
-                    Ok(val) => {
-                        self.measurements.temperature = Some(Measurement::new(val));
+                .read_generic(
+                    Some(self.config.timeout),
+                    regs::TEMPERATURE.protocol_addr(),
+                    regs::TEMPERATURE.reg_type(),
+                )
+                .then(
+                    move |res| match res.and_then(|val| Ok(decode_f32_reg(&val)?)) {
+                        Ok(val) => {
+                            self.measurements.temperature = Some(Measurement::new(val));
 
-                        Ok(self)
-                    }
-                    Err(err) => Err((err, self)),
-                })
+                            Ok(self)
+                        }
+                        Err(err) => Err((err, self)),
+                    },
+                )
         }
 
         pub fn measure_generic(mut self) -> impl Future<Item = Self, Error = (Error, Self)> {
             self.proxy
-                .read_generic(Some(self.config.timeout))
-                .then(move |res| match res {
-                    Ok(val) => {
-                        self.measurements.generic = Some(Measurement::new(val));
-
-                        Ok(self)
-                    }
-                    Err(err) => Err((err, self)),
-                })
-        }
+                .read_generic(
+                    Some(self.config.timeout),
+                    regs::SENSOR_TYPE.protocol_addr(),
+                    regs::SENSOR_TYPE.reg_type(),
+                )
+                .then(
+                    move |res| match res.and_then(|val| Ok(decode_generic_reg(&val)?)) {
+                        Ok(val) => {
+                            self.measurements.generic = Some(Measurement::new(val));
 
-        pub fn read_any(mut self) -> impl Future<Item = Self, Error = (Error, Self)> {
-            self.proxy
-                .read_generic(Some(self.config.timeout))
-                .then(move |res| match res {
-                    Ok(val) => {
-                        self.measurements.generic = Some(Measurement::new(val));
-
-                        Ok(self)
-                    }
-                    Err(err) => Err((err, self)),
-                })
+                            Ok(self)
+                        }
+                        Err(err) => Err((err, self)),
+                    },
+                )
         }
 
         /*
@@ -208,6 +214,7 @@ pub fn main() {
         );
         core.run(ctrl_loop.broadcast_slave()).unwrap();
     }
+    #[allow(dead_code)]
     fn write_to_csv(data: Measurements) -> std::result::Result<(), Box<dyn std::error::Error>> {
         let file = OpenOptions::new()
             .append(true)
@@ -1,30 +1,162 @@
-use coriolis::core::modbus::*;
+// The control loop is handed back with every error
+#![allow(clippy::result_large_err)]
+
+use coriolis::core::modbus::{
+    alarms::{decode_alarms, STATUS_REGS},
+    scaled::ScaledVar,
+    units::unit_reg,
+    *,
+};
 //{FW_REG_COUNT, decode_any_reg, decode_generic_reg};
-use std::collections::HashMap;
-use std::time::Instant;
-use tokio_modbus::slave;
+
+/// Curated device profiles of ambiguous registers in `ModbusMap.csv`.
+const PROFILES_PATH: &str = "ModbusProfiles.csv";
+
+const SEARCH_USAGE: &str =
+    "Usage: modrs search [--type <F32|U16|A16|...>] [--profile <profile>] [--limit <n>] <terms>...";
+
+/// Print the registers that best match the search terms.
+fn search(path: &str, args: &[String]) -> Result<(), String> {
+    use coriolis::buildmap::{RegisterMap, SearchQuery};
+
+    let mut terms = Vec::new();
+    let mut reg_type = None;
+    let mut profile = None;
+    let mut limit = 20;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {}", arg))
+        };
+        match arg.as_str() {
+            "--type" => {
+                let value = value()?;
+                let parsed = value.parse::<RegType>();
+                reg_type = Some(parsed.map_err(|_| format!("Invalid register type {:?}", value))?)
+            }
+            "--profile" => profile = Some(value()?.parse()?),
+            "--limit" => limit = value()?.parse().map_err(|_| "Invalid limit".to_string())?,
+            _ => terms.push(arg.as_str()),
+        }
+    }
+    if terms.is_empty() {
+        return Err(SEARCH_USAGE.to_string());
+    }
+    let mut map = RegisterMap::from_path(path).map_err(|e| e.to_string())?;
+    map.load_profiles(PROFILES_PATH)
+        .map_err(|e| format!("{}: {}", PROFILES_PATH, e))?;
+    map.set_profile(profile);
+    let mut query = SearchQuery::new(&terms.join(" "));
+    if let Some(reg_type) = reg_type {
+        query = query.with_type(reg_type);
+    }
+    println!("{:>5}  {:<5} {:>5}  Description", "Addr", "Type", "Words");
+    for hit in map.search_query(&query).into_iter().take(limit) {
+        println!(
+            "{:>5}  {:<5} {:>5}  {}",
+            hit.def.addr,
+            hit.def.reg_type.to_string(),
+            hit.def.word_count(),
+            hit.def.description
+        );
+    }
+    Ok(())
+}
+
+const WRITE_USAGE: &str = "Usage: modrs write [--confirm] <register|0xxxx coil> <value>";
+
+/// A register value or coil state to write.
+enum Write {
+    Register(coriolis::buildmap::RegisterDef, Box<Value>),
+    Coil(coriolis::buildmap::BitDef, bool),
+}
+
+/// Parse the register or coil, value and confirmation of a write.
+fn parse_write(
+    args: &[String],
+    map: &coriolis::buildmap::RegisterMap,
+) -> Result<(Write, coriolis::buildmap::Confirm), String> {
+    use coriolis::buildmap::Confirm;
+
+    let mut confirm = Confirm::No;
+    let mut positional = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--confirm" => confirm = Confirm::Yes,
+            _ => positional.push(arg.as_str()),
+        }
+    }
+    let (addr, value) = match *positional.as_slice() {
+        [addr, value] => (addr, value),
+        _ => return Err(WRITE_USAGE.to_string()),
+    };
+    let (table, addr) =
+        Table::parse_address(addr).map_err(|_| format!("Invalid register {:?}", addr))?;
+    match table {
+        Table::HoldingRegister => {
+            let def = map
+                .get(addr)
+                .ok_or_else(|| format!("Register {} is missing in the Modbus map", addr))?;
+            let value = Value::parse(def.reg_type, value)
+                .map_err(|_| format!("Invalid {} value {:?}", def.reg_type, value))?;
+            Ok((Write::Register(def.clone(), Box::new(value)), confirm))
+        }
+        Table::Coil => {
+            let def = map
+                .coil(addr)
+                .ok_or_else(|| format!("Coil {} is missing in the Modbus map", addr))?;
+            let state = match value.to_lowercase().as_str() {
+                "1" | "on" | "true" => true,
+                "0" | "off" | "false" => false,
+                _ => return Err(format!("Invalid coil state {:?}", value)),
+            };
+            Ok((Write::Coil(def.clone(), state), confirm))
+        }
+        table => Err(format!("Cannot write {} {}", table, addr)),
+    }
+}
+
 //#[cfg(feature = "modbus-rtu")]
 pub fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("search") {
+        if let Err(err) = search("ModbusMap.csv", &args[1..]) {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     use chrono::{DateTime, Utc};
     use env_logger::Builder as LoggerBuilder;
-    use futures::{future::Either, Future, Stream};
+    use futures::{
+        future::Either,
+        stream,
+        unsync::mpsc::{unbounded, UnboundedSender},
+        Future, Stream,
+    };
     use std::{cell::RefCell, env, io::Error, rc::Rc, time::Duration};
     use stream_cancel::{StreamExt, Tripwire};
     use tokio::timer::Interval;
     use tokio_core::reactor::{Core, Handle};
+    use tokio_modbus::client;
     use tokio_modbus::prelude::{client::util::*, *};
 
-    use coriolis::{buildmap::build_hashmap, modbus, *};
+    use coriolis::{
+        buildmap::{RegisterDef, RegisterMap},
+        core::modbus::plan::ReadPlan,
+        error, modbus, *,
+    };
 
-    use csv::{Reader, Writer, WriterBuilder};
+    use csv::Writer;
 
     use std::fs::File;
     use std::fs::OpenOptions;
     // Open a file to write the CSV data to
-    let file = File::create("data.csv").expect("hay problemo");
-    // Build the HashMap from CSV
+    File::create("data.csv").expect("hay problemo");
+    // Path of the register map CSV
     let path = String::from("ModbusMap.csv");
-    //let my_hmap: HashMap<u16, String> = build_hashmap(&path);
 
     let mut logger_builder = LoggerBuilder::new();
     logger_builder.filter_level(log::LevelFilter::Info);
@@ -41,30 +173,49 @@ pub fn main() {
     struct ContextConfig {
         handle: Handle,
         tty_path: String,
+        framing: Framing,
     }
 
     impl NewContext for ContextConfig {
         fn new_context(&self) -> Box<dyn Future<Item = client::Context, Error = Error>> {
-            Box::new(modbus::rtu::connect_path(&self.handle, &self.tty_path))
+            Box::new(modbus::rtu::connect_path_with_framing(
+                &self.handle,
+                &self.tty_path,
+                self.framing,
+            ))
         }
     }
 
     #[derive(Debug, Clone)]
     struct SlaveConfig {
+        port: String,
         slave: Slave,
         cycle_time: Duration,
         timeout: Duration,
-        read_index: usize,
-        regs: Vec<u16>,
-        hmap: HashMap<u16, String>,
+        regs: Vec<RegisterDef>,
+        max_read_gap: u16,
+        scaled_int: bool,
     }
     impl SlaveConfig {
-        fn next(&mut self) {
-            self.read_index = (self.read_index + 1) % self.regs.len();
-        }
-        fn add_regs(&mut self, regs: Vec<u16>) {
+        fn add_regs(&mut self, regs: Vec<u16>, map: &RegisterMap) {
             for reg in regs {
-                self.regs.push(reg);
+                match map.get(reg) {
+                    Some(def) if !def.access.mode(map.profile()).is_readable() => {
+                        log::warn!("Skipping write-only register {}", reg)
+                    }
+                    Some(def) => {
+                        if map.variants(reg).len() > 1 {
+                            log::warn!(
+                                "Register {} has multiple definitions, using {:?} for profile {:?}",
+                                reg,
+                                def.description,
+                                map.profile()
+                            );
+                        }
+                        self.regs.push(def.clone())
+                    }
+                    None => log::warn!("Skipping register {} missing in the Modbus map", reg),
+                }
             }
         }
     }
@@ -72,30 +223,138 @@ pub fn main() {
     //read the config file
     let new_config = setup::read_config();
     //unpack the config here
-    let com_list = new_config.ComPort;
     let mb_addr: Slave = Slave(new_config.ModbusAddress);
     let interval = new_config.cycle_time;
-    let regs = new_config.Regs;
     let timeout = new_config.timeout;
-    let context_config = ContextConfig {
-        handle: core.handle(),
-        tty_path: com_list[0].to_owned(),
-        //tty_path: "COM9".to_owned(),
+
+    // A bus with its own reconnecting context, i.e. a serial port or a
+    // TCP connection
+    struct Bus {
+        port: String,
+        new_context: Box<dyn NewContext>,
+        slaves: Vec<Slave>,
+        regs: Vec<u16>,
+    }
+    let mut buses: Vec<Bus> = match new_config.TcpAddress {
+        Some(ref addr) => match modbus::tcp::resolve(addr) {
+            Ok(socket_addr) => {
+                log::info!("Connecting: {} with unit id {}", socket_addr, mb_addr.0);
+                vec![Bus {
+                    port: socket_addr.to_string(),
+                    new_context: Box::new(modbus::tcp::TcpConnector::new(
+                        core.handle(),
+                        socket_addr,
+                        mb_addr,
+                    )),
+                    slaves: new_config
+                        .modbus_addresses()
+                        .into_iter()
+                        .map(Slave)
+                        .collect(),
+                    regs: new_config.Regs.clone(),
+                }]
+            }
+            Err(err) => {
+                log::error!("Failed to resolve {}: {}", addr, err);
+                std::process::exit(1);
+            }
+        },
+        None => new_config
+            .ports()
+            .into_iter()
+            .map(|port| {
+                let context_config = ContextConfig {
+                    handle: core.handle(),
+                    tty_path: port.ComPort.clone(),
+                    framing: new_config.Framing,
+                    //tty_path: "COM9".to_owned(),
+                };
+                log::info!("Connecting: {:?}", context_config);
+                Bus {
+                    port: port.ComPort,
+                    new_context: Box::new(context_config),
+                    slaves: port.ModbusAddresses.into_iter().map(Slave).collect(),
+                    regs: port.Regs.unwrap_or_default(),
+                }
+            })
+            .collect(),
     };
+    if buses.is_empty() {
+        log::error!("No serial port or TCP address configured");
+        std::process::exit(1);
+    }
 
-    let mut slave_config = SlaveConfig {
+    let slave_config = SlaveConfig {
+        port: String::new(),
         slave: mb_addr,
         cycle_time: Duration::from_millis(interval),
         timeout: Duration::from_millis(timeout),
-        read_index: 0,
         regs: Vec::new(),
-        hmap: build_hashmap(&path),
+        max_read_gap: new_config.MaxReadGap,
+        scaled_int: new_config.ScaledInt,
+    };
+    // Build the register map from CSV
+    let mut map = match RegisterMap::from_path(&path) {
+        Ok(map) => map,
+        Err(err) => {
+            log::error!("Failed to load Modbus map {}: {}", path, err);
+            std::process::exit(1);
+        }
     };
+    if let Err(err) = map.load_profiles(PROFILES_PATH) {
+        log::error!("Failed to load profiles {}: {}", PROFILES_PATH, err);
+        std::process::exit(1);
+    }
+    map.set_profile(new_config.Profile);
+    if args.first().map(String::as_str) == Some("write") {
+        // Writes go to ModbusAddress on the first port
+        let new_context = buses.swap_remove(0).new_context;
+        let shared_context = Rc::new(RefCell::new(SharedContext::new(None, new_context)));
+        let proxy = modbus::SlaveProxy::new(mb_addr, shared_context).with_profile(map.profile());
+        let timeout = Some(Duration::from_millis(timeout));
+        let res = parse_write(&args[1..], &map).and_then(|(write, confirm)| {
+            let write = proxy.connect(timeout).and_then(move |()| match write {
+                Write::Register(def, value) => Either::A(
+                    proxy
+                        .write_value(timeout, &def, &value, confirm)
+                        .map(|value| value.to_string()),
+                ),
+                Write::Coil(def, state) => Either::B(
+                    proxy
+                        .write_coil(timeout, &def, state, confirm)
+                        .map(move |()| if state { "on" } else { "off" }.to_string()),
+                ),
+            });
+            core.run(write).map_err(|err| err.to_string())
+        });
+        match res {
+            Ok(value) => println!("{}", value),
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
     // TODO: Get these regs from user input
 
     //let regs: Vec<u16> = vec![103, 95, 154, 119];
     //let regs: Vec<u16> = vec![5523, 119, 121, 126];
-    slave_config.add_regs(regs);
+    let bus_configs: Vec<_> = buses
+        .iter()
+        .map(|bus| {
+            let mut config = SlaveConfig {
+                port: bus.port.clone(),
+                ..slave_config.clone()
+            };
+            config.add_regs(bus.regs.clone(), &map);
+            if config.regs.is_empty() {
+                log::error!("No registers to read on {}", bus.port);
+                std::process::exit(1);
+            }
+            config
+        })
+        .collect();
 
     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     struct Measurement<T> {
@@ -113,119 +372,191 @@ pub fn main() {
             }
         }
     }
-    //removed Copy to accomodate generic<String>,
-    //should these be reg names? or reg types? (Coil/Reg/Long/Float/Ascii)
-    #[derive(Debug, Default, Clone, PartialEq)]
-    struct Measurements {
-        generic: Option<Measurement<Generic>>,
-        register: Option<Measurement<Register>>,
-        float: Option<Measurement<Float>>,
+
+    impl<T: ToString> Measurement<T> {
+        fn into_text(self) -> Measurement<String> {
+            Measurement {
+                ts: self.ts,
+                val: self.val.to_string(),
+                reg: self.reg,
+            }
+        }
+    }
+    // A line of the output that merges the measurements of all ports
+    struct Record {
+        port: String,
+        slave: Slave,
+        measurement: Measurement<String>,
     }
 
-    // Only a single slave sensor is used for demonstration purposes here.
-    // A typical application will use multiple slaves that all share
-    // the same Modbus environment, RTU client context and bus wiring,
-    // i.e. multiple sensors and actuators are all connected to a single
-    // serial port.
+    //lets make this handle any reg type
+    fn decode_any(
+        proxy: &modbus::SlaveProxy,
+        timeout: Option<Duration>,
+        scaled_int: bool,
+        def: RegisterDef,
+        words: Vec<u16>,
+    ) -> impl Future<Item = Option<Measurement<String>>, Error = Error> {
+        let reg_start = def.addr;
+        // Process variables are tagged with their unit, scaled integers
+        // only if enabled
+        let is_scaled = ScaledVar::from_addr(reg_start).is_some();
+        let measurement = if unit_reg(reg_start).is_some() && (!is_scaled || scaled_int) {
+            Either::A(
+                proxy
+                    .decode_measurement(timeout, reg_start, &words)
+                    .map(move |val| Measurement::new(val, reg_start).into_text()),
+            )
+        } else {
+            Either::B(futures::future::result(
+                proxy.decode_value(&def, &words).map(|val| {
+                    // Status registers are logged as alarm texts
+                    let symbolic = match val.as_u64() {
+                        Some(raw) if STATUS_REGS.contains(&reg_start) => {
+                            decode_alarms(reg_start, raw as u16).ok().map(|alarms| {
+                                let alarms: Vec<_> =
+                                    alarms.map(|alarm| alarm.to_string()).collect();
+                                if alarms.is_empty() {
+                                    return "none".to_string();
+                                }
+                                alarms.join(", ")
+                            })
+                        }
+                        Some(raw) => def
+                            .field
+                            .as_ref()
+                            .map(|field| field.decode(raw).to_string()),
+                        None => None,
+                    };
+                    match symbolic {
+                        Some(symbolic) => Measurement::new(symbolic, reg_start),
+                        None => Measurement::new(val, reg_start).into_text(),
+                    }
+                }),
+            ))
+        };
+        // Invalid values are skipped without failing the whole cycle
+        measurement.then(move |res| match res.map_err(error::Error::from) {
+            Ok(measurement) => Ok(Some(measurement)),
+            Err(err @ error::Error::Decode { .. }) => {
+                log::warn!("Failed to decode register {}: {}", reg_start, err);
+                Ok(None)
+            }
+            Err(err) => Err(err.into()),
+        })
+    }
+
+    // Each slave has its own control loop. All slaves share the same
+    // Modbus environment, RTU client context and bus wiring, i.e.
+    // multiple sensors and actuators are all connected to a single
+    // serial port. The scheduler takes turns between their requests.
     struct ControlLoop {
-        // Only shared with the single proxy and otherwise unused within the
-        // control loop. Just to demonstrate how to share the Modbus context
-        // and how to recover from communication errors by reconnecting.
-        _shared_context: Rc<RefCell<SharedContext>>,
         config: SlaveConfig,
         proxy: modbus::SlaveProxy,
-        measurements: Measurements,
+        // All registers are read at once in every cycle
+        plan: ReadPlan,
+        output: UnboundedSender<Record>,
     }
 
     impl ControlLoop {
-        pub fn new(config: SlaveConfig, new_context: Box<dyn NewContext>) -> Self {
-            let shared_context = Rc::new(RefCell::new(SharedContext::new(None, new_context)));
-            let proxy = modbus::SlaveProxy::new(config.slave, Rc::clone(&shared_context));
+        pub fn new(
+            config: SlaveConfig,
+            scheduler: &modbus::Scheduler,
+            output: UnboundedSender<Record>,
+        ) -> Self {
+            let proxy = scheduler.proxy(config.slave);
+            let regs: Vec<_> = config
+                .regs
+                .iter()
+                .map(|def| (def.addr, def.word_count()))
+                .collect();
+            let plan = ReadPlan::new(&regs, config.max_read_gap);
+            log::info!(
+                "Reading {} registers of slave {} with {} requests",
+                plan.len(),
+                config.slave.0,
+                plan.blocks().len()
+            );
             Self {
-                _shared_context: shared_context,
                 config,
                 proxy,
-                measurements: Default::default(),
+                plan,
+                output,
             }
         }
 
+        fn send_output(&self, measurement: Measurement<String>) {
+            let record = Record {
+                port: self.config.port.clone(),
+                slave: self.config.slave,
+                measurement,
+            };
+            // The receiver lives as long as the control loops
+            let _ = self.output.unbounded_send(record);
+        }
+
         fn reconnect(&self) -> impl Future<Item = (), Error = Error> {
             self.proxy.reconnect()
         }
 
-        //lets make this handle any reg type
-        pub fn measure_any(mut self) -> impl Future<Item = Self, Error = (Error, Self)> {
-            let reg_start = self.config.regs[self.config.read_index];
-            //use HashMap lookup to get reg_count and reg_type
-            let map_value = self.config.hmap.get(&reg_start).unwrap().as_str();
-            let reg_type: char = map_value.chars().nth(0).unwrap();
-            //cut reg_count/2 if reg_type == 'A'
-            let reg_count = count_calc(&reg_type, map_value[1..].parse::<u16>().unwrap());
-            //println!("reg: {:?}", &reg_start);
+        /// Read the byte orders of the slave. A slave that fails keeps
+        /// the default byte orders instead of stopping all control loops.
+        fn read_byte_orders(&self) -> impl Future<Item = (), Error = ()> {
+            let port = self.config.port.clone();
+            let slave = self.config.slave.0;
+            self.proxy
+                .read_byte_orders(Some(self.config.timeout))
+                .or_else(move |err| {
+                    log::error!(
+                        "Using the default byte orders of slave {} on {}: {}",
+                        slave,
+                        port,
+                        err
+                    );
+                    Ok(())
+                })
+        }
+
+        /// Read all registers with as few requests as possible and
+        /// decode them one after the other.
+        pub fn measure_all(self) -> impl Future<Item = Self, Error = (Error, Self)> {
+            let timeout = Some(self.config.timeout);
+            let proxy = self.proxy.clone();
+            let defs = self.config.regs.clone();
+            let scaled_int = self.config.scaled_int;
             self.proxy
-                .read_generic(
-                    Some(self.config.timeout),
-                    reg_start - 1,
-                    reg_count,
-                    reg_type,
-                )
-                //move into closure and do the decode for each type
+                .read_plan(timeout, &self.plan)
+                .and_then(move |words| {
+                    stream::iter_ok(defs.into_iter().zip(words))
+                        .and_then(move |(def, words)| {
+                            decode_any(&proxy, timeout, scaled_int, def, words)
+                        })
+                        .collect()
+                })
                 .then(move |res| match res {
-                    Ok(val) => {
-                        if reg_type == 'A' {
-                            //println!("got a 'A'");
-                            let d = decode_generic_reg(val);
-                            match d {
-                                Ok(res) => {
-                                    self.measurements.generic =
-                                        Some(Measurement::new(res, reg_start))
-                                }
-                                Err(e) => println!("decode error {:?}", e),
-                            }
-                            Ok(self)
-                        } else if reg_type == 'U' {
-                            //println!("got a 'U'");
-                            let d = decode_u_reg(val);
-                            match d {
-                                Ok(res) => {
-                                    self.measurements.register =
-                                        Some(Measurement::new(res, reg_start))
-                                }
-                                Err(e) => println!("decode error {:?}", e),
-                            }
-                            Ok(self)
-                        } else if reg_type == 'F' {
-                            //println!("got a 'F'");
-                            let d = decode_f_reg(val);
-                            match d {
-                                Ok(res) => {
-                                    self.measurements.float = Some(Measurement::new(res, reg_start))
-                                }
-                                Err(e) => println!("decode error {:?}", e),
-                            }
-                            Ok(self)
-                        } else {
-                            println!("got a 'something else'");
-                            //self.measurements.vec = None;
-                            Ok(self)
+                    Ok(measurements) => {
+                        for measurement in measurements.into_iter().flatten() {
+                            self.send_output(measurement);
                         }
+                        Ok(self)
                     }
-                    Err(err) => {
-                        println!("error in read_generic");
-                        //self.measurements.vec = None;
-                        //Ok(self)
-                        Err((err, self))
-                    }
+                    Err(err) => Err((err, self)),
                 })
         }
 
+        /// Reconnect only if the connection is broken, exceptions and
+        /// invalid responses are answered on an intact connection.
         pub fn recover_after_error(&self, err: &Error) -> impl Future<Item = (), Error = ()> {
+            if !error::Error::needs_reconnect_after(err) {
+                log::warn!("Request of slave {} failed: {}", self.config.slave.0, err);
+                return Either::B(futures::future::ok(()));
+            }
             log::warn!("Reconnecting after error: {}", err);
-            self.reconnect().or_else(|err| {
+            Either::A(self.reconnect().or_else(|err| {
                 log::error!("Failed to reconnect: {}", err);
                 // Continue and don't leave/terminate the control loop!
                 Ok(())
-            })
+            }))
         }
 
         pub fn broadcast_slave(&self) -> impl Future<Item = (), Error = Error> {
@@ -233,23 +564,49 @@ pub fn main() {
         }
     }
 
-    log::info!("Connecting: {:?}", context_config);
-    //maybe here? turn context_config into ctx
-    let ctrl_loop = ControlLoop::new(slave_config, Box::new(context_config));
-
-    //ctrl_loop.config.reg_start = 0xf6;
-    //ctrl_loop.config.reg_count = 0x02;
-    core.run(ctrl_loop.reconnect()).unwrap();
+    // Each port is polled by its own scheduler on the same event loop
+    let (output, records) = unbounded();
+    let mut ctrl_loops = Vec::new();
+    for (bus, bus_config) in buses.into_iter().zip(bus_configs) {
+        let shared_context = Rc::new(RefCell::new(SharedContext::new(None, bus.new_context)));
+        let scheduler = modbus::Scheduler::new(core.handle(), shared_context);
+        if let Err(err) = core.run(scheduler.reconnect()) {
+            log::error!("Failed to connect {}: {}", bus.port, err);
+        }
+        for slave in bus.slaves {
+            let config = SlaveConfig {
+                slave,
+                ..bus_config.clone()
+            };
+            ctrl_loops.push(ControlLoop::new(config, &scheduler, output.clone()));
+        }
+    }
+    drop(output);
+    let read_byte_orders = ctrl_loops.iter().map(ControlLoop::read_byte_orders);
+    // Failures are logged per slave
+    let _ = core.run(futures::future::join_all(read_byte_orders));
+    core.handle().spawn(records.for_each(|record| {
+        println!(
+            "{} {} slave {} reg {}: {}",
+            record.measurement.ts.to_rfc3339(),
+            record.port,
+            record.slave.0,
+            record.measurement.reg,
+            record.measurement.val
+        );
+        Ok(())
+    }));
 
     let broadcast_slave = false;
     if broadcast_slave {
         log::info!(
             "Resetting Modbus slave address to {:?}",
-            ctrl_loop.proxy.slave()
+            ctrl_loops[0].proxy.slave()
         );
-        core.run(ctrl_loop.broadcast_slave()).unwrap();
+        core.run(ctrl_loops[0].broadcast_slave()).unwrap();
     }
-    fn write_to_csv(data: Measurements) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    #[allow(dead_code)]
+    fn write_to_csv(record: &Record) -> std::result::Result<(), Box<dyn std::error::Error>> {
         let file = OpenOptions::new()
             .append(true)
             .create(true)
@@ -259,59 +616,46 @@ pub fn main() {
             .from_writer(file);
         //nn to modify for all measurement types
         let generic = vec![
-            data.register.as_ref().unwrap().ts.to_string(),
-            data.register.as_ref().unwrap().val.to_string(),
-            data.register.as_ref().unwrap().reg.to_string(),
+            record.measurement.ts.to_string(),
+            record.measurement.val.clone(),
+            record.measurement.reg.to_string(),
         ];
         wtr.write_record(generic)?;
 
         wtr.flush()?;
         Ok(())
     }
-    let start = Instant::now();
-    //let elapsed = start.elapsed();
     let (_trigger, tripwire) = Tripwire::new();
-    let cycle_interval = Interval::new_interval(ctrl_loop.config.cycle_time);
-    let ctrl_loop_task = cycle_interval
-        .map_err(|err| {
-            log::error!("Aborting control loop after timer error: {:?}", err);
-        })
-        .take_until(tripwire)
-        .fold(ctrl_loop, |ctrl_loop, _event| {
-            // Asynchronous chain of measurements. The control loop
-            // is consumed and returned upon each step to update the
-            // measurement after reading a new value asynchronously.
-            futures::future::ok(ctrl_loop)
-                .and_then(ControlLoop::measure_any)
-                .then(|res| match res {
-                    Ok(mut ctrl_loop) => {
-                        //write_to_csv(ctrl_loop.measurements.clone());
-
-                        //let elapsed = start.elapsed();
-
-                        //println!("Elapsed time: {:?}", elapsed);
-
-                        if let Some(generic) = ctrl_loop.measurements.generic.take() {
-                            println!("Generic: {:?}", generic);
-                        } else if let Some(register) = ctrl_loop.measurements.register.take() {
-                            println!("Register: {:?}", register);
-                        } else if let Some(float) = ctrl_loop.measurements.float.take() {
-                            println!("Float: {:?}", float);
+    let ctrl_loop_tasks = ctrl_loops.into_iter().map(|ctrl_loop| {
+        let cycle_interval = Interval::new_interval(ctrl_loop.config.cycle_time);
+        cycle_interval
+            .map_err(|err| {
+                log::error!("Aborting control loop after timer error: {:?}", err);
+            })
+            .take_until(tripwire.clone())
+            .fold(ctrl_loop, |ctrl_loop, _event| {
+                // Asynchronous chain of measurements. The control loop
+                // is consumed and returned upon each step to update the
+                // measurement after reading a new value asynchronously.
+                futures::future::ok(ctrl_loop)
+                    .and_then(ControlLoop::measure_all)
+                    .then(|res| match res {
+                        Ok(ctrl_loop) => {
+                            //let elapsed = start.elapsed();
+
+                            //println!("Elapsed time: {:?}", elapsed);
+
+                            Either::A(futures::future::ok(ctrl_loop))
                         }
-                        //println!("Some {:?}", ctrl_loop.measurements);
-                        //log::info!("{:?}", ctrl_loop.measurements);
-                        ctrl_loop.config.next(); //increment the modbus reg read index
-                        Either::A(futures::future::ok(ctrl_loop))
-                    }
-                    Err((err, mut ctrl_loop)) => {
-                        log::info!("{:?}", ctrl_loop.measurements);
-                        ctrl_loop.config.next();
-                        Either::B(ctrl_loop.recover_after_error(&err).map(|()| ctrl_loop))
-                    }
-                })
-        });
+                        Err((err, ctrl_loop)) => {
+                            Either::B(ctrl_loop.recover_after_error(&err).map(|()| ctrl_loop))
+                        }
+                    })
+            })
+    });
 
-    core.run(ctrl_loop_task).unwrap();
+    core.run(futures::future::join_all(ctrl_loop_tasks))
+        .unwrap();
 }
 
 /*#[cfg(not(feature = "modbus-rtu"))]
@@ -0,0 +1,528 @@
+//! Blocking Modbus RTU client that needs neither futures nor a reactor,
+//! e.g. for batch scripts and small command-line utilities.
+//!
+//! Requests are sent one at a time and each call waits for the response
+//! until its timeout expires. Failed requests return an `error::Error`
+//! with the request.
+
+use crate::{
+    buildmap::{BitDef, Confirm, DeviceProfile, RegisterDef},
+    core::{
+        modbus::{regs, rtu::*, *},
+        units::{Unit, UnitValue},
+        Capabilities, RawCounts, RelativePermittivity, Temperature, VolumetricWaterContent,
+    },
+    error::{Error, Result, Target},
+};
+
+use serialport::{ClearBuffer, SerialPort, SerialPortSettings};
+use std::{
+    cell::{Cell, RefCell},
+    ffi::OsStr,
+    io::{self, ErrorKind, Read, Write},
+    net::TcpStream,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The timeout of requests if none is given.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+pub const SERIAL_PORT_SETTINGS: SerialPortSettings = SerialPortSettings {
+    baud_rate: BAUD_RATE,
+    data_bits: DATA_BITS,
+    flow_control: FLOW_CONTROL,
+    parity: PARITY,
+    stop_bits: STOP_BITS,
+    timeout: DEFAULT_TIMEOUT,
+};
+
+/// A byte stream that carries RTU frames.
+pub trait Transport: Read + Write {
+    /// Set the timeout of the next read.
+    fn set_timeout(&mut self, timeout: Duration) -> io::Result<()>;
+
+    /// Discard bytes that have been received but not read yet, e.g. a
+    /// late response to a request that has timed out.
+    fn clear_input(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for Box<dyn SerialPort> {
+    fn set_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        SerialPort::set_timeout(self.as_mut(), timeout)?;
+        Ok(())
+    }
+
+    fn clear_input(&mut self) -> io::Result<()> {
+        self.clear(ClearBuffer::Input)?;
+        Ok(())
+    }
+}
+
+/// RTU frames over TCP, e.g. to a transparent serial device server.
+impl Transport for TcpStream {
+    fn set_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        self.set_read_timeout(Some(timeout))
+    }
+}
+
+/// Synchronous client for a single slave.
+///
+/// The transport is borrowed mutably during each request, so all
+/// methods take `&self` like `modbus::SlaveProxy`.
+#[derive(Debug)]
+pub struct Client<T> {
+    transport: RefCell<T>,
+    slave: u8,
+    byte_order: ByteOrder,
+    double_byte_order: ByteOrder,
+    profile: Option<DeviceProfile>,
+    timeout: Duration,
+    frame_delay: Duration,
+    last_frame: Cell<Option<Instant>>,
+}
+
+impl Client<Box<dyn SerialPort>> {
+    /// Open a serial port with `SERIAL_PORT_SETTINGS`.
+    pub fn open(path: impl AsRef<OsStr>, slave: u8) -> Result<Self> {
+        log::info!("Opening serial port {}", path.as_ref().to_string_lossy());
+        let mut port = serialport::open_with_settings(path.as_ref(), &SERIAL_PORT_SETTINGS)
+            .map_err(io::Error::from)?;
+        //set the DTR pin
+        port.write_data_terminal_ready(true)
+            .map_err(io::Error::from)?;
+        Ok(Self::new(port, slave))
+    }
+}
+
+impl<T: Transport> Client<T> {
+    pub fn new(transport: T, slave: u8) -> Self {
+        Self {
+            transport: RefCell::new(transport),
+            slave,
+            byte_order: ByteOrder::default(),
+            double_byte_order: ByteOrder::default(),
+            profile: None,
+            timeout: DEFAULT_TIMEOUT,
+            frame_delay: inter_frame_delay(BAUD_RATE),
+            last_frame: Cell::new(None),
+        }
+    }
+
+    pub fn with_byte_order(mut self, byte_order: ByteOrder) -> Self {
+        self.byte_order = byte_order;
+        self
+    }
+
+    pub fn with_double_byte_order(mut self, byte_order: ByteOrder) -> Self {
+        self.double_byte_order = byte_order;
+        self
+    }
+
+    pub fn with_profile(mut self, profile: Option<DeviceProfile>) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// The timeout of requests without an explicit timeout.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The silent interval between frames if the transport does not
+    /// run at `BAUD_RATE`.
+    pub fn with_frame_delay(mut self, frame_delay: Duration) -> Self {
+        self.frame_delay = frame_delay;
+        self
+    }
+
+    pub fn slave(&self) -> u8 {
+        self.slave
+    }
+
+    pub fn set_slave(&mut self, slave: u8) {
+        self.slave = slave;
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport.into_inner()
+    }
+
+    /// Read the byte orders of F32 and F64 registers from the device,
+    /// see `SlaveProxy::connect()`.
+    ///
+    /// The default byte orders are kept if they cannot be read.
+    pub fn read_byte_orders(&mut self, timeout: Option<Duration>) {
+        for (reg, double) in &[(BYTE_ORDER_REG, false), (DOUBLE_BYTE_ORDER_REG, true)] {
+            let res = self
+                .read_words(timeout, reg.protocol_addr(), 1)
+                .and_then(|words| Ok(ByteOrder::from_code(words[0])?));
+            match res {
+                Ok(byte_order) if *double => self.double_byte_order = byte_order,
+                Ok(byte_order) => self.byte_order = byte_order,
+                Err(err) => log::warn!("Failed to read register {}: {}", reg.addr(), err),
+            }
+        }
+    }
+
+    /// Send a request and return the decoded response.
+    fn call(&self, timeout: Option<Duration>, request: &Request<'_>) -> Result<Response> {
+        self.transact(timeout, request)
+            .map_err(|err| Error::from(err).with_target(Target::new(self.slave, request)))
+    }
+
+    fn transact(&self, timeout: Option<Duration>, request: &Request<'_>) -> io::Result<Response> {
+        let mut frame = [0; MAX_FRAME_LEN];
+        let len = request.encode_frame(self.slave, &mut frame)?;
+
+        let deadline = Instant::now() + timeout.unwrap_or(self.timeout);
+        let mut transport = self.transport.borrow_mut();
+        transport.clear_input()?;
+        // Keep the silent interval after the previous frame
+        if let Some(last_frame) = self.last_frame.get() {
+            if let Some(delay) = self.frame_delay.checked_sub(last_frame.elapsed()) {
+                thread::sleep(delay);
+            }
+        }
+        transport.write_all(&frame[..len])?;
+        transport.flush()?;
+        self.last_frame.set(Some(Instant::now()));
+
+        let mut response = [0; MAX_FRAME_LEN];
+        read_exact_until(
+            &mut *transport,
+            deadline,
+            &mut response[..RESPONSE_HEADER_LEN],
+        )?;
+        let len = response_frame_len(request.function(), &response).map_err(|_| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Unexpected function code {} in response", response[1]),
+            )
+        })?;
+        read_exact_until(
+            &mut *transport,
+            deadline,
+            &mut response[RESPONSE_HEADER_LEN..len],
+        )?;
+        self.last_frame.set(Some(Instant::now()));
+
+        let (slave, pdu) =
+            decode_frame(&response[..len]).map_err(|_| Error::Crc { target: None })?;
+        if !is_response_from(self.slave, slave) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Invalid response header: expected slave {}, actual slave {}",
+                    self.slave, slave
+                ),
+            ));
+        }
+        Ok(decode_response(request, pdu)?)
+    }
+
+    /// Read holding registers starting at the 0-based protocol address.
+    pub fn read_words(&self, timeout: Option<Duration>, addr: u16, count: u16) -> Result<Vec<u16>> {
+        match self.call(timeout, &Request::ReadHoldingRegisters { addr, count })? {
+            Response::ReadHoldingRegisters(words) => Ok(words.to_vec()),
+            _ => Err(DecodeError::InvalidData.into()),
+        }
+    }
+
+    /// Read input registers starting at the 0-based protocol address.
+    pub fn read_input_registers(
+        &self,
+        timeout: Option<Duration>,
+        addr: u16,
+        count: u16,
+    ) -> Result<Vec<u16>> {
+        match self.call(timeout, &Request::ReadInputRegisters { addr, count })? {
+            Response::ReadInputRegisters(words) => Ok(words.to_vec()),
+            _ => Err(DecodeError::InvalidData.into()),
+        }
+    }
+
+    /// Read coils starting at the 0-based protocol address.
+    pub fn read_coils(
+        &self,
+        timeout: Option<Duration>,
+        addr: u16,
+        count: u16,
+    ) -> Result<Vec<bool>> {
+        match self.call(timeout, &Request::ReadCoils { addr, count })? {
+            Response::ReadCoils(bits) => Ok(bits.iter().collect()),
+            _ => Err(DecodeError::InvalidData.into()),
+        }
+    }
+
+    /// Read discrete inputs starting at the 0-based protocol address.
+    pub fn read_discrete_inputs(
+        &self,
+        timeout: Option<Duration>,
+        addr: u16,
+        count: u16,
+    ) -> Result<Vec<bool>> {
+        match self.call(timeout, &Request::ReadDiscreteInputs { addr, count })? {
+            Response::ReadDiscreteInputs(bits) => Ok(bits.iter().collect()),
+            _ => Err(DecodeError::InvalidData.into()),
+        }
+    }
+
+    /// Read a coil or discrete input, see `SlaveProxy::read_bit()`.
+    pub fn read_bit(&self, timeout: Option<Duration>, def: &BitDef) -> Result<bool> {
+        let bits = self.call(timeout, &def.read_request())?.into_bits()?;
+        Ok(bits.get(0).ok_or(DecodeError::InsufficientInput)?)
+    }
+
+    /// Switch a coil on or off, see `SlaveProxy::write_coil()`.
+    pub fn write_coil(
+        &self,
+        timeout: Option<Duration>,
+        def: &BitDef,
+        state: bool,
+        confirm: Confirm,
+    ) -> Result<()> {
+        self.call(timeout, &def.write_request(self.profile, state, confirm)?)?;
+        Ok(())
+    }
+
+    /// Write the raw words of a register, see
+    /// `SlaveProxy::write_register()`.
+    pub fn write_register(
+        &self,
+        timeout: Option<Duration>,
+        def: &RegisterDef,
+        words: &[u16],
+        confirm: Confirm,
+    ) -> Result<()> {
+        self.call(timeout, &def.write_request(self.profile, words, confirm)?)?;
+        Ok(())
+    }
+
+    fn byte_order_of(&self, reg_type: RegType) -> ByteOrder {
+        match reg_type {
+            RegType::F64 => self.double_byte_order,
+            _ => self.byte_order,
+        }
+    }
+
+    /// Read and decode a register, see `SlaveProxy::read_value()`.
+    pub fn read_value(&self, timeout: Option<Duration>, def: &RegisterDef) -> Result<Value> {
+        let words = self.call(timeout, &def.read_request())?.into_words()?;
+        Ok(def.decode(&words, self.byte_order_of(def.reg_type))?)
+    }
+
+    /// Encode, write and verify a register, see `SlaveProxy::write_value()`.
+    pub fn write_value(
+        &self,
+        timeout: Option<Duration>,
+        def: &RegisterDef,
+        value: &Value,
+        confirm: Confirm,
+    ) -> Result<Value> {
+        let byte_order = self.byte_order_of(def.reg_type);
+        let words = def.encode(value, byte_order)?;
+        self.write_register(timeout, def, &words, confirm)?;
+        if !def.verifies_write(self.profile) {
+            return Ok(value.clone());
+        }
+        let read_back = self.call(timeout, &def.read_request())?.into_words()?;
+        def.verify_write(&words, &read_back, byte_order)
+    }
+
+    fn read_f32(&self, timeout: Option<Duration>, reg: regs::RegDef<f32>) -> Result<f32> {
+        let words = self.read_words(timeout, reg.protocol_addr(), reg.word_count())?;
+        Ok(decode_f32(&words, self.byte_order)?)
+    }
+}
+
+fn read_exact_until<T: Transport + ?Sized>(
+    transport: &mut T,
+    deadline: Instant,
+    mut buf: &mut [u8],
+) -> io::Result<()> {
+    while !buf.is_empty() {
+        let timeout = deadline
+            .checked_duration_since(Instant::now())
+            .filter(|timeout| *timeout > Duration::from_millis(0))
+            .ok_or_else(|| io::Error::new(ErrorKind::TimedOut, "reading timed out"))?;
+        transport.set_timeout(timeout)?;
+        match transport.read(buf) {
+            Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+            Ok(n) => buf = &mut buf[n..],
+            Err(err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err)
+                if err.kind() == ErrorKind::WouldBlock || err.kind() == ErrorKind::TimedOut =>
+            {
+                return Err(io::Error::new(ErrorKind::TimedOut, "reading timed out"))
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+/// The temperature is converted from the temperature unit of the
+/// device and the water content is the net water cut of the net oil
+/// application. Permittivity and raw counts are not available.
+impl<T: Transport> Capabilities for Client<T> {
+    type ReadError = Error;
+
+    fn read_temperature(&self, timeout: Option<Duration>) -> Result<Temperature> {
+        let value = self.read_f32(timeout, regs::TEMPERATURE)?;
+        let unit_reg = regs::TEMPERATURE_UNIT;
+        let code = self.read_words(timeout, unit_reg.protocol_addr(), 1)?[0];
+        let celsius = Unit::from_symbol("degC").expect("unit table");
+        UnitValue::new(value, Unit::from_code(code))
+            .convert(celsius)
+            .map(|value| Temperature::from_degree_celsius(value.value))
+            .ok_or_else(|| {
+                log::warn!("Invalid temperature unit {}", code);
+                DecodeError::InvalidData.into()
+            })
+    }
+
+    fn read_water_content(&self, timeout: Option<Duration>) -> Result<VolumetricWaterContent> {
+        let percent = self.read_f32(timeout, regs::ACTUAL_NET_WATER_CUT)?;
+        Ok(VolumetricWaterContent::from_percent(f64::from(percent)))
+    }
+
+    fn read_permittivity(&self, _timeout: Option<Duration>) -> Result<RelativePermittivity> {
+        Err(Error::Unsupported {
+            target: None,
+            what: "Permittivity",
+        })
+    }
+
+    fn read_raw_counts(&self, _timeout: Option<Duration>) -> Result<RawCounts> {
+        Err(Error::Unsupported {
+            target: None,
+            what: "Raw counts",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::buildmap::RegisterMap;
+    use std::{net::TcpListener, thread};
+
+    fn frame(adu: &[u8]) -> Vec<u8> {
+        let mut frame = adu.to_vec();
+        frame.extend_from_slice(&crc16(adu).to_le_bytes());
+        frame
+    }
+
+    type Server = thread::JoinHandle<(Vec<Vec<u8>>, TcpStream)>;
+
+    /// Answer each 8-byte request with the next response and return
+    /// the requests together with the open connection.
+    fn serve(responses: Vec<Vec<u8>>) -> (TcpStream, Server) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut requests = Vec::new();
+            for response in responses {
+                let mut request = vec![0; 8];
+                stream.read_exact(&mut request).unwrap();
+                requests.push(request);
+                stream.write_all(&response).unwrap();
+            }
+            (requests, stream)
+        });
+        (stream, server)
+    }
+
+    #[test]
+    fn read_temperature() {
+        // 77 °F with byte order 3-4-1-2
+        let (stream, server) = serve(vec![
+            frame(&[0x6F, 0x03, 0x04, 0x00, 0x00, 0x42, 0x9A]),
+            frame(&[0x6F, 0x03, 0x02, 0x00, 0x21]),
+        ]);
+        let client = Client::new(stream, 0x6F);
+        let temperature = client.read_temperature(None).unwrap();
+        assert!((temperature.to_degree_celsius() - 25.0).abs() < 1e-4);
+        assert_eq!(
+            vec![
+                frame(&[0x6F, 0x03, 0x00, 0xFA, 0x00, 0x02]),
+                frame(&[0x6F, 0x03, 0x00, 0x28, 0x00, 0x01]),
+            ],
+            server.join().unwrap().0
+        );
+    }
+
+    #[test]
+    fn coils() {
+        let (stream, server) = serve(vec![
+            frame(&[0x01, 0x05, 0x00, 0x02, 0xFF, 0x00]),
+            frame(&[0x01, 0x01, 0x01, 0x01]),
+            frame(&[0x01, 0x02, 0x01, 0x00]),
+        ]);
+        let map = RegisterMap::from_reader(
+            "Type,Addr,Register Description\n,00003,Start sensor zero\n,100003,Zero in progress\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        let client = Client::new(stream, 0x01);
+        let zero = map.coil(3).unwrap();
+        client.write_coil(None, zero, true, Confirm::Yes).unwrap();
+        assert!(client.read_bit(None, zero).unwrap());
+        let in_progress = map.discrete_input(3).unwrap();
+        assert!(!client.read_bit(None, in_progress).unwrap());
+        assert!(matches!(
+            client.write_coil(None, in_progress, true, Confirm::Yes),
+            Err(Error::Refused { .. })
+        ));
+        assert_eq!(
+            vec![
+                frame(&[0x01, 0x05, 0x00, 0x02, 0xFF, 0x00]),
+                frame(&[0x01, 0x01, 0x00, 0x02, 0x00, 0x01]),
+                frame(&[0x01, 0x02, 0x00, 0x02, 0x00, 0x01]),
+            ],
+            server.join().unwrap().0
+        );
+    }
+
+    #[test]
+    fn write_and_errors() {
+        let (stream, server) = serve(vec![
+            frame(&[0x01, 0x06, 0x00, 0x9A, 0x00, 0x01]),
+            frame(&[0x01, 0x83, 0x02]),
+            frame(&[0x02, 0x03, 0x02, 0x00, 0x01]),
+            vec![0x01, 0x03, 0x02, 0x00, 0x01, 0x00, 0x00],
+            vec![],
+        ]);
+        let map = RegisterMap::from_reader(
+            "Type,Addr,Register Description\nU16,155,Damping\n".as_bytes(),
+        )
+        .unwrap();
+        let client = Client::new(stream, 0x01).with_timeout(Duration::from_millis(50));
+        client
+            .write_register(None, map.get(155).unwrap(), &[1], Confirm::No)
+            .unwrap();
+        assert_eq!(
+            "Modbus function 3: Illegal data address (slave 1, function 3, address 65534, count 1)",
+            client.read_words(None, 0xFFFE, 1).unwrap_err().to_string()
+        );
+        assert!(matches!(
+            client.read_words(None, 0, 1),
+            Err(Error::Transport { .. })
+        ));
+        assert_eq!(
+            "CRC mismatch (slave 1, function 3, address 0, count 1)",
+            client.read_words(None, 0, 1).unwrap_err().to_string()
+        );
+        assert!(matches!(
+            client.read_words(None, 0, 1),
+            Err(Error::Timeout { .. })
+        ));
+        assert_eq!(5, server.join().unwrap().0.len());
+    }
+}
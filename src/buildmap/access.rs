@@ -0,0 +1,632 @@
+use super::DeviceProfile;
+
+use crate::core::modbus::{alarms::STATUS_REGS, units::unit_reg, RegType};
+
+use std::{fmt, io};
+
+/// Whether a register may be read and/or written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AccessMode {
+    ReadOnly,
+    WriteOnly,
+    ReadWrite,
+}
+
+impl AccessMode {
+    pub fn is_readable(self) -> bool {
+        self != AccessMode::WriteOnly
+    }
+
+    pub fn is_writable(self) -> bool {
+        self != AccessMode::ReadOnly
+    }
+}
+
+impl fmt::Display for AccessMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use AccessMode::*;
+        match self {
+            ReadOnly => write!(f, "R"),
+            WriteOnly => write!(f, "W"),
+            ReadWrite => write!(f, "RW"),
+        }
+    }
+}
+
+/// What happens on the device when a register is written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SideEffect {
+    /// Clears totals, inventories or counters, e.g. "Writing to this
+    /// register clears this total".
+    Clear,
+    /// Resets totals, batches or the device, e.g. "Reset Mass Total".
+    Reset,
+    /// Acknowledges alarms or alerts.
+    Acknowledge,
+    /// Starts a zero or calibration that replaces the calibration of
+    /// the device, e.g. "Start Sensor Zero".
+    Calibrate,
+    /// Starts or stops a procedure, e.g. "Start discrete batch".
+    Command,
+}
+
+impl SideEffect {
+    /// Writes that irrecoverably discard data on the device.
+    pub fn is_destructive(self) -> bool {
+        match self {
+            SideEffect::Clear | SideEffect::Reset | SideEffect::Calibrate => true,
+            SideEffect::Acknowledge | SideEffect::Command => false,
+        }
+    }
+}
+
+impl fmt::Display for SideEffect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use SideEffect::*;
+        match self {
+            Clear => write!(f, "clear"),
+            Reset => write!(f, "reset"),
+            Acknowledge => write!(f, "acknowledge"),
+            Calibrate => write!(f, "calibrate"),
+            Command => write!(f, "command"),
+        }
+    }
+}
+
+/// Explicit consent for writes with destructive side effects.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Confirm {
+    No,
+    Yes,
+}
+
+/// Access metadata of a register as stated in its description.
+///
+/// Registers without any statement are considered read/write, except
+/// for measured values, see `Access::parse_register()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Access {
+    pub mode: AccessMode,
+    /// Device families for which `mode` does not apply, e.g.
+    /// "(Read only for RFT9739)" or "[5700 RO]".
+    pub overrides: Vec<(DeviceProfile, AccessMode)>,
+    pub side_effect: Option<SideEffect>,
+    /// The only value that may be written, e.g. "Only 0 can be written".
+    pub only_value: Option<u64>,
+}
+
+impl Default for Access {
+    fn default() -> Self {
+        Self {
+            mode: AccessMode::ReadWrite,
+            overrides: Vec::new(),
+            side_effect: None,
+            only_value: None,
+        }
+    }
+}
+
+impl Access {
+    /// Extract the access metadata from a register description.
+    ///
+    /// Side effects of commands like "Reset Mass Total" are only
+    /// recognized for `U16` registers, because timestamps share
+    /// the same wording, e.g. "Reset Time".
+    pub fn parse(reg_type: RegType, description: &str) -> Self {
+        Self::parse_with(description, reg_type == RegType::U16, AccessMode::ReadWrite)
+    }
+
+    /// Extract the access metadata of the holding register at `addr`.
+    ///
+    /// Process variables, status registers, totals, inventories and
+    /// counters are measured by the device and considered read-only
+    /// unless their description states otherwise or a side effect of
+    /// writing, e.g. clearing a total.
+    pub fn parse_register(addr: u16, reg_type: RegType, description: &str) -> Self {
+        let mut access = Self::parse(reg_type, description);
+        let measured = unit_reg(addr).is_some()
+            || STATUS_REGS.contains(&addr)
+            || is_accumulated(reg_type, description);
+        if measured && access.side_effect.is_none() {
+            access.mode = Self::parse_with(description, false, AccessMode::ReadOnly).mode;
+        }
+        access
+    }
+
+    /// Extract the access metadata from a coil description, e.g. the
+    /// side effect of "Reset all totals".
+    pub fn parse_coil(description: &str) -> Self {
+        Self::parse_with(description, true, AccessMode::ReadWrite)
+    }
+
+    /// The access of discrete inputs and input registers.
+    pub fn read_only() -> Self {
+        Self {
+            mode: AccessMode::ReadOnly,
+            ..Default::default()
+        }
+    }
+
+    fn parse_with(description: &str, commands: bool, mode: AccessMode) -> Self {
+        let mut access = Access {
+            mode,
+            ..Default::default()
+        };
+        for clause in clauses(description) {
+            match parse_mode(&clause) {
+                Some((mode, Qualifier::All)) => access.mode = mode,
+                Some((mode, Qualifier::Profile(profile))) => access.overrides.push((profile, mode)),
+                Some((_, Qualifier::Unknown)) | None => {}
+            }
+        }
+
+        let lower = description.to_lowercase();
+        access.only_value = lower.find("only ").and_then(|pos| {
+            let rest = &lower[pos + "only ".len()..];
+            let digits = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            if digits > 0 && rest[digits..].contains("can be written") {
+                rest[..digits].parse().ok()
+            } else {
+                None
+            }
+        });
+        access.side_effect = if lower.contains("writing to this register clears")
+            || lower.contains("to clear")
+            || lower.contains("cleared/acknowledged by writing")
+        {
+            Some(SideEffect::Clear)
+        } else if lower.contains("to acknowledge") {
+            Some(SideEffect::Acknowledge)
+        } else if commands {
+            // The command is named before any explanation, e.g. "DZDT
+            // Reset (0=Do nothing, 1=capture ...)"
+            let head = lower.split(['(', '[']).next().unwrap_or_default();
+            let mut words = head.split_whitespace();
+            let command = words.next().unwrap_or_default();
+            match command {
+                _ if command == "reset" || words.last() == Some("reset") => Some(SideEffect::Reset),
+                "acknowledge" => Some(SideEffect::Acknowledge),
+                "start" if head.contains("zero") || head.contains("calibrat") => {
+                    Some(SideEffect::Calibrate)
+                }
+                "start" | "stop" | "start/stop" => Some(SideEffect::Command),
+                _ => None,
+            }
+        } else {
+            None
+        };
+        access
+    }
+
+    /// The access mode that applies to `profile`.
+    pub fn mode(&self, profile: Option<DeviceProfile>) -> AccessMode {
+        profile
+            .and_then(|profile| {
+                self.overrides
+                    .iter()
+                    .find(|(p, _)| *p == profile)
+                    .map(|(_, mode)| *mode)
+            })
+            .unwrap_or(self.mode)
+    }
+
+    pub fn is_destructive(&self) -> bool {
+        self.side_effect.is_some_and(SideEffect::is_destructive)
+    }
+
+    /// Check if writing `value` is permitted for `profile`.
+    ///
+    /// Destructive writes are refused unless they are confirmed.
+    pub fn check_write(
+        &self,
+        profile: Option<DeviceProfile>,
+        value: u64,
+        confirm: Confirm,
+    ) -> Result<(), AccessError> {
+        if !self.mode(profile).is_writable() {
+            return Err(AccessError::ReadOnly);
+        }
+        if let Some(only_value) = self.only_value {
+            if value != only_value {
+                return Err(AccessError::InvalidValue { value, only_value });
+            }
+        }
+        match self.side_effect {
+            Some(side_effect) if side_effect.is_destructive() && confirm != Confirm::Yes => {
+                Err(AccessError::Unconfirmed(side_effect))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A write that has been refused by `Access::check_write()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccessError {
+    ReadOnly,
+    /// The register only accepts a single value.
+    InvalidValue {
+        value: u64,
+        only_value: u64,
+    },
+    /// The write has a destructive side effect and needs to be confirmed.
+    Unconfirmed(SideEffect),
+}
+
+impl fmt::Display for AccessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use AccessError::*;
+        match self {
+            ReadOnly => write!(f, "Register is read-only"),
+            InvalidValue { value, only_value } => write!(
+                f,
+                "Invalid value {}, only {} can be written",
+                value, only_value
+            ),
+            Unconfirmed(side_effect) => write!(
+                f,
+                "Writing has a destructive side effect ({}) and must be confirmed",
+                side_effect
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AccessError {}
+
+impl From<AccessError> for io::Error {
+    fn from(from: AccessError) -> Self {
+        use AccessError::*;
+        match from {
+            ReadOnly | Unconfirmed(_) => Self::new(io::ErrorKind::PermissionDenied, from),
+            InvalidValue { .. } => Self::new(io::ErrorKind::InvalidInput, from),
+        }
+    }
+}
+
+/// The lowercase clauses of a description, separated by parentheses,
+/// brackets, semicolons and commas.
+pub(super) fn clauses(description: &str) -> impl Iterator<Item = String> + '_ {
+    description
+        .split(['(', ')', '[', ']', ';', ','])
+        .map(|clause| clause.trim().to_lowercase())
+        .filter(|clause| !clause.is_empty())
+}
+
+/// Whether a register holds a total, an inventory or a counter that the
+/// device accumulates, e.g. "Mass Inventory - Double - user units" or
+/// "Core Power Cycle Count (Set to 0 on master reset)".
+///
+/// Registers that only refer to totals, e.g. "Mass flow cutoff for
+/// digital outputs and internal totals" or "Mass Total Units", are
+/// configuration.
+fn is_accumulated(reg_type: RegType, description: &str) -> bool {
+    const CONFIG: [&str; 9] = [
+        "cutoff",
+        "factor",
+        "offset",
+        "override",
+        "rate",
+        "setting",
+        "threshold",
+        "used",
+        "zero",
+    ];
+    let is_total = |word: &str| {
+        word.ends_with("total")
+            || word.ends_with("totals")
+            || ["inventory", "invemtory"].contains(&word)
+    };
+    let lower = description.to_lowercase();
+    if lower.contains("set to 0 on master reset") {
+        return true;
+    }
+    let words: Vec<&str> = lower
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect();
+    // "Mass Total Units" but not "Mass Total - Double - user units"
+    let total_unit = words
+        .windows(2)
+        .any(|pair| is_total(pair[0]) && ["unit", "units"].contains(&pair[1]));
+    if total_unit || words.iter().any(|word| CONFIG.contains(word)) {
+        return false;
+    }
+    let total =
+        matches!(reg_type, RegType::F32 | RegType::F64) && words.iter().any(|word| is_total(word));
+    // Counters are named by the head of the description, e.g. "Alarmn
+    // Count (inactive to active transitions)" but not "DDC Trigger Count
+    // of Triggered Events"
+    let head = lower.split(['(', '[']).next().unwrap_or_default();
+    let counter = matches!(head.split_whitespace().last(), Some("count" | "counter"))
+        || clauses(description).any(|clause| clause == "count");
+    total || counter
+}
+
+/// Whether a clause only states the access mode, e.g. "read only for
+/// rft9739", see `clauses()`.
+pub(super) fn is_access_clause(clause: &str) -> bool {
+    parse_mode(clause).is_some()
+}
+
+enum Qualifier {
+    All,
+    Profile(DeviceProfile),
+    /// Restricted to something other than a known device family,
+    /// e.g. "RW for v1.x" or "Read Only for CDM".
+    Unknown,
+}
+
+/// Parse a clause that consists of an access statement only, e.g.
+/// "read only for rft9739", "5700 ro", "gen5- write only" or
+/// "ro for others".
+fn parse_mode(clause: &str) -> Option<(AccessMode, Qualifier)> {
+    const MODES: [(&str, AccessMode); 7] = [
+        ("read only", AccessMode::ReadOnly),
+        ("read-only", AccessMode::ReadOnly),
+        ("ro", AccessMode::ReadOnly),
+        ("write only", AccessMode::WriteOnly),
+        ("write-only", AccessMode::WriteOnly),
+        ("wo", AccessMode::WriteOnly),
+        ("rw", AccessMode::ReadWrite),
+    ];
+    for (keyword, mode) in MODES.iter() {
+        if let Some(rest) = clause.strip_prefix(keyword) {
+            if rest.is_empty() {
+                return Some((*mode, Qualifier::All));
+            }
+            if let Some(target) = rest.strip_prefix(" for ") {
+                let qualifier = match target.trim() {
+                    "others" => Qualifier::All,
+                    target => qualify(target),
+                };
+                return Some((*mode, qualifier));
+            }
+        }
+        if let Some(device) = clause.strip_suffix(keyword) {
+            let device = device.trim_end_matches(|c: char| c.is_whitespace() || c == '-');
+            if let Ok(profile) = device.parse() {
+                return Some((*mode, Qualifier::Profile(profile)));
+            }
+        }
+    }
+    None
+}
+
+fn qualify(device: &str) -> Qualifier {
+    match device.parse() {
+        Ok(profile) => Qualifier::Profile(profile),
+        Err(_) => Qualifier::Unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_access_modes() {
+        let access = Access::parse(
+            RegType::U16,
+            "Mass total (Scaled Int)  (Writing to this register clears this total) (Read only for RFT9739)",
+        );
+        assert_eq!(AccessMode::ReadWrite, access.mode(None));
+        assert_eq!(
+            AccessMode::ReadOnly,
+            access.mode(Some(DeviceProfile::Rft9739))
+        );
+        assert_eq!(Some(SideEffect::Clear), access.side_effect);
+
+        let access = Access::parse(
+            RegType::U16,
+            "Flow direction  (see flow direction codes)  Gen5- Write Only, if written will attempt to initialize",
+        );
+        assert_eq!(
+            AccessMode::WriteOnly,
+            access.mode(Some(DeviceProfile::Gen5))
+        );
+        assert_eq!(AccessMode::ReadWrite, access.mode(None));
+
+        let access = Access::parse(RegType::U16, "Real-time clock: seconds (0-59) [5700 RO]");
+        assert_eq!(AccessMode::ReadOnly, access.mode(Some(DeviceProfile::Gen5)));
+
+        let access = Access::parse(
+            RegType::F32,
+            "Superior Calorific Value (RW for 5700; RO for others)",
+        );
+        assert_eq!(AccessMode::ReadOnly, access.mode(None));
+        assert_eq!(
+            AccessMode::ReadWrite,
+            access.mode(Some(DeviceProfile::Gen5))
+        );
+
+        for description in [
+            "Altus-Compatibility Tempcal Slope (Read Only for CDM)",
+            "http: port - RW for v1.x, RO for v2.0 and above",
+            "DDC Trigger Index  ( 0-7 valid, Triggers 0-2 are predefined and read-only)",
+            "Mass flow rate",
+        ]
+        .iter()
+        {
+            assert_eq!(Access::default(), Access::parse(RegType::U16, description));
+        }
+    }
+
+    #[test]
+    fn measured_registers() {
+        let access = Access::parse_register(247, RegType::F32, "Mass flow rate");
+        assert_eq!(AccessMode::ReadOnly, access.mode(None));
+        assert_eq!(
+            Err(AccessError::ReadOnly),
+            access.check_write(None, 0, Confirm::Yes)
+        );
+        let access =
+            Access::parse_register(419, RegType::U16, "Bit #0 – Core EEPROM Checksum Error");
+        assert_eq!(AccessMode::ReadOnly, access.mode(None));
+
+        // Writing clears the total
+        let access = Access::parse_register(
+            8,
+            RegType::U16,
+            "Mass total (Scaled Int)  (Writing to this register clears this total) (Read only for RFT9739)",
+        );
+        assert_eq!(AccessMode::ReadWrite, access.mode(None));
+        assert_eq!(
+            AccessMode::ReadOnly,
+            access.mode(Some(DeviceProfile::Rft9739))
+        );
+
+        let access = Access::parse_register(154, RegType::U16, "Start Sensor Zero");
+        assert_eq!(AccessMode::ReadWrite, access.mode(None));
+
+        // Totals, inventories and counters
+        for (reg_type, description) in [
+            (RegType::F64, "Mass Inventory - Double - user units"),
+            (RegType::F64, "Most significant word for binary mass total"),
+            (
+                RegType::F32,
+                "Snapshot Contract Period #1 \"Today's\" (Current) Total",
+            ),
+            (
+                RegType::U16,
+                "Core Power On Time (Set to 0 on master reset)  (units of 2 hours)",
+            ),
+            (
+                RegType::U16,
+                "Alarmn Count (inactive to active transitions)",
+            ),
+        ]
+        .iter()
+        {
+            let access = Access::parse_register(4200, *reg_type, description);
+            assert_eq!(AccessMode::ReadOnly, access.mode(None), "{}", description);
+        }
+        for (reg_type, description) in [
+            (
+                RegType::F32,
+                "Mass flow cutoff for digital outputs and internal totals",
+            ),
+            (RegType::F32, "Dead Volume (Mass Total Units)"),
+            (RegType::U16, "Reset Mass Total"),
+            (RegType::U16, "conversion count setting - UP"),
+            (RegType::F32, "Override Peak Detect Count (0 = No Override)"),
+        ]
+        .iter()
+        {
+            let access = Access::parse_register(4200, *reg_type, description);
+            assert_eq!(AccessMode::ReadWrite, access.mode(None), "{}", description);
+        }
+        let access = Access::parse_register(
+            7342,
+            RegType::U16,
+            "logged number of press counts UP (write any value to clear to 0)",
+        );
+        assert_eq!(AccessMode::ReadWrite, access.mode(None));
+        assert_eq!(Some(SideEffect::Clear), access.side_effect);
+    }
+
+    #[test]
+    fn parse_side_effects() {
+        let access = Access::parse(
+            RegType::U16,
+            "Configuration Change Counter incremented for all config writes.  Only 0 can be written (to clear it)",
+        );
+        assert_eq!(Some(0), access.only_value);
+        assert!(access.is_destructive());
+
+        let access = Access::parse(RegType::U16, "Reset Mass Total   (G5: Config Total #1)");
+        assert_eq!(Some(SideEffect::Reset), access.side_effect);
+        assert_eq!(
+            Some(SideEffect::Reset),
+            Access::parse(RegType::U16, "Force Reset").side_effect
+        );
+        assert_eq!(
+            Some(SideEffect::Reset),
+            Access::parse(
+                RegType::U16,
+                "DZDT Reset (0=Do nothing, 1=capture DZDT Delta T and load into starting point)"
+            )
+            .side_effect
+        );
+        assert_eq!(
+            None,
+            Access::parse(
+                RegType::U16,
+                "Totalizer Reset Option for Custody Transfer (see Reset Option codes)"
+            )
+            .side_effect
+        );
+        assert_eq!(None, Access::parse(RegType::U32, "Reset Time").side_effect);
+        assert_eq!(
+            Some(SideEffect::Acknowledge),
+            Access::parse(RegType::U16, "Alarmn Status (write 0 to acknowledge alarm)").side_effect
+        );
+        let access = Access::parse(RegType::U16, "Start Sensor Zero");
+        assert_eq!(Some(SideEffect::Calibrate), access.side_effect);
+        assert_eq!(
+            Err(AccessError::Unconfirmed(SideEffect::Calibrate)),
+            access.check_write(None, 1, Confirm::No)
+        );
+        let access = Access::parse(RegType::U16, "Start discrete batch");
+        assert_eq!(Some(SideEffect::Command), access.side_effect);
+        assert!(!access.is_destructive());
+        assert_eq!(
+            Some(1),
+            Access::parse(
+                RegType::U16,
+                "Fieldbus / Profibus PA AI L Type (Only 1=Direct can be written)"
+            )
+            .only_value
+        );
+    }
+
+    #[test]
+    fn parse_coils() {
+        let access = Access::parse_coil("Reset all totals");
+        assert_eq!(Some(SideEffect::Reset), access.side_effect);
+        assert!(access.is_destructive());
+        let access =
+            Access::parse_coil("Start temperature slope/offset calibration (see register 151)");
+        assert_eq!(Some(SideEffect::Calibrate), access.side_effect);
+        assert!(access.is_destructive());
+        let access = Access::read_only();
+        assert_eq!(
+            Err(AccessError::ReadOnly),
+            access.check_write(None, 1, Confirm::Yes)
+        );
+    }
+
+    #[test]
+    fn check_writes() {
+        let access = Access::parse(
+            RegType::U16,
+            "Mass total (Scaled Int)  (Writing to this register clears this total) (Read only for RFT9739)",
+        );
+        assert_eq!(
+            Err(AccessError::ReadOnly),
+            access.check_write(Some(DeviceProfile::Rft9739), 0, Confirm::Yes)
+        );
+        assert_eq!(
+            Err(AccessError::Unconfirmed(SideEffect::Clear)),
+            access.check_write(None, 0, Confirm::No)
+        );
+        assert_eq!(Ok(()), access.check_write(None, 0, Confirm::Yes));
+
+        let access = Access::parse(
+            RegType::U16,
+            "Counter.  Only 0 can be written (to clear it)",
+        );
+        assert_eq!(
+            Err(AccessError::InvalidValue {
+                value: 1,
+                only_value: 0
+            }),
+            access.check_write(None, 1, Confirm::Yes)
+        );
+
+        let access = Access::parse(RegType::F32, "Mass flow cutoff");
+        assert_eq!(Ok(()), access.check_write(None, 0, Confirm::No));
+    }
+}
@@ -0,0 +1,290 @@
+use std::fmt;
+
+/// Named values of an enumerated register, e.g. `(0=Idle, 1=Running, 2=Paused)`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EnumDef {
+    pub variants: Vec<(u64, String)>,
+}
+
+impl EnumDef {
+    pub fn name(&self, value: u64) -> Option<&str> {
+        self.variants
+            .iter()
+            .find(|(v, _)| *v == value)
+            .map(|(_, name)| name.as_str())
+    }
+
+    /// Reverse lookup of a value by its case-insensitive name.
+    pub fn value(&self, name: &str) -> Option<u64> {
+        self.variants
+            .iter()
+            .find(|(_, n)| n.eq_ignore_ascii_case(name))
+            .map(|(v, _)| *v)
+    }
+}
+
+/// Named bits of a status register, e.g. `Bit #0 – Batch In Progress`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BitfieldDef {
+    pub bits: Vec<(u8, String)>,
+}
+
+impl BitfieldDef {
+    pub fn name(&self, bit: u8) -> Option<&str> {
+        self.bits
+            .iter()
+            .find(|(b, _)| *b == bit)
+            .map(|(_, name)| name.as_str())
+    }
+
+    /// All bits that are set in `raw`, including those without a name.
+    pub fn active(&self, raw: u64) -> Vec<Flag<'_>> {
+        (0..64)
+            .filter(|bit| raw & (1 << bit) != 0)
+            .map(|bit| Flag {
+                bit,
+                name: self.name(bit),
+            })
+            .collect()
+    }
+
+    fn insert(&mut self, bit: u8, name: String) {
+        match self.bits.binary_search_by_key(&bit, |(b, _)| *b) {
+            Ok(idx) => self.bits[idx].1 = name,
+            Err(idx) => self.bits.insert(idx, (bit, name)),
+        }
+    }
+}
+
+/// Structured meaning of the value of an integer register.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FieldDef {
+    Enum(EnumDef),
+    Bitfield(BitfieldDef),
+}
+
+impl FieldDef {
+    /// Extract an enumeration or bitfield from a register description.
+    pub fn parse(description: &str) -> Option<Self> {
+        if let Some((bit, name)) = parse_bit(description) {
+            let mut bitfield = BitfieldDef::default();
+            bitfield.insert(bit, name);
+            return Some(FieldDef::Bitfield(bitfield));
+        }
+        parse_enum(description).map(FieldDef::Enum)
+    }
+
+    /// Add a continuation row `Bit #n – ...` to a bitfield.
+    pub(crate) fn add_bit(&mut self, description: &str) -> bool {
+        match (self, parse_bit(description)) {
+            (FieldDef::Bitfield(bitfield), Some((bit, name))) => {
+                bitfield.insert(bit, name);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn decode(&self, raw: u64) -> Symbolic<'_> {
+        match self {
+            FieldDef::Enum(def) => Symbolic::Enum {
+                value: raw,
+                name: def.name(raw),
+            },
+            FieldDef::Bitfield(def) => Symbolic::Flags {
+                raw,
+                active: def.active(raw),
+            },
+        }
+    }
+}
+
+/// An active bit of a status register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Flag<'a> {
+    pub bit: u8,
+    pub name: Option<&'a str>,
+}
+
+impl<'a> fmt::Display for Flag<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name {
+            Some(name) => write!(f, "{}", name),
+            None => write!(f, "Bit #{}", self.bit),
+        }
+    }
+}
+
+/// The value of an enumerated or bitfield register with its names.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Symbolic<'a> {
+    Enum { value: u64, name: Option<&'a str> },
+    Flags { raw: u64, active: Vec<Flag<'a>> },
+}
+
+impl<'a> fmt::Display for Symbolic<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Symbolic::Enum {
+                name: Some(name), ..
+            } => write!(f, "{}", name),
+            Symbolic::Enum { value, name: None } => write!(f, "{} (unknown)", value),
+            Symbolic::Flags { active, .. } if active.is_empty() => write!(f, "none"),
+            Symbolic::Flags { active, .. } => {
+                for (i, flag) in active.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", flag)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Parse `Bit #3 – Name` into the bit number and its name.
+fn parse_bit(description: &str) -> Option<(u8, String)> {
+    let rest = description.trim_start().strip_prefix("Bit #")?;
+    let digits = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    let bit: u8 = rest[..digits].parse().ok().filter(|bit| *bit < 64)?;
+    let name = rest[digits..]
+        .trim_start_matches(|c: char| c.is_whitespace() || c == '–' || c == '-' || c == ':')
+        .trim();
+    Some((bit, name.to_string()))
+}
+
+fn parse_int(s: &str) -> Option<u64> {
+    let s = s.trim();
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16).ok()
+    } else if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) {
+        s.parse().ok()
+    } else {
+        None
+    }
+}
+
+/// Parse a single `value=Name` or `Name=value` pair.
+fn parse_pair(part: &str) -> Option<(u64, String)> {
+    let (lhs, rhs) = part.split_once('=')?;
+    let (lhs, rhs) = (lhs.trim(), rhs.trim());
+    if lhs.is_empty() || rhs.is_empty() {
+        return None;
+    }
+    if let Some(value) = parse_int(lhs) {
+        Some((value, rhs.to_string()))
+    } else {
+        parse_int(rhs).map(|value| (value, lhs.to_string()))
+    }
+}
+
+/// Find the first parenthesized or bracketed group with at least two
+/// value/name pairs, e.g. `(0=Idle, 1=Running)` or `(high=1/low=2)`.
+fn parse_enum(description: &str) -> Option<EnumDef> {
+    groups(description).into_iter().find_map(|group| {
+        [&[',', ';'][..], &[',', ';', '/'][..]]
+            .iter()
+            .find_map(|separators| {
+                let variants = group
+                    .split(*separators)
+                    .filter(|part| !part.trim().is_empty())
+                    .map(parse_pair)
+                    .collect::<Option<Vec<_>>>()?;
+                if variants.len() >= 2 {
+                    Some(EnumDef { variants })
+                } else {
+                    None
+                }
+            })
+    })
+}
+
+/// The innermost contents of all `(...)` and `[...]` groups.
+fn groups(description: &str) -> Vec<&str> {
+    let mut groups = Vec::new();
+    let mut start = None;
+    for (i, c) in description.char_indices() {
+        match c {
+            '(' | '[' => start = Some(i + c.len_utf8()),
+            ')' | ']' => {
+                if let Some(start) = start.take() {
+                    groups.push(&description[start..i]);
+                }
+            }
+            _ => {}
+        }
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enum_def(description: &str) -> EnumDef {
+        match FieldDef::parse(description) {
+            Some(FieldDef::Enum(def)) => def,
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_enumerations() {
+        let def = enum_def("Batch State (0=Idle, 1=Running, 2=Paused)");
+        assert_eq!(Some("Running"), def.name(1));
+        assert_eq!(None, def.name(3));
+        assert_eq!(Some(2), def.value("paused"));
+
+        let def = enum_def("Event 1 type (high=1/low=2)");
+        assert_eq!(Some("high"), def.name(1));
+        assert_eq!(Some("low"), def.name(2));
+
+        let def = enum_def("AOC Compensation Algorithm  (Upward=0, Downward=1, Fixed=2)");
+        assert_eq!(Some("Fixed"), def.name(2));
+
+        let def = enum_def("LCD software rotation [ 0=0°,   1=90°,    2=180°,   3=270° ]");
+        assert_eq!(Some("270°"), def.name(3));
+
+        let def =
+            enum_def("Variable Index for Trend Data (0=mass flow; 1=volume flow/GSV; 2=density)");
+        assert_eq!(Some("volume flow/GSV"), def.name(1));
+
+        assert_eq!(
+            None,
+            FieldDef::parse("Slot Override ( 0xFFFF = No Override )")
+        );
+        assert_eq!(None, FieldDef::parse("Mass flow rate (Scaled Int)"));
+    }
+
+    #[test]
+    fn parse_bitfields() {
+        let mut def = FieldDef::parse("Bit #0 – Batch In Progress").unwrap();
+        assert!(def.add_bit("Bit #3 – Batch Paused"));
+        assert!(!def.add_bit("Batch State"));
+        match &def {
+            FieldDef::Bitfield(bits) => {
+                assert_eq!(Some("Batch In Progress"), bits.name(0));
+                assert_eq!(Some("Batch Paused"), bits.name(3));
+            }
+            _ => panic!(),
+        }
+        assert_eq!(
+            "Batch In Progress, Bit #2, Batch Paused",
+            def.decode(0b1101).to_string()
+        );
+        assert_eq!("none", def.decode(0).to_string());
+
+        let def = FieldDef::parse("Bit #0 – Discrete Input 1 Status (0=OFF, 1=ON)").unwrap();
+        assert!(matches!(def, FieldDef::Bitfield(_)));
+    }
+
+    #[test]
+    fn decode_enum() {
+        let def = FieldDef::parse("Batch State (0=Idle, 1=Running, 2=Paused)").unwrap();
+        assert_eq!("Running", def.decode(1).to_string());
+        assert_eq!("7 (unknown)", def.decode(7).to_string());
+    }
+}
@@ -1,37 +1,1058 @@
-use csv::Reader;
-use std::collections::HashMap;
-use std::fs::File;
-
-pub fn build_hashmap(path: &str) -> HashMap<u16, String> {
-    let file: File = File::open(path).unwrap();
-    let mut rdr: Reader<File> = Reader::from_reader(file);
-    let mut hmap: HashMap<u16, String> = HashMap::new();
-    for result in rdr.records() {
-        let record = result.unwrap();
-        let reg_type = record.get(0);
-        //change this to match empty string
-        let reg = match reg_type {
-            Some(reg) => {
-                if "" == reg.to_string() {
-                    "U8".to_string() //default type
-                } else {
-                    reg.to_string()
-                }
+mod access;
+mod fields;
+mod profile;
+mod search;
+
+pub use self::{
+    access::{Access, AccessError, AccessMode, Confirm, SideEffect},
+    fields::{BitfieldDef, EnumDef, FieldDef, Flag, Symbolic},
+    profile::{Ambiguity, DeviceProfile},
+    search::{SearchHit, SearchQuery},
+};
+
+use crate::{
+    core::modbus::{
+        decode, encode, rtu::Request, ByteOrder, DecodeResult, RegType, Table, Value, Words,
+    },
+    error::{self, Error},
+};
+
+use csv::{ReaderBuilder, StringRecord};
+use std::{
+    collections::{btree_map, BTreeMap},
+    fmt,
+    fs::File,
+    io::{self, Read},
+    ops::RangeBounds,
+    path::Path,
+};
+
+/// Errors that occur while loading a Modbus map from CSV.
+#[derive(Debug)]
+pub enum MapError {
+    Io(io::Error),
+    Csv(csv::Error),
+    /// The type column of a row could not be parsed, e.g. `F23`.
+    InvalidType {
+        line: u64,
+        value: String,
+    },
+    /// The address column of a row is neither a valid register number
+    /// nor a reference.
+    InvalidAddress {
+        line: u64,
+        value: String,
+    },
+    /// The optional profile column of a row names an unknown device profile.
+    InvalidProfile {
+        line: u64,
+        value: String,
+    },
+    /// A profile override names no definition of the map, see
+    /// `RegisterMap::apply_profiles()`.
+    UnmatchedProfile {
+        line: u64,
+        addr: u16,
+    },
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use MapError::*;
+        match self {
+            Io(err) => write!(f, "I/O error: {}", err),
+            Csv(err) => write!(f, "CSV error: {}", err),
+            InvalidType { line, value } => {
+                write!(f, "Invalid register type {:?} in line {}", value, line)
             }
-            None => {
-                println!("reg_type: {:?}", reg_type);
-                "U8".to_string() //default type
+            InvalidAddress { line, value } => {
+                write!(f, "Invalid register address {:?} in line {}", value, line)
             }
-        };
+            InvalidProfile { line, value } => {
+                write!(f, "Invalid device profile {:?} in line {}", value, line)
+            }
+            UnmatchedProfile { line, addr } => write!(
+                f,
+                "Register {} in line {} has no definition with this description",
+                addr, line
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MapError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MapError::Io(err) => Some(err),
+            MapError::Csv(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for MapError {
+    fn from(from: io::Error) -> Self {
+        MapError::Io(from)
+    }
+}
+
+impl From<csv::Error> for MapError {
+    fn from(from: csv::Error) -> Self {
+        MapError::Csv(from)
+    }
+}
+
+pub type MapResult<T> = Result<T, MapError>;
+
+/// A single register definition of the Modbus map.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegisterDef {
+    /// Either the holding registers or the input registers.
+    pub table: Table,
+    /// 1-based register number as listed in the map.
+    pub addr: u16,
+    pub reg_type: RegType,
+    pub description: String,
+    /// The device family this definition is specific to, if any.
+    pub profile: Option<DeviceProfile>,
+    /// Enumeration or bitfield of an integer register.
+    pub field: Option<FieldDef>,
+    /// Read/write permissions and side effects of writes.
+    pub access: Access,
+}
+
+impl RegisterDef {
+    /// Number of 16-bit words occupied by this register.
+    pub fn word_count(&self) -> u16 {
+        self.reg_type.word_count()
+    }
+
+    /// Check if writing `words` to this register is permitted for
+    /// `profile`, see `Access::check_write()`.
+    pub fn check_write(
+        &self,
+        profile: Option<DeviceProfile>,
+        words: &[u16],
+        confirm: Confirm,
+    ) -> Result<(), AccessError> {
+        let value = words
+            .iter()
+            .fold(0u64, |value, word| (value << 16) | u64::from(*word));
+        self.access.check_write(profile, value, confirm)
+    }
+
+    /// The 0-based protocol address.
+    pub fn protocol_addr(&self) -> u16 {
+        self.addr - 1
+    }
+
+    /// The request that reads this register from its table.
+    pub fn read_request(&self) -> Request<'static> {
+        let (addr, count) = (self.protocol_addr(), self.word_count());
+        match self.table {
+            Table::InputRegister => Request::ReadInputRegisters { addr, count },
+            _ => Request::ReadHoldingRegisters { addr, count },
+        }
+    }
+
+    /// The request that writes the raw `words` of this register.
+    ///
+    /// Fails if the number of words does not match the register type
+    /// and refuses writes that are not permitted, see `check_write()`.
+    pub fn write_request<'a>(
+        &self,
+        profile: Option<DeviceProfile>,
+        words: &'a [u16],
+        confirm: Confirm,
+    ) -> error::Result<Request<'a>> {
+        if words.len() != usize::from(self.word_count()) {
+            return Err(Error::InvalidInput {
+                target: None,
+                message: format!(
+                    "Register {} expects {} words instead of {}",
+                    self.addr,
+                    self.word_count(),
+                    words.len()
+                ),
+            });
+        }
+        if let Err(err) = self.check_write(profile, words, confirm) {
+            log::warn!("Refused to write register {}: {}", self.addr, err);
+            return Err(err.into());
+        }
+        Ok(Request::write_registers(self.protocol_addr(), words))
+    }
+
+    /// Encode `value` into the words of this register, see
+    /// `core::modbus::encode()`.
+    pub fn encode(&self, value: &Value, byte_order: ByteOrder) -> error::Result<Words> {
+        encode(self.reg_type, value, byte_order).map_err(|err| Error::InvalidInput {
+            target: None,
+            message: format!(
+                "Cannot write {} to {} register {}: {}",
+                value, self.reg_type, self.addr, err
+            ),
+        })
+    }
+
+    /// Whether a written value is read back for verification, i.e.
+    /// the register is readable and writing has no side effect.
+    pub fn verifies_write(&self, profile: Option<DeviceProfile>) -> bool {
+        self.access.mode(profile).is_readable() && self.access.side_effect.is_none()
+    }
+
+    /// Compare the value read back after writing `words` and return
+    /// it.
+    ///
+    /// The decoded values are compared instead of the raw words,
+    /// because devices may pad text with NUL instead of spaces.
+    pub fn verify_write(
+        &self,
+        words: &[u16],
+        read_back: &[u16],
+        byte_order: ByteOrder,
+    ) -> error::Result<Value> {
+        let value = self.decode(read_back, byte_order)?;
+        if read_back != words {
+            let written = self.decode(words, byte_order)?;
+            if value != written {
+                return Err(Error::Mismatch {
+                    target: None,
+                    addr: self.addr,
+                    written: Box::new(written),
+                    read_back: Box::new(value),
+                });
+            }
+        }
+        Ok(value)
+    }
+
+    /// Decode the words of an enumerated or bitfield register into
+    /// named values. The words of multi-word registers are expected
+    /// in big-endian order.
+    pub fn decode_symbolic(&self, words: &[u16]) -> Option<Symbolic<'_>> {
+        let field = self.field.as_ref()?;
+        let words = words.get(..usize::from(self.word_count()))?;
+        let raw = words
+            .iter()
+            .fold(0u64, |raw, word| (raw << 16) | u64::from(*word));
+        Some(field.decode(raw))
+    }
+
+    /// Decode the words of this register, see `core::modbus::decode()`.
+    ///
+    /// Bitfield registers are decoded as `Value::Bits`.
+    pub fn decode(&self, words: &[u16], byte_order: ByteOrder) -> DecodeResult<Value> {
+        let value = decode(self.reg_type, words, byte_order)?;
+        match self.field {
+            Some(FieldDef::Bitfield(_)) => Ok(value.as_u64().map_or(value, Value::Bits)),
+            _ => Ok(value),
+        }
+    }
+}
+
+/// A single coil or discrete input of the Modbus map.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BitDef {
+    pub table: Table,
+    /// 1-based number as listed in the map.
+    pub addr: u16,
+    pub description: String,
+    /// The device family this definition is specific to, if any.
+    pub profile: Option<DeviceProfile>,
+    /// Discrete inputs are read-only, writing coils may have side
+    /// effects, e.g. resetting totals.
+    pub access: Access,
+}
 
-        let addr = record.get(1);
-        match addr {
-            Some(a) => {
-                let address = a.to_string().parse::<u16>().ok().unwrap();
-                hmap.entry(address).or_insert(reg);
+impl BitDef {
+    /// Check if switching the coil to `state` is permitted for
+    /// `profile`, see `Access::check_write()`.
+    pub fn check_write(
+        &self,
+        profile: Option<DeviceProfile>,
+        state: bool,
+        confirm: Confirm,
+    ) -> Result<(), AccessError> {
+        self.access.check_write(profile, u64::from(state), confirm)
+    }
+
+    /// The 0-based protocol address.
+    pub fn protocol_addr(&self) -> u16 {
+        self.addr - 1
+    }
+
+    /// The request that reads this coil or discrete input.
+    pub fn read_request(&self) -> Request<'static> {
+        let (addr, count) = (self.protocol_addr(), 1);
+        match self.table {
+            Table::DiscreteInput => Request::ReadDiscreteInputs { addr, count },
+            _ => Request::ReadCoils { addr, count },
+        }
+    }
+
+    /// The request that switches the coil to `state`.
+    ///
+    /// Refuses writes that are not permitted, see `check_write()`.
+    pub fn write_request(
+        &self,
+        profile: Option<DeviceProfile>,
+        state: bool,
+        confirm: Confirm,
+    ) -> error::Result<Request<'static>> {
+        if let Err(err) = self.check_write(profile, state, confirm) {
+            log::warn!("Refused to write {} {}: {}", self.table, self.addr, err);
+            return Err(err.into());
+        }
+        Ok(Request::WriteSingleCoil {
+            addr: self.protocol_addr(),
+            state,
+        })
+    }
+}
+
+/// Typed lookup table of all registers in a Modbus map, ordered by address.
+///
+/// Addresses with multiple definitions are resolved according to the
+/// selected `DeviceProfile`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RegisterMap {
+    entries: BTreeMap<u16, Vec<RegisterDef>>,
+    input_registers: BTreeMap<u16, Vec<RegisterDef>>,
+    bits: BTreeMap<(Table, u16), Vec<BitDef>>,
+    profile: Option<DeviceProfile>,
+}
+
+impl RegisterMap {
+    /// Load the map from a CSV file with the columns `Type,Addr,Register Description`.
+    pub fn from_path(path: impl AsRef<Path>) -> MapResult<Self> {
+        Self::from_reader(File::open(path)?)
+    }
+
+    /// Load the map from CSV data with the columns `Type,Addr,Register Description`.
+    ///
+    /// Rows without an address are ignored and an empty type defaults to `U8`.
+    /// An optional fourth column assigns a row to a `DeviceProfile`.
+    /// Otherwise the profile of addresses with more than one definition
+    /// is inferred from the descriptions, see `DeviceProfile::infer()`.
+    /// All definitions of an address are kept in the order of their
+    /// appearance.
+    ///
+    /// Enumerations and bitfields are extracted from the descriptions of
+    /// integer registers. Rows without an address that start with `Bit #n`
+    /// add further bits to the preceding bitfield register.
+    ///
+    /// Plain addresses are holding registers. Coils, discrete inputs and
+    /// input registers are listed with references that start with the
+    /// digit of their table, e.g. `00015` for coil 15 or `300001` for
+    /// input register 1, see `Table::parse_address()`. Addresses like
+    /// `10001` that are either a holding register or a reference are
+    /// rejected. The type of coils and discrete inputs is left empty.
+    pub fn from_reader<R: Read>(rdr: R) -> MapResult<Self> {
+        let mut rdr = ReaderBuilder::new().flexible(true).from_reader(rdr);
+        let mut map = Self::default();
+        let mut last_reg = None;
+        for result in rdr.records() {
+            let record = result?;
+            match parse_record(&record)? {
+                Some(Row::Register(def)) => {
+                    last_reg = Some((def.table, def.addr));
+                    map.table_mut(def.table)
+                        .entry(def.addr)
+                        .or_default()
+                        .push(def);
+                }
+                Some(Row::Bit(def)) => {
+                    last_reg = None;
+                    map.bits.entry((def.table, def.addr)).or_default().push(def);
+                }
+                None => {
+                    if let Some(field) = last_reg
+                        .and_then(|(table, addr)| map.table_mut(table).get_mut(&addr))
+                        .and_then(|variants| variants.last_mut())
+                        .and_then(|def| def.field.as_mut())
+                    {
+                        field.add_bit(record.get(2).unwrap_or_default());
+                    }
+                }
+            }
+        }
+        map.infer_profiles();
+        Ok(map)
+    }
+
+    /// Infer the profiles of the definitions that share their address
+    /// with others and have no explicit profile.
+    fn infer_profiles(&mut self) {
+        let registers = self
+            .entries
+            .values_mut()
+            .chain(self.input_registers.values_mut())
+            .filter(|variants| variants.len() > 1)
+            .flatten();
+        for def in registers.filter(|def| def.profile.is_none()) {
+            def.profile = DeviceProfile::infer(&def.description);
+        }
+        let bits = self
+            .bits
+            .values_mut()
+            .filter(|variants| variants.len() > 1)
+            .flatten();
+        for def in bits.filter(|def| def.profile.is_none()) {
+            def.profile = DeviceProfile::infer(&def.description);
+        }
+    }
+
+    /// Load curated profile overrides from a CSV file, see
+    /// `apply_profiles()`.
+    pub fn load_profiles(&mut self, path: impl AsRef<Path>) -> MapResult<()> {
+        self.apply_profiles(File::open(path)?)
+    }
+
+    /// Assign holding register definitions to device profiles with
+    /// curated overrides, e.g. from `ModbusProfiles.csv`, for ambiguous
+    /// addresses that cannot be inferred from the descriptions.
+    ///
+    /// The CSV data has the columns `Addr,Register Description,Profile,Source`
+    /// and lines that start with `#` are comments. Each row selects the
+    /// definitions of the address with exactly the same description. The
+    /// source that justifies an override is not evaluated.
+    pub fn apply_profiles<R: Read>(&mut self, rdr: R) -> MapResult<()> {
+        let mut rdr = ReaderBuilder::new()
+            .flexible(true)
+            .comment(Some(b'#'))
+            .from_reader(rdr);
+        for result in rdr.records() {
+            let record = result?;
+            let line = record_line(&record);
+            let addr = record.get(0).unwrap_or_default().trim();
+            let addr = addr.parse().map_err(|_| MapError::InvalidAddress {
+                line,
+                value: addr.to_string(),
+            })?;
+            let description = record.get(1).unwrap_or_default().trim();
+            let profile = record.get(2).unwrap_or_default().trim();
+            let profile = profile.parse().map_err(|_| MapError::InvalidProfile {
+                line,
+                value: profile.to_string(),
+            })?;
+            let mut defs = self
+                .entries
+                .get_mut(&addr)
+                .into_iter()
+                .flatten()
+                .filter(|def| def.description == description)
+                .peekable();
+            if defs.peek().is_none() {
+                return Err(MapError::UnmatchedProfile { line, addr });
             }
-            None => continue, //ignore cells w/no address
+            for def in defs {
+                def.profile = Some(profile);
+            }
+        }
+        Ok(())
+    }
+
+    fn table_mut(&mut self, table: Table) -> &mut BTreeMap<u16, Vec<RegisterDef>> {
+        match table {
+            Table::InputRegister => &mut self.input_registers,
+            _ => &mut self.entries,
+        }
+    }
+
+    /// Select the device profile for resolving ambiguous addresses.
+    pub fn with_profile(mut self, profile: DeviceProfile) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+
+    pub fn set_profile(&mut self, profile: Option<DeviceProfile>) {
+        self.profile = profile;
+    }
+
+    pub fn profile(&self) -> Option<DeviceProfile> {
+        self.profile
+    }
+
+    /// The definition of `addr` for the selected profile.
+    pub fn get(&self, addr: u16) -> Option<&RegisterDef> {
+        self.entries
+            .get(&addr)
+            .map(|variants| profile::resolve(variants, self.profile))
+    }
+
+    /// The definition of input register `addr` for the selected profile.
+    pub fn input_register(&self, addr: u16) -> Option<&RegisterDef> {
+        self.input_registers
+            .get(&addr)
+            .map(|variants| profile::resolve(variants, self.profile))
+    }
+
+    /// The definition of coil `addr` for the selected profile.
+    pub fn coil(&self, addr: u16) -> Option<&BitDef> {
+        self.bit(Table::Coil, addr)
+    }
+
+    /// The definition of discrete input `addr` for the selected profile.
+    pub fn discrete_input(&self, addr: u16) -> Option<&BitDef> {
+        self.bit(Table::DiscreteInput, addr)
+    }
+
+    fn bit(&self, table: Table, addr: u16) -> Option<&BitDef> {
+        self.bits
+            .get(&(table, addr))
+            .map(|variants| profile::resolve(variants, self.profile))
+    }
+
+    /// Iterate over all input registers in ascending address order.
+    pub fn input_registers(&self) -> impl Iterator<Item = &RegisterDef> {
+        let profile = self.profile;
+        self.input_registers
+            .values()
+            .map(move |variants| profile::resolve(variants, profile))
+    }
+
+    /// Iterate over all coils followed by all discrete inputs in
+    /// ascending address order.
+    pub fn bits(&self) -> impl Iterator<Item = &BitDef> {
+        let profile = self.profile;
+        self.bits
+            .values()
+            .map(move |variants| profile::resolve(variants, profile))
+    }
+
+    /// All definitions of holding register `addr` in the order of their
+    /// appearance.
+    pub fn variants(&self, addr: u16) -> &[RegisterDef] {
+        self.entries.get(&addr).map_or(&[], Vec::as_slice)
+    }
+
+    /// Report all addresses with more than one definition.
+    pub fn ambiguities(&self) -> Vec<Ambiguity<'_>> {
+        self.entries
+            .iter()
+            .filter(|(_, variants)| variants.len() > 1)
+            .map(|(addr, variants)| Ambiguity {
+                addr: *addr,
+                variants,
+            })
+            .collect()
+    }
+
+    pub fn contains(&self, addr: u16) -> bool {
+        self.entries.contains_key(&addr)
+    }
+
+    /// The number of holding register addresses.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterate over all registers in ascending address order.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            inner: self.entries.values(),
+            profile: self.profile,
+        }
+    }
+
+    /// Iterate over all registers with an address in `range`.
+    pub fn range<B: RangeBounds<u16>>(&self, range: B) -> impl Iterator<Item = &RegisterDef> {
+        let profile = self.profile;
+        self.entries
+            .range(range)
+            .map(move |(_, variants)| profile::resolve(variants, profile))
+    }
+}
+
+/// Iterator over the resolved definitions of a `RegisterMap`.
+pub struct Iter<'a> {
+    inner: btree_map::Values<'a, u16, Vec<RegisterDef>>,
+    profile: Option<DeviceProfile>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a RegisterDef;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let profile = self.profile;
+        self.inner
+            .next()
+            .map(|variants| profile::resolve(variants, profile))
+    }
+}
+
+impl<'a> IntoIterator for &'a RegisterMap {
+    type Item = &'a RegisterDef;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+fn record_line(record: &StringRecord) -> u64 {
+    record.position().map_or(0, |pos| pos.line())
+}
+
+enum Row {
+    Register(RegisterDef),
+    Bit(BitDef),
+}
+
+fn parse_record(record: &StringRecord) -> MapResult<Option<Row>> {
+    let addr = match record.get(1).map(str::trim) {
+        Some(addr) if !addr.is_empty() => addr,
+        _ => return Ok(None), //ignore cells w/no address
+    };
+    let (table, addr) = Table::parse_address(addr).map_err(|_| MapError::InvalidAddress {
+        line: record_line(record),
+        value: addr.to_string(),
+    })?;
+    let reg_type = match record.get(0).map(str::trim) {
+        Some(reg_type) if !reg_type.is_empty() && table.is_bit() => {
+            return Err(MapError::InvalidType {
+                line: record_line(record),
+                value: reg_type.to_string(),
+            })
+        }
+        Some(reg_type) if !reg_type.is_empty() => {
+            reg_type.parse().map_err(|_| MapError::InvalidType {
+                line: record_line(record),
+                value: reg_type.to_string(),
+            })?
+        }
+        _ => RegType::U8, //default type
+    };
+    let description = record.get(2).unwrap_or_default().trim().to_string();
+    let profile = match record.get(3).map(str::trim) {
+        Some(profile) if !profile.is_empty() => {
+            Some(profile.parse().map_err(|_| MapError::InvalidProfile {
+                line: record_line(record),
+                value: profile.to_string(),
+            })?)
+        }
+        _ => None,
+    };
+    if table.is_bit() {
+        let access = match table {
+            Table::Coil => Access::parse_coil(&description),
+            _ => Access::read_only(),
         };
+        return Ok(Some(Row::Bit(BitDef {
+            table,
+            addr,
+            description,
+            profile,
+            access,
+        })));
+    }
+    let field = match reg_type {
+        RegType::F32 | RegType::F64 | RegType::Ascii(_) => None,
+        _ => FieldDef::parse(&description),
+    };
+    let access = match table {
+        Table::InputRegister => Access::read_only(),
+        _ => Access::parse_register(addr, reg_type, &description),
+    };
+    Ok(Some(Row::Register(RegisterDef {
+        table,
+        addr,
+        reg_type,
+        description,
+        profile,
+        field,
+        access,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::core::modbus::Text;
+    use std::convert::TryFrom;
+
+    const MAP: &str = "Type,Addr,Register Description
+U16,1,Bit #0 – (E)EPROM checksum failure
+F32,247,Mass Flow Rate
+F32,249,Density
+A16,425,Device Tag
+,50,Fixed Date Low order byte: day
+U16,15,Quaternary variable (QV)
+U16,15,RFT9739 control output variable
+";
+
+    #[test]
+    fn load_typed_entries() {
+        let map = RegisterMap::from_reader(MAP.as_bytes()).unwrap();
+        assert_eq!(6, map.len());
+        let def = map.get(247).unwrap();
+        assert_eq!(RegType::F32, def.reg_type);
+        assert_eq!(2, def.word_count());
+        assert_eq!("Mass Flow Rate", def.description);
+        assert_eq!(RegType::Ascii(16), map.get(425).unwrap().reg_type);
+        assert_eq!(8, map.get(425).unwrap().word_count());
+        assert_eq!(RegType::U8, map.get(50).unwrap().reg_type);
+        assert_eq!("Quaternary variable (QV)", map.get(15).unwrap().description);
+        assert!(map.get(2).is_none());
+    }
+
+    #[test]
+    fn resolve_by_profile() {
+        let map = RegisterMap::from_reader(MAP.as_bytes()).unwrap();
+        assert_eq!(2, map.variants(15).len());
+        assert_eq!(Some(DeviceProfile::Rft9739), map.variants(15)[1].profile);
+        let map = map.with_profile(DeviceProfile::Rft9739);
+        assert_eq!(
+            "RFT9739 control output variable",
+            map.get(15).unwrap().description
+        );
+        assert_eq!("Mass Flow Rate", map.get(247).unwrap().description);
+        let map = map.with_profile(DeviceProfile::Gen5);
+        assert_eq!("Quaternary variable (QV)", map.get(15).unwrap().description);
+    }
+
+    #[test]
+    fn explicit_profile_column() {
+        let csv = "Type,Addr,Register Description,Profile
+F32,255,Viscosity,Orion
+F32,255,Undefined
+F32,247,Mass Flow Rate
+";
+        let mut map = RegisterMap::from_reader(csv.as_bytes()).unwrap();
+        assert_eq!("Undefined", map.get(255).unwrap().description);
+        map.set_profile(Some(DeviceProfile::Orion));
+        assert_eq!("Viscosity", map.get(255).unwrap().description);
+        let err =
+            RegisterMap::from_reader("Type,Addr,Desc,Profile\nF32,255,Viscosity,1500\n".as_bytes())
+                .unwrap_err();
+        assert!(matches!(err, MapError::InvalidProfile { line: 2, .. }));
+    }
+
+    #[test]
+    fn report_ambiguities() {
+        let map = RegisterMap::from_reader(MAP.as_bytes()).unwrap();
+        let ambiguities = map.ambiguities();
+        assert_eq!(1, ambiguities.len());
+        assert_eq!(15, ambiguities[0].addr);
+        assert!(ambiguities[0].is_conflicting());
+        assert!(ambiguities[0].is_resolved());
+
+        let mut map = RegisterMap::from_path("ModbusMap.csv").unwrap();
+        map.load_profiles("ModbusProfiles.csv").unwrap();
+        let ambiguities = map.ambiguities();
+        for addr in [255, 257, 1069, 1071, 1079] {
+            let ambiguity = ambiguities.iter().find(|a| a.addr == addr).unwrap();
+            assert!(ambiguity.is_resolved(), "{}", addr);
+        }
+    }
+
+    #[test]
+    fn write_requests() {
+        let map = RegisterMap::from_path("ModbusMap.csv").unwrap();
+        let flow = map.get(247).unwrap();
+        assert_eq!(
+            Request::ReadHoldingRegisters {
+                addr: 246,
+                count: 2
+            },
+            flow.read_request()
+        );
+        let err = flow.write_request(None, &[0, 0], Confirm::Yes).unwrap_err();
+        assert!(matches!(err, Error::Refused { .. }));
+
+        let zero = map.get(1315).unwrap();
+        let err = zero.write_request(None, &[0, 1], Confirm::No).unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }));
+        assert_eq!(
+            Request::WriteSingleRegister {
+                addr: 1314,
+                word: 1
+            },
+            zero.write_request(None, &[1], Confirm::Yes).unwrap()
+        );
+        let err = zero.write_request(None, &[1], Confirm::No).unwrap_err();
+        assert!(matches!(err, Error::Refused { .. }));
+        assert!(!zero.verifies_write(None));
+        let err = zero.encode(&Value::F32(1.0), ByteOrder::default());
+        assert!(matches!(err, Err(Error::InvalidInput { .. })));
+
+        let csv = "Type,Addr,Register Description\nA8,425,Tag\nF32,443,Lower Range Value\n";
+        let map = RegisterMap::from_reader(csv.as_bytes()).unwrap();
+        let text = Value::Text(Text::try_from("FT").unwrap());
+        let tag = map.get(425).unwrap();
+        let words = tag.encode(&text, ByteOrder::default()).unwrap();
+        assert_eq!(0x2020, words[1]);
+        // NUL padding instead of spaces
+        let read_back = [0x4654, 0, 0, 0];
+        assert_eq!(
+            text,
+            tag.verify_write(&words, &read_back, ByteOrder::default())
+                .unwrap()
+        );
+        let range = map.get(443).unwrap();
+        let words = range
+            .encode(&Value::F32(1.0), ByteOrder::default())
+            .unwrap();
+        let err = range
+            .verify_write(&words, &[0, 0], ByteOrder::default())
+            .unwrap_err();
+        assert!(matches!(err, Error::Mismatch { addr: 443, .. }));
+        assert_eq!("Register 443 reads back 0 instead of 1", err.to_string());
+
+        let map = RegisterMap::from_path("ModbusMap.csv").unwrap();
+        let mirror = map.coil(545).unwrap();
+        assert_eq!(
+            Request::WriteSingleCoil {
+                addr: 544,
+                state: true
+            },
+            mirror.write_request(None, true, Confirm::No).unwrap()
+        );
+    }
+
+    #[test]
+    fn bundled_coils() {
+        // Only the coils that the register descriptions refer to
+        let map = RegisterMap::from_path("ModbusMap.csv").unwrap();
+        assert_eq!(
+            vec![15, 16, 348, 445, 509, 525, 545],
+            map.bits().map(|def| def.addr).collect::<Vec<_>>()
+        );
+        assert!(map.bits().all(|def| def.table == Table::Coil));
+        let calibration = map.coil(15).unwrap();
+        assert_eq!(Some(SideEffect::Calibrate), calibration.access.side_effect);
+        assert_eq!(
+            "Enable Ethernet mirror mode (see register 5899)",
+            map.coil(545).unwrap().description
+        );
+    }
+
+    #[test]
+    fn bundled_map_profiles() {
+        let mut map = RegisterMap::from_path("ModbusMap.csv").unwrap();
+        // The vendor map does not name the device of these definitions
+        assert_eq!(None, map.variants(255)[0].profile);
+        map.load_profiles("ModbusProfiles.csv").unwrap();
+        assert_eq!("Undefined", map.get(255).unwrap().description);
+        assert_eq!(
+            "(Internally Derived) Pressure",
+            map.get(257).unwrap().description
+        );
+        map.set_profile(Some(DeviceProfile::Orion));
+        assert_eq!("Viscosity", map.get(255).unwrap().description);
+        assert_eq!("Differential Pressure", map.get(257).unwrap().description);
+        assert_eq!("Force Reset", map.get(1069).unwrap().description);
+        map.set_profile(Some(DeviceProfile::Series2700));
+        assert_eq!("Undefined", map.get(255).unwrap().description);
+        assert_eq!("DZDT Mode", map.get(1069).unwrap().description.trim());
+        // Access qualifiers do not make a definition device specific
+        assert_eq!(None, map.get(8).unwrap().profile);
+        map.set_profile(Some(DeviceProfile::Rft9739));
+        assert_eq!(1, map.variants(8).len());
+        assert!(map.get(8).unwrap().description.starts_with("Mass total"));
+    }
+
+    #[test]
+    fn apply_profiles() {
+        let csv = "Type,Addr,Register Description
+F32,255,Viscosity
+F32,255,Undefined
+";
+        let mut map = RegisterMap::from_reader(csv.as_bytes()).unwrap();
+        let profiles = "# Curated profiles
+Addr,Register Description,Profile,Source
+255,Viscosity,Orion,register 4085
+";
+        map.apply_profiles(profiles.as_bytes()).unwrap();
+        assert_eq!(Some(DeviceProfile::Orion), map.variants(255)[0].profile);
+        assert_eq!("Undefined", map.get(255).unwrap().description);
+
+        let err = map
+            .apply_profiles("Addr,Desc,Profile\n255,Density,Orion\n".as_bytes())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            MapError::UnmatchedProfile { line: 2, addr: 255 }
+        ));
+        let err = map
+            .apply_profiles("Addr,Desc,Profile\n255,Undefined,1500\n".as_bytes())
+            .unwrap_err();
+        assert!(matches!(err, MapError::InvalidProfile { line: 2, .. }));
+    }
+
+    #[test]
+    fn enums_and_bitfields() {
+        let csv = "Type,Addr,Register Description
+U16,2526,\"Batch State (0=Idle, 1=Running, 2=Paused)\"
+U16,419,\"Bit #0 – Core EEPROM Checksum Error (Config, Powerdown, Program)\"
+,,Bit #1 – Core RAM Error
+,,Bit #6 – Sensor Failure
+F32,247,Mass Flow Rate (0=a/1=b)
+";
+        let map = RegisterMap::from_reader(csv.as_bytes()).unwrap();
+        let state = map.get(2526).unwrap();
+        assert_eq!("Running", state.decode_symbolic(&[1]).unwrap().to_string());
+        let status = map.get(419).unwrap();
+        assert_eq!(
+            "Core RAM Error, Sensor Failure",
+            status.decode_symbolic(&[0x0042]).unwrap().to_string()
+        );
+        assert!(status.decode_symbolic(&[]).is_none());
+        assert_eq!(
+            Ok(Value::Bits(0x42)),
+            status.decode(&[0x0042], ByteOrder::default())
+        );
+        assert_eq!(Ok(Value::U16(1)), state.decode(&[1], ByteOrder::default()));
+        assert!(map.get(247).unwrap().field.is_none());
+    }
+
+    #[test]
+    fn coils_and_inputs() {
+        let csv = "Type,Addr,Register Description
+F32,247,Mass Flow Rate
+,00003,Start sensor zero
+,00056,Reset all totals
+,100021,Sensor zero in progress
+F32,300247,Mass Flow Rate
+U16,300419,Bit #0 – Core EEPROM Checksum Error
+,,Bit #1 – Core RAM Error
+";
+        let map = RegisterMap::from_reader(csv.as_bytes()).unwrap();
+        assert_eq!(1, map.len());
+        let zero = map.coil(3).unwrap();
+        assert_eq!(
+            (Table::Coil, "Start sensor zero"),
+            (zero.table, &*zero.description)
+        );
+        assert_eq!(Some(SideEffect::Calibrate), zero.access.side_effect);
+        let reset = map.coil(56).unwrap();
+        assert_eq!(
+            Err(AccessError::Unconfirmed(SideEffect::Reset)),
+            reset.check_write(None, true, Confirm::No)
+        );
+        assert!(map.coil(21).is_none());
+        let input = map.discrete_input(21).unwrap();
+        assert_eq!(
+            Err(AccessError::ReadOnly),
+            input.check_write(None, true, Confirm::Yes)
+        );
+        assert_eq!(
+            vec![
+                (Table::Coil, 3),
+                (Table::Coil, 56),
+                (Table::DiscreteInput, 21)
+            ],
+            map.bits()
+                .map(|def| (def.table, def.addr))
+                .collect::<Vec<_>>()
+        );
+
+        let flow = map.input_register(247).unwrap();
+        assert_eq!(
+            (Table::InputRegister, RegType::F32),
+            (flow.table, flow.reg_type)
+        );
+        assert_eq!(AccessMode::ReadOnly, flow.access.mode(None));
+        assert_eq!(Table::HoldingRegister, map.get(247).unwrap().table);
+        let status = map.input_register(419).unwrap();
+        assert_eq!(
+            "Core RAM Error",
+            status.decode_symbolic(&[0x0002]).unwrap().to_string()
+        );
+        assert_eq!(2, map.input_registers().count());
+
+        let err =
+            RegisterMap::from_reader("Type,Addr,Desc\nU16,00003,Zero\n".as_bytes()).unwrap_err();
+        assert!(matches!(err, MapError::InvalidType { line: 2, .. }));
+        let err = RegisterMap::from_reader("Type,Addr,Desc\n,10003,Zero\n".as_bytes()).unwrap_err();
+        assert!(matches!(err, MapError::InvalidAddress { line: 2, .. }));
+        let map = RegisterMap::from_reader("Type,Addr,Desc\nU16,410003,Zero\n".as_bytes()).unwrap();
+        assert_eq!(Table::HoldingRegister, map.get(10003).unwrap().table);
+    }
+
+    #[test]
+    fn bundled_totals_and_counters() {
+        let map = RegisterMap::from_path("ModbusMap.csv").unwrap();
+        let f64_totals = [305, 309]
+            .iter()
+            .copied()
+            .chain((4200..=4268).step_by(4))
+            .chain((4274..=4290).step_by(4));
+        let f32_totals = [969, 971, 975, 977, 981, 983, 1657, 1659, 4397, 4399]
+            .iter()
+            .copied()
+            .chain((2327..=2343).step_by(2))
+            .chain((3383..=3389).step_by(2))
+            .chain((3972..=3986).step_by(2))
+            .chain((4420..=4434).step_by(2))
+            .chain([4440, 4442].iter().copied());
+        let counters = (497..=500).chain(1515..=1516);
+        for addr in f64_totals.chain(f32_totals).chain(counters) {
+            let def = map.get(addr).unwrap();
+            let access = &def.access;
+            assert!(
+                !access.mode(None).is_writable() || access.side_effect == Some(SideEffect::Clear),
+                "{} {}",
+                addr,
+                def.description
+            );
+        }
+        assert_eq!(
+            Err(AccessError::ReadOnly),
+            map.get(4200)
+                .unwrap()
+                .check_write(None, &[0; 4], Confirm::Yes)
+        );
+        assert_eq!(
+            Err(AccessError::Unconfirmed(SideEffect::Reset)),
+            map.get(1070).unwrap().check_write(None, &[1], Confirm::No)
+        );
+        // Configuration of the totals stays writable
+        assert!(map.get(195).unwrap().access.mode(None).is_writable());
+        assert!(map.get(4383).unwrap().access.mode(None).is_writable());
+    }
+
+    #[test]
+    fn range_query() {
+        let map = RegisterMap::from_reader(MAP.as_bytes()).unwrap();
+        let addrs: Vec<u16> = map.range(15..=249).map(|def| def.addr).collect();
+        assert_eq!(vec![15, 50, 247, 249], addrs);
+    }
+
+    #[test]
+    fn invalid_rows_are_errors() {
+        let err =
+            RegisterMap::from_reader("Type,Addr,Desc\nF23,247,Mass\n".as_bytes()).unwrap_err();
+        assert!(matches!(err, MapError::InvalidType { line: 2, .. }));
+        let err =
+            RegisterMap::from_reader("Type,Addr,Desc\nF32,x247,Mass\n".as_bytes()).unwrap_err();
+        assert!(matches!(err, MapError::InvalidAddress { line: 2, .. }));
+    }
+
+    #[test]
+    fn load_bundled_map() {
+        let map = RegisterMap::from_path("ModbusMap.csv").unwrap();
+        assert_eq!(RegType::F32, map.get(247).unwrap().reg_type);
+        assert_eq!(RegType::U64, map.get(7109).unwrap().reg_type);
+        assert_eq!(RegType::Ascii(25), map.get(2584).unwrap().reg_type);
+        assert_eq!(13, map.get(2584).unwrap().word_count());
+        assert!(map.get(8).unwrap().access.is_destructive());
+        assert!(map.get(1322).unwrap().access.is_destructive());
+        assert_eq!(
+            Err(AccessError::Unconfirmed(SideEffect::Reset)),
+            map.get(1322).unwrap().check_write(None, &[1], Confirm::No)
+        );
+        assert_eq!(Some(0), map.get(352).unwrap().access.only_value);
+        assert_eq!(
+            AccessMode::WriteOnly,
+            map.get(17).unwrap().access.mode(Some(DeviceProfile::Gen5))
+        );
+        assert!(!map.get(247).unwrap().access.is_destructive());
     }
-    return hmap;
 }
@@ -0,0 +1,206 @@
+use super::{access, BitDef, RegisterDef};
+
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// Transmitter family that determines the meaning of registers which
+/// are defined more than once in the Modbus map.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum DeviceProfile {
+    /// Legacy RFT9739 transmitters.
+    #[serde(rename = "RFT9739", alias = "9739")]
+    Rft9739,
+    /// Series 2000 transmitters, e.g. 1700/2700.
+    #[serde(rename = "2700", alias = "Series2700")]
+    Series2700,
+    /// Generation 5 transmitters, e.g. 4200/5700.
+    #[serde(rename = "5700", alias = "Gen5")]
+    Gen5,
+    /// Fork density and viscosity meters.
+    #[serde(rename = "Orion", alias = "Fork")]
+    Orion,
+}
+
+impl DeviceProfile {
+    pub const ALL: [DeviceProfile; 4] = [
+        DeviceProfile::Rft9739,
+        DeviceProfile::Series2700,
+        DeviceProfile::Gen5,
+        DeviceProfile::Orion,
+    ];
+
+    /// Guess the profile a register definition is specific to from
+    /// device names mentioned in its description.
+    ///
+    /// Clauses that only restrict the access, e.g. "(Read only for
+    /// RFT9739)", do not make a definition specific to a device.
+    pub fn infer(description: &str) -> Option<Self> {
+        let clauses: Vec<String> = access::clauses(description)
+            .filter(|clause| !access::is_access_clause(clause))
+            .collect();
+        let matches = |keywords: &[&str]| {
+            keywords
+                .iter()
+                .any(|kw| clauses.iter().any(|clause| clause.contains(kw)))
+        };
+        if matches(&["rft9739", "9739"]) {
+            Some(DeviceProfile::Rft9739)
+        } else if matches(&["gen5", "5700"]) {
+            Some(DeviceProfile::Gen5)
+        } else if matches(&["orion", "fork"]) {
+            Some(DeviceProfile::Orion)
+        } else if matches(&["2700"]) {
+            Some(DeviceProfile::Series2700)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for DeviceProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use DeviceProfile::*;
+        match self {
+            Rft9739 => write!(f, "RFT9739"),
+            Series2700 => write!(f, "2700"),
+            Gen5 => write!(f, "5700"),
+            Orion => write!(f, "Orion"),
+        }
+    }
+}
+
+impl FromStr for DeviceProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use DeviceProfile::*;
+        match s.trim().to_lowercase().as_str() {
+            "rft9739" | "9739" => Ok(Rft9739),
+            "2700" | "series2700" => Ok(Series2700),
+            "5700" | "gen5" => Ok(Gen5),
+            "orion" | "fork" => Ok(Orion),
+            _ => Err(format!("Unknown device profile {:?}", s)),
+        }
+    }
+}
+
+/// Pick the definition that applies to `profile` among all definitions
+/// of the same address.
+///
+/// A definition specific to the profile wins over a generic one. Without
+/// a match the first generic definition is used, or the first definition
+/// if all of them are specific to other profiles.
+pub(crate) fn resolve<T: Profiled>(variants: &[T], profile: Option<DeviceProfile>) -> &T {
+    profile
+        .and_then(|profile| variants.iter().find(|def| def.profile() == Some(profile)))
+        .or_else(|| variants.iter().find(|def| def.profile().is_none()))
+        .unwrap_or(&variants[0])
+}
+
+/// Definitions of the Modbus map that may be specific to a profile.
+pub(crate) trait Profiled {
+    fn profile(&self) -> Option<DeviceProfile>;
+}
+
+impl Profiled for RegisterDef {
+    fn profile(&self) -> Option<DeviceProfile> {
+        self.profile
+    }
+}
+
+impl Profiled for BitDef {
+    fn profile(&self) -> Option<DeviceProfile> {
+        self.profile
+    }
+}
+
+/// An address with more than one definition in the Modbus map.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ambiguity<'a> {
+    pub addr: u16,
+    pub variants: &'a [RegisterDef],
+}
+
+impl<'a> Ambiguity<'a> {
+    /// The definitions differ in type or description.
+    pub fn is_conflicting(&self) -> bool {
+        let first = &self.variants[0];
+        self.variants
+            .iter()
+            .any(|def| def.reg_type != first.reg_type || def.description != first.description)
+    }
+
+    /// Every profile selects a distinct definition, i.e. there is at
+    /// most one generic definition and no profile is listed twice.
+    pub fn is_resolved(&self) -> bool {
+        let generic = self.variants.iter().filter(|def| def.profile.is_none());
+        generic.count() <= 1
+            && DeviceProfile::ALL.iter().all(|profile| {
+                self.variants
+                    .iter()
+                    .filter(|def| def.profile == Some(*profile))
+                    .count()
+                    <= 1
+            })
+    }
+
+    pub fn resolve(&self, profile: Option<DeviceProfile>) -> &'a RegisterDef {
+        resolve(self.variants, profile)
+    }
+}
+
+impl<'a> fmt::Display for Ambiguity<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", self.addr)?;
+        for (i, def) in self.variants.iter().enumerate() {
+            let sep = if i == 0 { " " } else { " | " };
+            write!(f, "{}{} [{}]", sep, def.description, def.reg_type)?;
+            if let Some(profile) = def.profile {
+                write!(f, " ({})", profile)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn infer_profile() {
+        assert_eq!(
+            Some(DeviceProfile::Rft9739),
+            DeviceProfile::infer("RFT9739 control output variable")
+        );
+        assert_eq!(
+            Some(DeviceProfile::Orion),
+            DeviceProfile::infer("Fork State")
+        );
+        assert_eq!(
+            Some(DeviceProfile::Gen5),
+            DeviceProfile::infer("2nd Mass total (Gen5 only)")
+        );
+        assert_eq!(None, DeviceProfile::infer("Quaternary variable (QV)"));
+        // Access restrictions do not make a register device-specific
+        assert_eq!(
+            None,
+            DeviceProfile::infer("Mass total (Scaled Int) (Read only for RFT9739)")
+        );
+        assert_eq!(
+            None,
+            DeviceProfile::infer(
+                "Flow direction  (see flow direction codes)  Gen5- Write Only, if written will attempt to initialize"
+            )
+        );
+    }
+
+    #[test]
+    fn parse_profile() {
+        for profile in DeviceProfile::ALL.iter() {
+            assert_eq!(Ok(*profile), profile.to_string().parse());
+        }
+        assert_eq!(Ok(DeviceProfile::Gen5), "gen5".parse());
+        assert!("1500".parse::<DeviceProfile>().is_err());
+    }
+}
@@ -0,0 +1,157 @@
+use super::{RegisterDef, RegisterMap};
+
+use crate::core::modbus::RegType;
+
+/// A register that matches a search query.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SearchHit<'a> {
+    pub def: &'a RegisterDef,
+    /// Token overlap with the query, higher is better.
+    pub score: usize,
+}
+
+/// Case-insensitive full-text query over the register descriptions.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchQuery {
+    tokens: Vec<String>,
+    phrase: String,
+    reg_type: Option<RegType>,
+}
+
+impl SearchQuery {
+    pub fn new(text: &str) -> Self {
+        let tokens = tokenize(text);
+        let phrase = tokens.join(" ");
+        Self {
+            tokens,
+            phrase,
+            reg_type: None,
+        }
+    }
+
+    /// Only match registers of the given type.
+    pub fn with_type(mut self, reg_type: RegType) -> Self {
+        self.reg_type = Some(reg_type);
+        self
+    }
+
+    /// Score a register definition, `0` if it does not match at all.
+    ///
+    /// Every query token that equals a token of the description counts
+    /// twice as much as a token that is only a prefix of one, e.g.
+    /// "zero" in "zeroing". Matching the whole query as a phrase adds
+    /// one more point.
+    pub fn score(&self, def: &RegisterDef) -> usize {
+        if self
+            .reg_type
+            .is_some_and(|reg_type| reg_type != def.reg_type)
+        {
+            return 0;
+        }
+        let desc_tokens = tokenize(&def.description);
+        let mut score = 0;
+        for token in &self.tokens {
+            if desc_tokens.iter().any(|t| t == token) {
+                score += 2;
+            } else if desc_tokens.iter().any(|t| t.starts_with(token.as_str())) {
+                score += 1;
+            }
+        }
+        if score > 0 && self.tokens.len() > 1 && desc_tokens.join(" ").contains(&self.phrase) {
+            score += 1;
+        }
+        score
+    }
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    tokens.dedup();
+    tokens
+}
+
+impl RegisterMap {
+    /// Search the register descriptions, best matches first.
+    pub fn search(&self, text: &str) -> Vec<SearchHit<'_>> {
+        self.search_query(&SearchQuery::new(text))
+    }
+
+    /// Search the register descriptions, best matches first.
+    ///
+    /// Hits with the same score are ordered by the length of their
+    /// description, i.e. more specific registers come first, and then
+    /// by address.
+    pub fn search_query(&self, query: &SearchQuery) -> Vec<SearchHit<'_>> {
+        let mut hits: Vec<SearchHit<'_>> = self
+            .iter()
+            .map(|def| SearchHit {
+                def,
+                score: query.score(def),
+            })
+            .filter(|hit| hit.score > 0)
+            .collect();
+        hits.sort_by(|lhs, rhs| {
+            rhs.score
+                .cmp(&lhs.score)
+                .then(lhs.def.description.len().cmp(&rhs.def.description.len()))
+                .then(lhs.def.addr.cmp(&rhs.def.addr))
+        });
+        hits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_bundled_map() {
+        let map = RegisterMap::from_path("ModbusMap.csv").unwrap();
+
+        let hits = map.search("drive gain");
+        assert!(!hits.is_empty());
+        assert!(hits[0]
+            .def
+            .description
+            .to_lowercase()
+            .contains("drive gain"));
+
+        let hits = map.search("LIVE ZERO");
+        assert!(hits[0].def.description.to_lowercase().contains("live zero"));
+
+        assert!(map.search("xyzzy").is_empty());
+    }
+
+    #[test]
+    fn filter_by_type() {
+        let map = RegisterMap::from_path("ModbusMap.csv").unwrap();
+        let query = SearchQuery::new("mass flow").with_type(RegType::U16);
+        let hits = map.search_query(&query);
+        assert!(!hits.is_empty());
+        assert!(hits.iter().all(|hit| hit.def.reg_type == RegType::U16));
+    }
+
+    #[test]
+    fn rank_by_overlap() {
+        let map = RegisterMap::from_reader(
+            "Type,Addr,Desc
+F32,247,Mass flow rate
+F32,249,Density
+F32,253,Volume flow rate
+U16,2,Mass flow rate (Scaled Int)
+"
+            .as_bytes(),
+        )
+        .unwrap();
+        let addrs: Vec<u16> = map
+            .search("mass flow")
+            .iter()
+            .map(|hit| hit.def.addr)
+            .collect();
+        assert_eq!(vec![247, 2, 253], addrs);
+    }
+}
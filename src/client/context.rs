@@ -0,0 +1,244 @@
+//! Request/response transactions on a byte stream with RTU, ASCII or
+//! TCP framing.
+
+use crate::core::modbus::{
+    ascii::{self, FRAME_START, MAX_ADU_LEN},
+    is_response_from,
+    rtu::{
+        crc16, decode_frame, decode_response, inter_frame_delay, response_frame_len, Request,
+        Response, BAUD_RATE, MAX_FRAME_LEN, MAX_PDU_LEN, RESPONSE_HEADER_LEN,
+    },
+    Framing,
+};
+
+use std::{
+    fmt,
+    io::{Error, ErrorKind, Result},
+    time::Duration,
+};
+use tokio1::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufStream},
+    time::{sleep_until, Instant},
+};
+
+/// A byte stream that carries Modbus frames, e.g. a serial port or a
+/// TCP connection.
+pub trait Transport: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Transport for T {}
+
+/// The framing of requests and responses on a transport.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    Rtu,
+    Ascii,
+    /// MBAP header with a transaction id and the unit id
+    Tcp,
+}
+
+impl From<Framing> for Protocol {
+    fn from(from: Framing) -> Self {
+        match from {
+            Framing::Rtu => Protocol::Rtu,
+            Framing::Ascii => Protocol::Ascii,
+        }
+    }
+}
+
+/// A connected transport for one request at a time.
+pub struct Context {
+    stream: BufStream<Box<dyn Transport>>,
+    protocol: Protocol,
+    transaction_id: u16,
+    /// The silent interval between RTU frames
+    frame_delay: Duration,
+    last_frame: Option<Instant>,
+}
+
+impl fmt::Debug for Context {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Context")
+            .field("protocol", &self.protocol)
+            .finish()
+    }
+}
+
+impl Context {
+    pub fn new(transport: impl Transport + 'static, protocol: Protocol) -> Self {
+        let transport: Box<dyn Transport> = Box::new(transport);
+        let frame_delay = match protocol {
+            Protocol::Rtu => inter_frame_delay(BAUD_RATE),
+            Protocol::Ascii | Protocol::Tcp => Duration::from_millis(0),
+        };
+        Self {
+            stream: BufStream::new(transport),
+            protocol,
+            transaction_id: 0,
+            frame_delay,
+            last_frame: None,
+        }
+    }
+
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    /// Send a request to a slave and return the decoded response.
+    ///
+    /// Exception responses fail with an `ExceptionResponse` as the
+    /// inner error.
+    pub async fn call(&mut self, slave: u8, request: &Request<'_>) -> Result<Response> {
+        let mut pdu = [0; MAX_PDU_LEN];
+        let len = request.encode_pdu(&mut pdu)?;
+        let request_pdu = &pdu[..len];
+        let (response_slave, response) = match self.protocol {
+            Protocol::Rtu => self.call_rtu(slave, request_pdu).await?,
+            Protocol::Ascii => self.call_ascii(slave, request_pdu).await?,
+            Protocol::Tcp => self.call_tcp(slave, request_pdu).await?,
+        };
+        if !is_response_from(slave, response_slave) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Invalid response header: expected slave {}, actual slave {}",
+                    slave, response_slave
+                ),
+            ));
+        }
+        Ok(decode_response(request, &response)?)
+    }
+
+    async fn send(&mut self, frame: &[u8]) -> Result<()> {
+        self.stream.write_all(frame).await?;
+        self.stream.flush().await
+    }
+
+    async fn call_rtu(&mut self, slave: u8, request: &[u8]) -> Result<(u8, Vec<u8>)> {
+        let mut frame = Vec::with_capacity(request.len() + 3);
+        frame.push(slave);
+        frame.extend_from_slice(request);
+        frame.extend_from_slice(&crc16(&frame).to_le_bytes());
+        // Keep the silent interval after the previous frame
+        if let Some(deadline) = self
+            .last_frame
+            .map(|last_frame| last_frame + self.frame_delay)
+        {
+            if deadline > Instant::now() {
+                sleep_until(deadline).await;
+            }
+        }
+        self.send(&frame).await?;
+        self.last_frame = Some(Instant::now());
+
+        let mut adu = vec![0; MAX_FRAME_LEN];
+        self.stream
+            .read_exact(&mut adu[..RESPONSE_HEADER_LEN])
+            .await?;
+        let len = response_frame_len(request[0], &adu).map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Unexpected function code {} in response", adu[1]),
+            )
+        })?;
+        self.stream
+            .read_exact(&mut adu[RESPONSE_HEADER_LEN..len])
+            .await?;
+        self.last_frame = Some(Instant::now());
+        let (slave, pdu) =
+            decode_frame(&adu[..len]).map_err(|_| crate::error::Error::Crc { target: None })?;
+        Ok((slave, pdu.to_vec()))
+    }
+
+    async fn call_ascii(&mut self, slave: u8, request: &[u8]) -> Result<(u8, Vec<u8>)> {
+        let mut adu = Vec::with_capacity(request.len() + 1);
+        adu.push(slave);
+        adu.extend_from_slice(request);
+        let mut frame = [0; ascii::MAX_FRAME_LEN];
+        let len = ascii::encode_frame(&adu, &mut frame)?;
+        self.send(&frame[..len]).await?;
+
+        let mut line = Vec::with_capacity(ascii::MAX_FRAME_LEN);
+        loop {
+            line.clear();
+            if self.stream.read_until(b'\n', &mut line).await? == 0 {
+                return Err(ErrorKind::UnexpectedEof.into());
+            }
+            // Skip noise in front of the start of a frame
+            if let Some(start) = line.iter().rposition(|b| *b == FRAME_START) {
+                let mut adu = vec![0; MAX_ADU_LEN];
+                let len = ascii::decode_frame(&line[start..], &mut adu)?;
+                adu.truncate(len);
+                if adu.len() < 2 {
+                    return Err(Error::new(ErrorKind::InvalidData, "Invalid response"));
+                }
+                return Ok((adu[0], adu.split_off(1)));
+            }
+        }
+    }
+
+    async fn call_tcp(&mut self, slave: u8, request: &[u8]) -> Result<(u8, Vec<u8>)> {
+        self.transaction_id = self.transaction_id.wrapping_add(1);
+        let mut frame = Vec::with_capacity(request.len() + 7);
+        frame.extend_from_slice(&self.transaction_id.to_be_bytes());
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend_from_slice(&(request.len() as u16 + 1).to_be_bytes());
+        frame.push(slave);
+        frame.extend_from_slice(request);
+        self.send(&frame).await?;
+
+        let mut header = [0; 7];
+        self.stream.read_exact(&mut header).await?;
+        let transaction_id = u16::from_be_bytes([header[0], header[1]]);
+        let len = usize::from(u16::from_be_bytes([header[4], header[5]]));
+        if transaction_id != self.transaction_id || header[2..4] != [0, 0] || len < 2 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Invalid response header: {:02X?}", header),
+            ));
+        }
+        let mut response = vec![0; len - 1];
+        self.stream.read_exact(&mut response).await?;
+        Ok((header[6], response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio1::{io::duplex, runtime::Builder};
+
+    #[test]
+    fn tcp_and_ascii() {
+        let rt = Builder::new_current_thread().build().unwrap();
+        rt.block_on(async {
+            let (client, mut server) = duplex(1024);
+            let mut context = Context::new(client, Protocol::Tcp);
+            server
+                .write_all(&[0, 1, 0, 0, 0, 5, 17, 3, 2, 0x12, 0x34])
+                .await
+                .unwrap();
+            let request = Request::ReadHoldingRegisters {
+                addr: 246,
+                count: 1,
+            };
+            let response = context.call(17, &request).await.unwrap();
+            assert_eq!(
+                Response::ReadHoldingRegisters([0x1234][..].iter().copied().collect()),
+                response
+            );
+            let mut frame = [0; 12];
+            server.read_exact(&mut frame).await.unwrap();
+            assert_eq!([0, 1, 0, 0, 0, 6, 17, 3, 0, 246, 0, 1], frame);
+
+            let (client, mut server) = duplex(1024);
+            let mut context = Context::new(client, Protocol::Ascii);
+            server.write_all(b"\0:11830369\r\n").await.unwrap();
+            let err = context.call(17, &request).await.unwrap_err();
+            assert_eq!("Modbus function 3: Illegal data value", err.to_string());
+            let mut frame = [0; 17];
+            server.read_exact(&mut frame).await.unwrap();
+            assert_eq!(b":110300F60001F5\r\n", &frame);
+        });
+    }
+}
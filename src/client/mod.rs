@@ -0,0 +1,936 @@
+//! Asynchronous client with `async fn`s and `Send` futures on tokio 1,
+//! an alternative to `modbus::SlaveProxy` behind the optional feature
+//! `async`. `modrs` and the `Scheduler` still use `SlaveProxy`.
+//!
+//! All clients of a `Bus` share a single connection. Requests are
+//! serialized in the order of their arrival, and each request is sent
+//! to the slave of its client. After transport errors, timeouts and CRC
+//! failures the connection is dropped and re-established with the next
+//! request. Failed requests return an `error::Error` with the request.
+
+mod context;
+
+pub use self::context::{Context, Protocol, Transport};
+
+use crate::{
+    buildmap::{BitDef, Confirm, DeviceProfile, RegisterDef},
+    core::{
+        modbus::{
+            alarms::{AlarmStatus, STATUS_REGS},
+            plan::ReadPlan,
+            rtu::*,
+            scaled::{ScaledVar, Scaling, SCALING_REG_COUNT, SCALING_REG_START},
+            units::{special_unit_reg, unit_reg},
+            *,
+        },
+        units::{SpecialUnit, Unit, UnitValue, SPECIAL_UNIT_CODE},
+    },
+    error::{Error, Result, Target},
+};
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    io,
+    net::SocketAddr,
+    pin::Pin,
+    sync::{Arc, Mutex as StdMutex},
+    time::Duration,
+};
+use tokio1::{net::TcpStream, sync::Mutex};
+use tokio_serial5::{SerialPort, SerialStream};
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Establishes a new connection for a `Bus`.
+pub trait Connect: Send + Sync {
+    fn connect(&self) -> BoxFuture<'_, io::Result<Context>>;
+}
+
+impl<F, Fut> Connect for F
+where
+    F: Fn() -> Fut + Send + Sync,
+    Fut: Future<Output = io::Result<Context>> + Send + 'static,
+{
+    fn connect(&self) -> BoxFuture<'_, io::Result<Context>> {
+        Box::pin(self())
+    }
+}
+
+/// Opens a serial port with the settings of `core::modbus::rtu`.
+#[derive(Debug, Clone)]
+pub struct SerialConnector {
+    path: String,
+    framing: Framing,
+}
+
+impl SerialConnector {
+    pub fn new(path: impl Into<String>, framing: Framing) -> Self {
+        Self {
+            path: path.into(),
+            framing,
+        }
+    }
+}
+
+fn open_serial_port(path: &str, framing: Framing) -> io::Result<SerialStream> {
+    use tokio_serial5::{DataBits, FlowControl, Parity, StopBits};
+
+    let (data_bits, parity) = match framing {
+        Framing::Rtu => (DATA_BITS, PARITY),
+        Framing::Ascii => (ASCII_DATA_BITS, ASCII_PARITY),
+    };
+    let data_bits = match data_bits {
+        serialport::DataBits::Five => DataBits::Five,
+        serialport::DataBits::Six => DataBits::Six,
+        serialport::DataBits::Seven => DataBits::Seven,
+        serialport::DataBits::Eight => DataBits::Eight,
+    };
+    let parity = match parity {
+        serialport::Parity::None => Parity::None,
+        serialport::Parity::Odd => Parity::Odd,
+        serialport::Parity::Even => Parity::Even,
+    };
+    let stop_bits = match STOP_BITS {
+        serialport::StopBits::One => StopBits::One,
+        serialport::StopBits::Two => StopBits::Two,
+    };
+    let flow_control = match FLOW_CONTROL {
+        serialport::FlowControl::None => FlowControl::None,
+        serialport::FlowControl::Software => FlowControl::Software,
+        serialport::FlowControl::Hardware => FlowControl::Hardware,
+    };
+    let builder = tokio_serial5::new(path, BAUD_RATE)
+        .data_bits(data_bits)
+        .parity(parity)
+        .stop_bits(stop_bits)
+        .flow_control(flow_control);
+    let mut port = SerialStream::open(&builder)?;
+    //set the DTR pin
+    port.write_data_terminal_ready(true)?;
+    Ok(port)
+}
+
+impl Connect for SerialConnector {
+    fn connect(&self) -> BoxFuture<'_, io::Result<Context>> {
+        Box::pin(async move {
+            log::info!(
+                "Connecting to serial port {} ({:?})",
+                self.path,
+                self.framing
+            );
+            let port = open_serial_port(&self.path, self.framing)?;
+            Ok(Context::new(port, self.framing.into()))
+        })
+    }
+}
+
+/// Connects to a Modbus TCP device or gateway.
+#[derive(Debug, Clone)]
+pub struct TcpConnector {
+    socket_addr: SocketAddr,
+}
+
+impl TcpConnector {
+    pub fn new(socket_addr: SocketAddr) -> Self {
+        Self { socket_addr }
+    }
+}
+
+impl Connect for TcpConnector {
+    fn connect(&self) -> BoxFuture<'_, io::Result<Context>> {
+        Box::pin(async move {
+            log::info!("Connecting to {}", self.socket_addr);
+            let stream = TcpStream::connect(self.socket_addr).await?;
+            stream.set_nodelay(true)?;
+            Ok(Context::new(stream, Protocol::Tcp))
+        })
+    }
+}
+
+struct BusState {
+    connector: Box<dyn Connect>,
+    context: Option<Context>,
+}
+
+/// A connection shared by the clients of multiple slaves.
+///
+/// Clones share the connection.
+#[derive(Clone)]
+pub struct Bus {
+    state: Arc<Mutex<BusState>>,
+}
+
+fn timed_out() -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, "Modbus request timed out")
+}
+
+async fn with_timeout<T>(
+    timeout: Option<Duration>,
+    future: impl Future<Output = io::Result<T>>,
+) -> io::Result<T> {
+    match timeout {
+        Some(timeout) => tokio1::time::timeout(timeout, future)
+            .await
+            .unwrap_or_else(|_| Err(timed_out())),
+        None => future.await,
+    }
+}
+
+impl Bus {
+    /// Create a disconnected bus that connects with the first request.
+    pub fn new(connector: impl Connect + 'static) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(BusState {
+                connector: Box::new(connector),
+                context: None,
+            })),
+        }
+    }
+
+    pub async fn is_connected(&self) -> bool {
+        self.state.lock().await.context.is_some()
+    }
+
+    /// Replace the connection to recover from communication errors.
+    pub async fn reconnect(&self) -> Result<()> {
+        let mut state = self.state.lock().await;
+        state.context = None;
+        state.context = Some(state.connector.connect().await?);
+        Ok(())
+    }
+
+    pub async fn disconnect(&self) {
+        self.state.lock().await.context = None;
+    }
+
+    /// Send a request to a slave and return the decoded response, see
+    /// `Context::call()`.
+    ///
+    /// Connects first if necessary. The connection is dropped if the
+    /// error needs a reconnect, see `error::Error::needs_reconnect()`.
+    /// It is also dropped if the returned future is dropped before the
+    /// response has been received, because the response might still
+    /// arrive and be mistaken for the response of the next request.
+    pub async fn call(
+        &self,
+        slave: u8,
+        request: &Request<'_>,
+        timeout: Option<Duration>,
+    ) -> Result<Response> {
+        let mut state = self.state.lock().await;
+        let BusState { connector, context } = &mut *state;
+        // The connection is only put back after the request completed
+        let mut connected = match context.take() {
+            Some(connected) => connected,
+            None => with_timeout(timeout, connector.connect()).await?,
+        };
+        match with_timeout(timeout, connected.call(slave, request)).await {
+            Ok(response) => {
+                *context = Some(connected);
+                Ok(response)
+            }
+            Err(err) => {
+                let err = Error::from(err).with_target(Target::new(slave, request));
+                if err.needs_reconnect() {
+                    log::debug!("Dropping the connection after request failed: {}", err);
+                } else {
+                    *context = Some(connected);
+                }
+                Err(err)
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct ClientState {
+    byte_order: ByteOrder,
+    double_byte_order: ByteOrder,
+    scaling: Option<Scaling>,
+    units: HashMap<u16, Unit>,
+}
+
+/// The 0-based protocol address of the 1-based register `addr`.
+fn protocol_addr(addr: u16) -> Result<u16> {
+    addr.checked_sub(1).ok_or_else(|| Error::InvalidInput {
+        target: None,
+        message: "Register 0 does not exist".to_string(),
+    })
+}
+
+fn log_byte_order(reg_type: &str, res: Result<ByteOrder>, byte_order: ByteOrder) {
+    match res {
+        Ok(_) => log::info!("Using {} byte order {}", reg_type, byte_order),
+        Err(err) => log::warn!(
+            "Using default {} byte order {} after reading it failed: {}",
+            reg_type,
+            byte_order,
+            err
+        ),
+    }
+}
+
+/// Client of a single slave on a shared `Bus`.
+///
+/// Clones share the bus, the byte orders and the cached scaling block
+/// and units.
+#[derive(Clone)]
+pub struct Client {
+    bus: Bus,
+    slave: u8,
+    profile: Option<DeviceProfile>,
+    state: Arc<StdMutex<ClientState>>,
+}
+
+impl Client {
+    pub fn new(bus: Bus, slave: u8) -> Self {
+        Self {
+            bus,
+            slave,
+            profile: None,
+            state: Default::default(),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, ClientState> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Select the byte order of F32 registers instead of reading it
+    /// from the device on `connect()`.
+    pub fn with_byte_order(self, byte_order: ByteOrder) -> Self {
+        self.state().byte_order = byte_order;
+        self
+    }
+
+    pub fn byte_order(&self) -> ByteOrder {
+        self.state().byte_order
+    }
+
+    /// Select the byte order of F64 registers instead of reading it
+    /// from the device on `connect()`.
+    pub fn with_double_byte_order(self, byte_order: ByteOrder) -> Self {
+        self.state().double_byte_order = byte_order;
+        self
+    }
+
+    pub fn double_byte_order(&self) -> ByteOrder {
+        self.state().double_byte_order
+    }
+
+    /// Select the device profile that determines the access mode of
+    /// registers on writing.
+    pub fn with_profile(mut self, profile: Option<DeviceProfile>) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    pub fn slave(&self) -> u8 {
+        self.slave
+    }
+
+    pub fn profile(&self) -> Option<DeviceProfile> {
+        self.profile
+    }
+
+    pub fn bus(&self) -> &Bus {
+        &self.bus
+    }
+
+    /// Reconnect the shared bus to recover from communication errors.
+    pub async fn reconnect(&self) -> Result<()> {
+        self.bus.reconnect().await
+    }
+
+    /// Connect and configure the byte orders of F32 and F64 registers
+    /// as selected in the device.
+    ///
+    /// The default byte orders are kept if they cannot be read, e.g. if
+    /// the device does not support registers 521 or 1344.
+    pub async fn connect(&self, timeout: Option<Duration>) -> Result<()> {
+        self.reconnect().await?;
+        let res = self.read_byte_order(timeout).await;
+        log_byte_order("F32", res, self.byte_order());
+        let res = self.read_double_byte_order(timeout).await;
+        log_byte_order("F64", res, self.double_byte_order());
+        Ok(())
+    }
+
+    /// Read the byte order of F32 registers from the device and use it
+    /// for all following reads.
+    pub async fn read_byte_order(&self, timeout: Option<Duration>) -> Result<ByteOrder> {
+        let byte_order = self
+            .read_byte_order_reg(timeout, BYTE_ORDER_REG.protocol_addr())
+            .await?;
+        self.state().byte_order = byte_order;
+        Ok(byte_order)
+    }
+
+    /// Read the byte order of F64 registers from the device and use it
+    /// for all following reads.
+    pub async fn read_double_byte_order(&self, timeout: Option<Duration>) -> Result<ByteOrder> {
+        let byte_order = self
+            .read_byte_order_reg(timeout, DOUBLE_BYTE_ORDER_REG.protocol_addr())
+            .await?;
+        self.state().double_byte_order = byte_order;
+        Ok(byte_order)
+    }
+
+    async fn read_byte_order_reg(
+        &self,
+        timeout: Option<Duration>,
+        reg_start: u16,
+    ) -> Result<ByteOrder> {
+        let words = self.read_words(timeout, reg_start, 1).await?;
+        let code = *words.first().ok_or(DecodeError::InsufficientInput)?;
+        Ok(ByteOrder::from_code(code)?)
+    }
+
+    /// Switch the Modbus slave address of all connected devices.
+    pub async fn broadcast_slave(&self, timeout: Option<Duration>) -> Result<()> {
+        let words = [u16::from(self.slave)];
+        self.write_words(timeout, BROADCAST_REG_ADDR, &words).await
+    }
+
+    pub async fn read_generic(
+        &self,
+        timeout: Option<Duration>,
+        reg_start: u16,
+        reg_type: RegType,
+    ) -> Result<Vec<u16>> {
+        self.read_words(timeout, reg_start, reg_type.word_count())
+            .await
+    }
+
+    /// Read holding registers starting at the 0-based protocol address.
+    pub async fn read_words(
+        &self,
+        timeout: Option<Duration>,
+        reg_start: u16,
+        reg_count: u16,
+    ) -> Result<Vec<u16>> {
+        let request = Request::ReadHoldingRegisters {
+            addr: reg_start,
+            count: reg_count,
+        };
+        match self.bus.call(self.slave, &request, timeout).await? {
+            Response::ReadHoldingRegisters(words) => Ok(words.to_vec()),
+            _ => Err(DecodeError::InvalidData.into()),
+        }
+    }
+
+    /// Read input registers starting at the 0-based protocol address.
+    pub async fn read_input_registers(
+        &self,
+        timeout: Option<Duration>,
+        reg_start: u16,
+        reg_count: u16,
+    ) -> Result<Vec<u16>> {
+        let request = Request::ReadInputRegisters {
+            addr: reg_start,
+            count: reg_count,
+        };
+        match self.bus.call(self.slave, &request, timeout).await? {
+            Response::ReadInputRegisters(words) => Ok(words.to_vec()),
+            _ => Err(DecodeError::InvalidData.into()),
+        }
+    }
+
+    /// Read coils starting at the 0-based protocol address.
+    pub async fn read_coils(
+        &self,
+        timeout: Option<Duration>,
+        start: u16,
+        count: u16,
+    ) -> Result<Vec<bool>> {
+        let request = Request::ReadCoils { addr: start, count };
+        match self.bus.call(self.slave, &request, timeout).await? {
+            Response::ReadCoils(bits) => Ok(bits.iter().collect()),
+            _ => Err(DecodeError::InvalidData.into()),
+        }
+    }
+
+    /// Read discrete inputs starting at the 0-based protocol address.
+    pub async fn read_discrete_inputs(
+        &self,
+        timeout: Option<Duration>,
+        start: u16,
+        count: u16,
+    ) -> Result<Vec<bool>> {
+        let request = Request::ReadDiscreteInputs { addr: start, count };
+        match self.bus.call(self.slave, &request, timeout).await? {
+            Response::ReadDiscreteInputs(bits) => Ok(bits.iter().collect()),
+            _ => Err(DecodeError::InvalidData.into()),
+        }
+    }
+
+    /// Read the state of a coil or discrete input.
+    pub async fn read_bit(&self, timeout: Option<Duration>, def: &BitDef) -> Result<bool> {
+        let response = self
+            .bus
+            .call(self.slave, &def.read_request(), timeout)
+            .await?;
+        Ok(response
+            .into_bits()?
+            .get(0)
+            .ok_or(DecodeError::InsufficientInput)?)
+    }
+
+    /// Switch a coil on or off, e.g. to start the zero calibration or
+    /// to reset the totals.
+    ///
+    /// Writes to discrete inputs are refused and writes with a
+    /// destructive side effect must be confirmed explicitly.
+    pub async fn write_coil(
+        &self,
+        timeout: Option<Duration>,
+        def: &BitDef,
+        state: bool,
+        confirm: Confirm,
+    ) -> Result<()> {
+        let request = def.write_request(self.profile, state, confirm)?;
+        self.bus.call(self.slave, &request, timeout).await?;
+        Ok(())
+    }
+
+    /// Write holding registers starting at the 0-based protocol
+    /// address without checking the access, see `write_register()`.
+    ///
+    /// A single word is written with _Write Single Register_ (0x06) and
+    /// multiple words with _Write Multiple Registers_ (0x10).
+    async fn write_words(
+        &self,
+        timeout: Option<Duration>,
+        reg_start: u16,
+        words: &[u16],
+    ) -> Result<()> {
+        let request = Request::write_registers(reg_start, words);
+        self.bus.call(self.slave, &request, timeout).await?;
+        Ok(())
+    }
+
+    /// Read the scaling block of the scaled integers once and
+    /// return the cached values afterwards.
+    pub async fn read_scaling(&self, timeout: Option<Duration>) -> Result<Scaling> {
+        if let Some(scaling) = self.state().scaling {
+            return Ok(scaling);
+        }
+        let words = self
+            .read_words(timeout, SCALING_REG_START, SCALING_REG_COUNT)
+            .await?;
+        let scaling = Scaling::decode(&words)?;
+        self.state().scaling = Some(scaling);
+        Ok(scaling)
+    }
+
+    /// Discard the cached scaling block and units, e.g. after the
+    /// configuration of the device has been changed.
+    pub fn invalidate_cache(&self) {
+        let mut state = self.state();
+        state.scaling = None;
+        state.units.clear();
+    }
+
+    /// Read a scaled integer and convert it into the value of the
+    /// process variable.
+    pub async fn read_scaled(&self, timeout: Option<Duration>, var: ScaledVar) -> Result<f32> {
+        let scaling = self.read_scaling(timeout).await?;
+        let words = self.read_words(timeout, var.protocol_addr(), 1).await?;
+        Ok(scaling.decode_value(var, &words)?)
+    }
+
+    /// Read the unit from a unit code register, e.g. 39 for the mass
+    /// flow rate, once and return the cached unit afterwards.
+    ///
+    /// The label of a special unit is read from the corresponding unit
+    /// string register.
+    pub async fn read_unit(&self, timeout: Option<Duration>, unit_reg: u16) -> Result<Unit> {
+        if let Some(unit) = self.state().units.get(&unit_reg) {
+            return Ok(*unit);
+        }
+        let words = self
+            .read_words(timeout, protocol_addr(unit_reg)?, 1)
+            .await?;
+        let code = *words.first().ok_or(DecodeError::InsufficientInput)?;
+        let unit = match (Unit::from_code(code), special_unit_reg(unit_reg)) {
+            (Unit::Unknown(SPECIAL_UNIT_CODE), Some(label_reg)) => {
+                let words = self
+                    .read_words(timeout, label_reg.protocol_addr(), label_reg.word_count())
+                    .await?;
+                SpecialUnit::from_words(&words)
+                    .map(Unit::Special)
+                    .ok_or(DecodeError::InvalidData)?
+            }
+            (unit, _) => unit,
+        };
+        self.state().units.insert(unit_reg, unit);
+        Ok(unit)
+    }
+
+    /// Read the float or scaled integer register of a process variable
+    /// together with its unit, e.g. mass flow rate (247) in kg/s.
+    pub async fn read_measurement(
+        &self,
+        timeout: Option<Duration>,
+        addr: u16,
+    ) -> Result<UnitValue> {
+        let reg_type = if ScaledVar::from_addr(addr).is_some() {
+            RegType::U16
+        } else {
+            RegType::F32
+        };
+        let words = self
+            .read_words(timeout, protocol_addr(addr)?, reg_type.word_count())
+            .await?;
+        self.decode_measurement(timeout, addr, &words).await
+    }
+
+    /// Decode the words of a process variable, e.g. from `read_plan()`,
+    /// and tag the value with its unit, see `read_measurement()`.
+    ///
+    /// The scaling block and the unit are read unless they are cached.
+    pub async fn decode_measurement(
+        &self,
+        timeout: Option<Duration>,
+        addr: u16,
+        words: &[u16],
+    ) -> Result<UnitValue> {
+        let unit_reg = unit_reg(addr).ok_or_else(|| Error::InvalidInput {
+            target: None,
+            message: format!("Register {} has no unit", addr),
+        })?;
+        let value = if let Some(var) = ScaledVar::from_addr(addr) {
+            self.read_scaling(timeout).await?.decode_value(var, words)?
+        } else {
+            decode_f32(words, self.byte_order())?
+        };
+        let unit = self.read_unit(timeout, unit_reg).await?;
+        Ok(UnitValue::new(value, unit))
+    }
+
+    /// Read all status registers for a snapshot of the active alarms.
+    pub async fn read_alarms(&self, timeout: Option<Duration>) -> Result<AlarmStatus> {
+        // Consecutive status registers are read at once: 419–423,
+        // 433–434 and 2661
+        let mut status = AlarmStatus::default();
+        for (index, count) in [(0, 5), (5, 2), (7, 1)] {
+            let words = self
+                .read_words(timeout, STATUS_REGS[index] - 1, count)
+                .await?;
+            for (i, word) in words.into_iter().enumerate() {
+                status.set(STATUS_REGS[index + i], word)?;
+            }
+        }
+        Ok(status)
+    }
+
+    fn byte_order_of(&self, reg_type: RegType) -> ByteOrder {
+        match reg_type {
+            RegType::F64 => self.double_byte_order(),
+            _ => self.byte_order(),
+        }
+    }
+
+    /// Read and decode a holding or input register with the byte order
+    /// of the device, see `RegisterDef::decode()`.
+    pub async fn read_value(&self, timeout: Option<Duration>, def: &RegisterDef) -> Result<Value> {
+        let response = self
+            .bus
+            .call(self.slave, &def.read_request(), timeout)
+            .await?;
+        self.decode_value(def, &response.into_words()?)
+    }
+
+    /// Decode the words of a register, e.g. from `read_plan()`, with the
+    /// byte order of the device.
+    pub fn decode_value(&self, def: &RegisterDef, words: &[u16]) -> Result<Value> {
+        Ok(def.decode(words, self.byte_order_of(def.reg_type))?)
+    }
+
+    /// Read the words of all registers of a plan with one request per
+    /// block, in the order of the registers of the plan.
+    pub async fn read_plan(
+        &self,
+        timeout: Option<Duration>,
+        plan: &ReadPlan,
+    ) -> Result<Vec<Vec<u16>>> {
+        let mut block_words = Vec::with_capacity(plan.blocks().len());
+        for block in plan.blocks() {
+            block_words.push(
+                self.read_words(timeout, block.protocol_addr(), block.count)
+                    .await?,
+            );
+        }
+        let words = plan.split(&block_words)?;
+        Ok(words.into_iter().map(<[u16]>::to_vec).collect())
+    }
+
+    /// Write the raw words of a register.
+    ///
+    /// Writes to read-only registers are refused and writes with a
+    /// destructive side effect, e.g. clearing a total, must be
+    /// confirmed explicitly.
+    pub async fn write_register(
+        &self,
+        timeout: Option<Duration>,
+        def: &RegisterDef,
+        words: &[u16],
+        confirm: Confirm,
+    ) -> Result<()> {
+        let request = def.write_request(self.profile, words, confirm)?;
+        self.bus.call(self.slave, &request, timeout).await?;
+        Ok(())
+    }
+
+    /// Encode and write the value of a register with the byte order of
+    /// the device, see `write_register()`.
+    ///
+    /// The register is read back afterwards to verify the written value
+    /// unless it is write-only or writing has a side effect, e.g. a
+    /// command. Returns the value read back.
+    pub async fn write_value(
+        &self,
+        timeout: Option<Duration>,
+        def: &RegisterDef,
+        value: &Value,
+        confirm: Confirm,
+    ) -> Result<Value> {
+        let byte_order = self.byte_order_of(def.reg_type);
+        let words = def.encode(value, byte_order)?;
+        self.write_register(timeout, def, &words, confirm).await?;
+        if !def.verifies_write(self.profile) {
+            return Ok(value.clone());
+        }
+        let response = self
+            .bus
+            .call(self.slave, &def.read_request(), timeout)
+            .await?;
+        def.verify_write(&words, &response.into_words()?, byte_order)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::buildmap::RegisterMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio1::{
+        io::{duplex, AsyncReadExt, AsyncWriteExt, DuplexStream},
+        runtime::{Builder, Runtime},
+    };
+
+    type Registers = HashMap<(u8, u16), u16>;
+
+    fn runtime() -> Runtime {
+        Builder::new_multi_thread().enable_all().build().unwrap()
+    }
+
+    fn frame(adu: &[u8]) -> Vec<u8> {
+        let mut frame = adu.to_vec();
+        frame.extend_from_slice(&crc16(adu).to_le_bytes());
+        frame
+    }
+
+    /// Answer requests for registers and coils from `registers` and
+    /// ignore requests to unknown slaves. Input registers mirror the
+    /// holding registers and coils are on for non-zero values.
+    async fn serve(mut stream: DuplexStream, mut registers: Registers) {
+        let mut request = [0; 8];
+        while stream.read_exact(&mut request).await.is_ok() {
+            let slave = request[0];
+            let addr = u16::from_be_bytes([request[2], request[3]]);
+            let value = u16::from_be_bytes([request[4], request[5]]);
+            if !registers.keys().any(|(s, _)| *s == slave) {
+                continue;
+            }
+            let response = match request[1] {
+                0x01 => {
+                    let mut bytes = vec![0; usize::from(value).div_ceil(8)];
+                    for i in 0..value {
+                        if registers.get(&(slave, addr + i)).is_some_and(|v| *v != 0) {
+                            bytes[usize::from(i / 8)] |= 1 << (i % 8);
+                        }
+                    }
+                    let mut response = vec![slave, 0x01, bytes.len() as u8];
+                    response.extend_from_slice(&bytes);
+                    response
+                }
+                function @ (0x03 | 0x04) => {
+                    let words = (addr..addr + value)
+                        .map(|addr| registers.get(&(slave, addr)).copied())
+                        .collect::<Option<Vec<_>>>();
+                    match words {
+                        Some(words) => {
+                            let mut response = vec![slave, function, 2 * words.len() as u8];
+                            for word in words {
+                                response.extend_from_slice(&word.to_be_bytes());
+                            }
+                            response
+                        }
+                        None => vec![slave, function | 0x80, 0x02],
+                    }
+                }
+                0x05 | 0x06 => {
+                    registers.insert((slave, addr), value);
+                    request[..6].to_vec()
+                }
+                function => vec![slave, function | 0x80, 0x01],
+            };
+            stream.write_all(&frame(&response)).await.unwrap();
+        }
+    }
+
+    fn connector(registers: Registers, connects: Arc<AtomicUsize>) -> impl Connect {
+        move || {
+            connects.fetch_add(1, Ordering::SeqCst);
+            let (client, server) = duplex(1024);
+            tokio1::spawn(serve(server, registers.clone()));
+            async move { Ok(Context::new(client, Protocol::Rtu)) }
+        }
+    }
+
+    #[test]
+    fn shared_bus() {
+        let mut registers = Registers::new();
+        // 25 °C with byte order 3-4-1-2
+        registers.insert((1, 250), 0x0000);
+        registers.insert((1, 251), 0x41C8);
+        registers.insert((1, 40), 32);
+        registers.insert((2, 0), 7);
+        let connects = Arc::new(AtomicUsize::new(0));
+        let bus = Bus::new(connector(registers, Arc::clone(&connects)));
+        let first = Client::new(bus.clone(), 1);
+        let second = Client::new(bus.clone(), 2);
+        let third = Client::new(bus, 1);
+
+        let rt = runtime();
+        let measurement = rt.spawn(async move { first.read_measurement(None, 251).await });
+        let words = rt.spawn(async move { second.read_words(None, 0, 1).await });
+        let measurement = rt.block_on(measurement).unwrap().unwrap();
+        assert_eq!("25 degC", measurement.to_string());
+        assert_eq!(vec![7], rt.block_on(words).unwrap().unwrap());
+        assert_eq!(1, connects.load(Ordering::SeqCst));
+
+        let plan = ReadPlan::new(&[(251, 2), (41, 1), (252, 1)], 0);
+        assert_eq!(
+            vec![vec![0x0000, 0x41C8], vec![32], vec![0x41C8]],
+            rt.block_on(third.read_plan(None, &plan)).unwrap()
+        );
+
+        let err = rt.block_on(third.read_measurement(None, 0)).unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { target: None, .. }));
+        let err = rt.block_on(third.read_unit(None, 0)).unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { target: None, .. }));
+    }
+
+    #[test]
+    fn coils_and_input_registers() {
+        let mut registers = Registers::new();
+        registers.insert((1, 2), 0);
+        registers.insert((1, 55), 0);
+        registers.insert((1, 56), 1);
+        registers.insert((1, 246), 0x0000);
+        registers.insert((1, 247), 0x41C8);
+        let bus = Bus::new(connector(registers, Default::default()));
+        let client = Client::new(bus, 1);
+        let map = RegisterMap::from_reader(
+            "Type,Addr,Register Description
+,00003,Start sensor zero
+,00056,Reset all totals
+F32,300247,Mass Flow Rate
+"
+            .as_bytes(),
+        )
+        .unwrap();
+        let zero = map.coil(3).unwrap();
+        let reset = map.coil(56).unwrap();
+
+        runtime().block_on(async {
+            assert_eq!(
+                vec![false, true],
+                client.read_coils(None, 55, 2).await.unwrap()
+            );
+            assert!(!client.read_bit(None, zero).await.unwrap());
+            client
+                .write_coil(None, zero, true, Confirm::Yes)
+                .await
+                .unwrap();
+            assert!(client.read_bit(None, zero).await.unwrap());
+
+            let err = client
+                .write_coil(None, reset, true, Confirm::No)
+                .await
+                .unwrap_err();
+            assert!(matches!(err, Error::Refused { .. }));
+
+            let flow = map.input_register(247).unwrap();
+            assert_eq!(
+                Value::F32(25.0),
+                client.read_value(None, flow).await.unwrap()
+            );
+            let err = client
+                .write_value(None, flow, &Value::F32(0.0), Confirm::Yes)
+                .await
+                .unwrap_err();
+            assert!(matches!(err, Error::Refused { .. }));
+        });
+    }
+
+    #[test]
+    fn reconnect_after_errors() {
+        let mut registers = Registers::new();
+        registers.insert((1, 154), 0);
+        let connects = Arc::new(AtomicUsize::new(0));
+        let bus = Bus::new(connector(registers, Arc::clone(&connects)));
+        let client = Client::new(bus.clone(), 1);
+        let silent = Client::new(bus.clone(), 9);
+        let timeout = Some(Duration::from_millis(100));
+        let map = RegisterMap::from_reader(
+            "Type,Addr,Register Description\nU16,155,Damping\n".as_bytes(),
+        )
+        .unwrap();
+        let damping = map.get(155).unwrap();
+
+        runtime().block_on(async {
+            client
+                .write_register(timeout, damping, &[1], Confirm::No)
+                .await
+                .unwrap();
+            assert_eq!(vec![1], client.read_words(timeout, 154, 1).await.unwrap());
+
+            // Exceptions keep the connection
+            let err = client.read_words(timeout, 155, 1).await.unwrap_err();
+            assert_eq!(
+                Some(ExceptionCode::IllegalDataAddress),
+                err.exception_code()
+            );
+            assert_eq!(
+                Some(&Target {
+                    slave: 1,
+                    function: 3,
+                    addr: 155,
+                    count: 1
+                }),
+                err.target()
+            );
+            assert!(bus.is_connected().await);
+
+            let err = silent.read_words(timeout, 0, 1).await.unwrap_err();
+            assert!(matches!(err, Error::Timeout { .. }));
+            assert!(!bus.is_connected().await);
+
+            // Cancelled requests drop the connection
+            assert_eq!(vec![0], client.read_words(timeout, 154, 1).await.unwrap());
+            let cancelled =
+                tokio1::time::timeout(Duration::from_millis(100), silent.read_words(None, 0, 1));
+            assert!(cancelled.await.is_err());
+            assert!(!bus.is_connected().await);
+
+            // The new connection has a fresh register table
+            assert_eq!(vec![0], client.read_words(timeout, 154, 1).await.unwrap());
+        });
+        assert_eq!(3, connects.load(Ordering::SeqCst));
+    }
+}
@@ -1,8 +1,12 @@
 #[cfg(feature = "modbus")]
 pub mod modbus;
+pub mod units;
 
 use core::{fmt, result::Result, time::Duration};
 
+#[cfg(feature = "alloc")]
+use alloc::{string::String, vec::Vec};
+
 /// (Thermodynamic) Temperature.
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
 #[repr(transparent)]
@@ -34,19 +38,25 @@ impl Float {
     pub const fn from_string(read_val: f32) -> Self {
         Self(read_val)
     }
+
+    pub const fn to_f32(self) -> f32 {
+        self.0
+    }
 }
 
 impl fmt::Display for Float {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.to_string())
+        write!(f, "{}", self.0)
     }
 }
 
 /// Register
+#[cfg(feature = "alloc")]
 #[derive(Clone, Debug, PartialEq, PartialOrd)]
 #[repr(transparent)]
 pub struct Register(Vec<u16>);
 
+#[cfg(feature = "alloc")]
 impl Register {
     pub const fn from_byte(word: Vec<u16>) -> Self {
         Self(word)
@@ -59,6 +69,7 @@ impl Register {
     }
 }
 
+#[cfg(feature = "alloc")]
 impl fmt::Display for Register {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{:?}", self.clone().to_display())
@@ -66,18 +77,21 @@ impl fmt::Display for Register {
 }
 
 /// (Ascii Strings).
+#[cfg(feature = "alloc")]
 #[derive(Clone, Debug, PartialEq, PartialOrd)]
 #[repr(transparent)]
 pub struct Generic(String);
 
+#[cfg(feature = "alloc")]
 impl Generic {
     pub const fn from_generic(read_val: String) -> Self {
         Self(read_val)
     }
 }
+#[cfg(feature = "alloc")]
 impl fmt::Display for Generic {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.to_string())
+        write!(f, "{}", self.0)
     }
 }
 
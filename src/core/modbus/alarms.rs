@@ -0,0 +1,319 @@
+//! Alarms in the status registers 419–423, 433–434 and 2661.
+//!
+//! Every bit of a status register signals an alarm condition, most of
+//! them with an alarm code A1–A121 as displayed by the transmitter.
+//! Alarms are classified according to NAMUR NE107.
+
+use super::{regs, DecodeError, DecodeResult};
+use core::fmt;
+
+/// NAMUR NE107 status signal of an alarm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Category {
+    MaintenanceRequired,
+    OutOfSpec,
+    FunctionCheck,
+    Failure,
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Category::MaintenanceRequired => write!(f, "Maintenance required"),
+            Category::OutOfSpec => write!(f, "Out of spec"),
+            Category::FunctionCheck => write!(f, "Function check"),
+            Category::Failure => write!(f, "Failure"),
+        }
+    }
+}
+
+/// The alarm signaled by a single bit of a status register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AlarmDef {
+    pub reg: u16,
+    pub bit: u8,
+    /// Alarm code without the leading `A`, e.g. 100 for A100.
+    pub code: Option<u16>,
+    pub text: &'static str,
+    pub category: Category,
+}
+
+/// The status registers in the order of `AlarmStatus::words`.
+pub const STATUS_REGS: [u16; 8] = [
+    regs::BIT_0_CORE_EEPROM_CHECKSUM_ERROR_CONFIG_POWERDOWN.addr(),
+    regs::BIT_0_PRIMARY_MA_OUTPUT_SATURATED_A100.addr(),
+    regs::BIT_0_BURST_MODE_ENABLED_A106_AI_SIMULATE.addr(),
+    regs::BIT_0_API_VCF_TEMPERATURE_OUTSIDE_STANDARD_RANGE.addr(),
+    regs::BIT_0_CORE_PROTECTED_BOOT_SECTOR_FAULT_INVALID.addr(),
+    regs::BIT_0_K1_FCF_COMBINATION_UNRECOGNIZED.addr(),
+    regs::BIT_0_ATTACHED_CORE_DATA_LOSS_POSSIBLE.addr(),
+    regs::BIT_0_XMTR_EEPROM_CHECKSUM_ERROR_A18.addr(),
+];
+
+const fn alarm(reg: u16, bit: u8, code: u16, text: &'static str, category: Category) -> AlarmDef {
+    AlarmDef {
+        reg,
+        bit,
+        code: if code == 0 { None } else { Some(code) },
+        text,
+        category,
+    }
+}
+
+/// All known alarm bits ordered by register and bit.
+#[rustfmt::skip]
+pub static ALARMS: [AlarmDef; 57] = {
+    use Category::*;
+    [
+        alarm(419, 0, 1, "Core EEPROM Checksum Error", Failure),
+        alarm(419, 1, 2, "Core RAM Error", Failure),
+        alarm(419, 2, 7, "Real-Time Interrupt Failure", Failure),
+        alarm(419, 3, 3, "Sensor Failure", Failure),
+        alarm(419, 4, 4, "Temperature Sensor Failure", Failure),
+        alarm(419, 5, 5, "Input Overrange", OutOfSpec),
+        alarm(419, 6, 110, "Frequency Output Saturated", OutOfSpec),
+        alarm(419, 7, 6, "Transmitter Not Characterized", Failure),
+        alarm(419, 8, 8, "Density Overrange", OutOfSpec),
+        alarm(419, 9, 9, "Transmitter Initializing/Warming Up", FunctionCheck),
+        alarm(419, 10, 10, "Calibration Failure", Failure),
+        alarm(419, 11, 11, "Zero Too Low", Failure),
+        alarm(419, 12, 12, "Zero Too High", Failure),
+        alarm(419, 13, 13, "Zero Too Noisy", Failure),
+        alarm(419, 14, 14, "Transmitter Failed", Failure),
+        alarm(419, 15, 16, "Sensor RTD Failure", Failure),
+        alarm(420, 0, 100, "Primary mA Output Saturated", OutOfSpec),
+        alarm(420, 1, 113, "Secondary mA Output Saturated", OutOfSpec),
+        alarm(420, 2, 101, "Primary mA Output Fixed", FunctionCheck),
+        alarm(420, 3, 114, "Secondary mA Output Fixed", FunctionCheck),
+        alarm(420, 4, 102, "Drive Overrange", OutOfSpec),
+        alarm(420, 5, 111, "Frequency Output Fixed", FunctionCheck),
+        alarm(420, 6, 104, "Calibration in Progress", FunctionCheck),
+        alarm(420, 7, 105, "Slug Flow", OutOfSpec),
+        alarm(420, 8, 107, "Power Reset Occurred", MaintenanceRequired),
+        alarm(420, 9, 108, "Event 1 On", OutOfSpec),
+        alarm(420, 10, 109, "Event 2 On", OutOfSpec),
+        alarm(420, 11, 17, "Sensor Case RTD Failure", Failure),
+        alarm(420, 12, 20, "Calibration Factors Unentered", Failure),
+        alarm(421, 0, 106, "Burst Mode Enabled", FunctionCheck),
+        alarm(421, 1, 112, "Upgrade Transmitter Software", MaintenanceRequired),
+        alarm(421, 2, 115, "No Input from External Input or Polling", OutOfSpec),
+        alarm(421, 3, 118, "Discrete Output 1 Fixed", FunctionCheck),
+        alarm(421, 4, 119, "Discrete Output 2 Fixed", FunctionCheck),
+        alarm(421, 5, 26, "Sensor/Transmitter Communication Error", Failure),
+        alarm(421, 6, 22, "Core Configuration Database Corrupt", Failure),
+        alarm(421, 7, 23, "Core Totals Database Corrupt", Failure),
+        alarm(421, 8, 24, "Core Program Corrupt", Failure),
+        alarm(421, 9, 27, "Security Breach", Failure),
+        alarm(421, 10, 28, "Sensor/Transmitter Write Failure", Failure),
+        alarm(421, 11, 29, "Internal Communication Failure", Failure),
+        alarm(421, 12, 30, "Incorrect Board Type", Failure),
+        alarm(421, 13, 31, "Low Power", Failure),
+        alarm(421, 14, 32, "Outputs Fixed during Meter Verification", FunctionCheck),
+        alarm(422, 0, 116, "API: Temperature Outside Standard Range", OutOfSpec),
+        alarm(422, 1, 117, "API: Density Outside Standard Range", OutOfSpec),
+        alarm(422, 2, 120, "ED: Unable to Fit Curve Data", OutOfSpec),
+        alarm(422, 3, 121, "ED: Extrapolation Alarm", OutOfSpec),
+        alarm(422, 4, 33, "Insufficient Pickoff Signal", Failure),
+        alarm(422, 5, 34, "Meter Verification Failed", MaintenanceRequired),
+        alarm(422, 6, 35, "Meter Verification Aborted", MaintenanceRequired),
+        alarm(423, 0, 25, "Core Protected Boot Sector Fault", Failure),
+        alarm(433, 0, 21, "K1/FCF Combination Unrecognized", Failure),
+        alarm(434, 0, 103, "Data Loss Possible", MaintenanceRequired),
+        alarm(2661, 0, 18, "Transmitter EEPROM Checksum Error", Failure),
+        alarm(2661, 1, 19, "Transmitter RAM Error", Failure),
+        alarm(2661, 2, 0, "Transmitter Configuration Changed", MaintenanceRequired),
+    ]
+};
+
+/// An active alarm bit, possibly unknown.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Alarm {
+    pub reg: u16,
+    pub bit: u8,
+    pub def: Option<&'static AlarmDef>,
+}
+
+impl Alarm {
+    fn new(reg: u16, bit: u8) -> Self {
+        let def = ALARMS.iter().find(|def| def.reg == reg && def.bit == bit);
+        Self { reg, bit, def }
+    }
+
+    pub fn code(&self) -> Option<u16> {
+        self.def.and_then(|def| def.code)
+    }
+
+    /// Unknown bits are considered a failure.
+    pub fn category(&self) -> Category {
+        self.def.map_or(Category::Failure, |def| def.category)
+    }
+}
+
+impl fmt::Display for Alarm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(code) = self.code() {
+            write!(f, "A{}: ", code)?;
+        }
+        match self.def {
+            Some(def) => write!(f, "{} ({})", def.text, def.category),
+            None => write!(
+                f,
+                "Bit #{} of register {} ({})",
+                self.bit,
+                self.reg,
+                self.category()
+            ),
+        }
+    }
+}
+
+fn word_alarms(reg: u16, word: u16) -> impl Iterator<Item = Alarm> {
+    (0..16)
+        .filter(move |bit| word & (1 << bit) != 0)
+        .map(move |bit| Alarm::new(reg, bit))
+}
+
+/// All active alarms in the word of a status register.
+pub fn decode_alarms(reg: u16, word: u16) -> DecodeResult<impl Iterator<Item = Alarm>> {
+    if !STATUS_REGS.contains(&reg) {
+        return Err(DecodeError::InvalidInput);
+    }
+    Ok(word_alarms(reg, word))
+}
+
+/// Snapshot of all status registers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AlarmStatus {
+    /// The words of the registers in `STATUS_REGS`.
+    pub words: [u16; 8],
+}
+
+impl AlarmStatus {
+    /// Update the word of a status register.
+    pub fn set(&mut self, reg: u16, word: u16) -> DecodeResult<()> {
+        let index = STATUS_REGS
+            .iter()
+            .position(|status_reg| *status_reg == reg)
+            .ok_or(DecodeError::InvalidInput)?;
+        self.words[index] = word;
+        Ok(())
+    }
+
+    /// All active alarms ordered by register and bit.
+    pub fn active(&self) -> impl Iterator<Item = Alarm> + '_ {
+        STATUS_REGS
+            .iter()
+            .zip(self.words.iter())
+            .flat_map(|(reg, word)| word_alarms(*reg, *word))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|word| *word == 0)
+    }
+
+    pub fn is_active(&self, code: u16) -> bool {
+        self.active().any(|alarm| alarm.code() == Some(code))
+    }
+
+    /// The most severe category of all active alarms.
+    pub fn category(&self) -> Option<Category> {
+        self.active().map(|alarm| alarm.category()).max()
+    }
+
+    /// The alarms that appeared and cleared since a previous snapshot.
+    pub fn changes(&self, previous: &AlarmStatus) -> AlarmChanges {
+        let mut changes = AlarmChanges::default();
+        for i in 0..STATUS_REGS.len() {
+            changes.raised.words[i] = self.words[i] & !previous.words[i];
+            changes.cleared.words[i] = previous.words[i] & !self.words[i];
+        }
+        changes
+    }
+}
+
+/// Alarms that appeared or cleared between two snapshots.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AlarmChanges {
+    pub raised: AlarmStatus,
+    pub cleared: AlarmStatus,
+}
+
+impl AlarmChanges {
+    pub fn is_empty(&self) -> bool {
+        self.raised.is_empty() && self.cleared.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alarm_table() {
+        assert_eq!([419, 420, 421, 422, 423, 433, 434, 2661], STATUS_REGS);
+        for (i, def) in ALARMS.iter().enumerate() {
+            assert!(STATUS_REGS.contains(&def.reg));
+            assert!(def.bit < 16);
+            if let Some(next) = ALARMS.get(i + 1) {
+                assert!((def.reg, def.bit) < (next.reg, next.bit));
+            }
+        }
+    }
+
+    #[test]
+    fn decode_status_words() {
+        let alarms: Vec<_> = decode_alarms(420, 0x0081).unwrap().collect();
+        assert_eq!(2, alarms.len());
+        assert_eq!(Some(100), alarms[0].code());
+        assert_eq!(Category::OutOfSpec, alarms[0].category());
+        assert_eq!(
+            "A100: Primary mA Output Saturated (Out of spec)",
+            alarms[0].to_string()
+        );
+        assert_eq!("A105: Slug Flow (Out of spec)", alarms[1].to_string());
+        assert_eq!(
+            "Bit #15 of register 423 (Failure)",
+            decode_alarms(423, 0x8000)
+                .unwrap()
+                .next()
+                .unwrap()
+                .to_string()
+        );
+        assert_eq!(0, decode_alarms(419, 0).unwrap().count());
+        assert!(decode_alarms(424, 1).is_err());
+    }
+
+    #[test]
+    fn compare_snapshots() {
+        let mut previous = AlarmStatus::default();
+        previous.set(420, 0x0001).unwrap();
+        previous.set(2661, 0x0001).unwrap();
+        let mut current = previous;
+        current.set(2661, 0x0000).unwrap();
+        current.set(421, 0x0001).unwrap();
+        assert!(current.is_active(106));
+        assert!(!current.is_active(18));
+        assert_eq!(Some(Category::FunctionCheck), current.category());
+        assert_eq!(Some(Category::Failure), previous.category());
+
+        let changes = current.changes(&previous);
+        assert_eq!(
+            vec![Some(106)],
+            changes
+                .raised
+                .active()
+                .map(|alarm| alarm.code())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![Some(18)],
+            changes
+                .cleared
+                .active()
+                .map(|alarm| alarm.code())
+                .collect::<Vec<_>>()
+        );
+        assert!(current.changes(&current).is_empty());
+        assert_eq!(Err(DecodeError::InvalidInput), current.set(425, 1));
+    }
+}
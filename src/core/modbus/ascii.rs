@@ -0,0 +1,147 @@
+//! Framing of the Modbus ASCII transmission mode.
+//!
+//! A frame starts with `':'`, followed by the slave address, the PDU
+//! and the LRC as pairs of uppercase hex digits, and ends with CR LF.
+//! The functions operate on the ADU without the LRC, i.e. the slave
+//! address followed by the PDU.
+
+use super::{DecodeError, DecodeResult};
+
+pub const FRAME_START: u8 = b':';
+pub const FRAME_END: [u8; 2] = *b"\r\n";
+
+/// Maximum length of the slave address and the PDU.
+pub const MAX_ADU_LEN: usize = 254;
+
+/// Maximum length of a frame with the hex encoded ADU and LRC.
+pub const MAX_FRAME_LEN: usize = 1 + 2 * (MAX_ADU_LEN + 1) + FRAME_END.len();
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+
+/// Longitudinal redundancy check, the two's complement of the sum of
+/// all bytes.
+pub fn lrc(adu: &[u8]) -> u8 {
+    adu.iter()
+        .fold(0u8, |sum, b| sum.wrapping_add(*b))
+        .wrapping_neg()
+}
+
+fn decode_hex_digit(digit: u8) -> DecodeResult<u8> {
+    match digit {
+        b'0'..=b'9' => Ok(digit - b'0'),
+        b'A'..=b'F' => Ok(digit - b'A' + 10),
+        b'a'..=b'f' => Ok(digit - b'a' + 10),
+        _ => Err(DecodeError::InvalidInput),
+    }
+}
+
+/// Write the frame of an ADU into `output` and return its length.
+///
+/// Fails with `InvalidInput` if the ADU is empty or too long and with
+/// `InsufficientInput` if `output` is too short for the frame.
+pub fn encode_frame(adu: &[u8], output: &mut [u8]) -> DecodeResult<usize> {
+    if adu.is_empty() || adu.len() > MAX_ADU_LEN {
+        return Err(DecodeError::InvalidInput);
+    }
+    let len = 1 + 2 * (adu.len() + 1) + FRAME_END.len();
+    if output.len() < len {
+        return Err(DecodeError::InsufficientInput);
+    }
+    output[0] = FRAME_START;
+    let lrc = lrc(adu);
+    for (i, b) in adu.iter().chain(Some(&lrc)).enumerate() {
+        output[1 + 2 * i] = HEX_DIGITS[usize::from(b >> 4)];
+        output[2 + 2 * i] = HEX_DIGITS[usize::from(b & 0x0F)];
+    }
+    output[len - FRAME_END.len()..len].copy_from_slice(&FRAME_END);
+    Ok(len)
+}
+
+/// Decode a complete frame including start and end characters into
+/// `output` and return the length of the ADU without the LRC.
+///
+/// Fails with `InvalidInput` for malformed frames, `InvalidData` if
+/// the LRC does not match, and `InsufficientInput` if `output` is too
+/// short for the ADU.
+pub fn decode_frame(frame: &[u8], output: &mut [u8]) -> DecodeResult<usize> {
+    if frame.first() != Some(&FRAME_START) || !frame.ends_with(&FRAME_END) {
+        return Err(DecodeError::InvalidInput);
+    }
+    let digits = &frame[1..frame.len() - FRAME_END.len()];
+    if digits.len() < 4 || !digits.len().is_multiple_of(2) {
+        return Err(DecodeError::InvalidInput);
+    }
+    let len = digits.len() / 2 - 1;
+    if output.len() < len {
+        return Err(DecodeError::InsufficientInput);
+    }
+    let mut sum = 0u8;
+    for (i, pair) in digits.chunks(2).enumerate() {
+        let b = decode_hex_digit(pair[0])? << 4 | decode_hex_digit(pair[1])?;
+        if i < len {
+            output[i] = b;
+        }
+        sum = sum.wrapping_add(b);
+    }
+    // The sum over the ADU and its LRC is zero
+    if sum != 0 {
+        return Err(DecodeError::InvalidData);
+    }
+    Ok(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Read holding registers 108–110 of slave 17 from the Modbus
+    // serial line specification
+    const ADU: [u8; 6] = [0x11, 0x03, 0x00, 0x6B, 0x00, 0x03];
+    const FRAME: &[u8] = b":1103006B00037E\r\n";
+
+    #[test]
+    fn encode() {
+        assert_eq!(0x7E, lrc(&ADU));
+        let mut output = [0; MAX_FRAME_LEN];
+        let len = encode_frame(&ADU, &mut output).unwrap();
+        assert_eq!(FRAME, &output[..len]);
+        assert_eq!(
+            Err(DecodeError::InsufficientInput),
+            encode_frame(&ADU, &mut output[..FRAME.len() - 1])
+        );
+        assert_eq!(
+            Err(DecodeError::InvalidInput),
+            encode_frame(&[], &mut output)
+        );
+    }
+
+    #[test]
+    fn decode() {
+        let mut output = [0; MAX_ADU_LEN];
+        let len = decode_frame(FRAME, &mut output).unwrap();
+        assert_eq!(ADU, output[..len]);
+        let len = decode_frame(b":1103006b00037e\r\n", &mut output).unwrap();
+        assert_eq!(ADU, output[..len]);
+
+        assert_eq!(
+            Err(DecodeError::InvalidData),
+            decode_frame(b":1103006B00047E\r\n", &mut output)
+        );
+        for frame in &[
+            &b"1103006B00037E\r\n"[..],
+            b":1103006B00037E",
+            b":1103006B00037\r\n",
+            b":1103006B0G037E\r\n",
+            b":7E\r\n",
+        ] {
+            assert_eq!(
+                Err(DecodeError::InvalidInput),
+                decode_frame(frame, &mut output)
+            );
+        }
+        assert_eq!(
+            Err(DecodeError::InsufficientInput),
+            decode_frame(FRAME, &mut output[..5])
+        );
+    }
+}
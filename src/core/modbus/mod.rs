@@ -1,11 +1,20 @@
 use super::*;
 
-#[cfg(feature = "rtu")]
+pub mod alarms;
+pub mod ascii;
+#[cfg(feature = "alloc")]
+pub mod plan;
+pub mod regs;
+pub mod scaled;
+pub mod units;
+mod value;
+
 pub mod rtu;
 
-use byteorder::{BigEndian, LittleEndian, ReadBytesExt, WriteBytesExt};
 use core::{convert::TryInto, fmt, mem, str};
-use std::io::Read;
+
+pub use self::value::{decode, encode, Value};
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum DecodeError {
     InsufficientInput,
@@ -27,8 +36,143 @@ impl fmt::Display for DecodeError {
 #[cfg(feature = "std")]
 impl std::error::Error for DecodeError {}
 
+#[cfg(feature = "std")]
+impl From<DecodeError> for std::io::Error {
+    fn from(from: DecodeError) -> Self {
+        use std::io::ErrorKind;
+        use DecodeError::*;
+        match from {
+            InsufficientInput | InvalidInput => Self::new(ErrorKind::InvalidInput, from),
+            InvalidData => Self::new(ErrorKind::InvalidData, from),
+        }
+    }
+}
+
 pub type DecodeResult<T> = Result<T, DecodeError>;
 
+/// The exception code of an exception response.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ExceptionCode {
+    IllegalFunction,
+    /// The register or coil does not exist, e.g. on another device
+    /// family.
+    IllegalDataAddress,
+    IllegalDataValue,
+    SlaveDeviceFailure,
+    /// The slave accepted a request that takes a long time to process.
+    Acknowledge,
+    SlaveDeviceBusy,
+    MemoryParityError,
+    GatewayPathUnavailable,
+    GatewayTargetDevice,
+    Unknown(u8),
+}
+
+impl ExceptionCode {
+    pub const fn from_code(code: u8) -> Self {
+        use ExceptionCode::*;
+        match code {
+            0x01 => IllegalFunction,
+            0x02 => IllegalDataAddress,
+            0x03 => IllegalDataValue,
+            0x04 => SlaveDeviceFailure,
+            0x05 => Acknowledge,
+            0x06 => SlaveDeviceBusy,
+            0x08 => MemoryParityError,
+            0x0A => GatewayPathUnavailable,
+            0x0B => GatewayTargetDevice,
+            code => Unknown(code),
+        }
+    }
+
+    pub const fn code(self) -> u8 {
+        use ExceptionCode::*;
+        match self {
+            IllegalFunction => 0x01,
+            IllegalDataAddress => 0x02,
+            IllegalDataValue => 0x03,
+            SlaveDeviceFailure => 0x04,
+            Acknowledge => 0x05,
+            SlaveDeviceBusy => 0x06,
+            MemoryParityError => 0x08,
+            GatewayPathUnavailable => 0x0A,
+            GatewayTargetDevice => 0x0B,
+            Unknown(code) => code,
+        }
+    }
+
+    /// The description of the code, e.g. "Illegal data address".
+    ///
+    /// The wording of the server exceptions matches `tokio-modbus`.
+    pub const fn description(self) -> Option<&'static str> {
+        use ExceptionCode::*;
+        match self {
+            IllegalFunction => Some("Illegal function"),
+            IllegalDataAddress => Some("Illegal data address"),
+            IllegalDataValue => Some("Illegal data value"),
+            SlaveDeviceFailure => Some("Server device failure"),
+            Acknowledge => Some("Acknowledge"),
+            SlaveDeviceBusy => Some("Server device busy"),
+            MemoryParityError => Some("Memory parity error"),
+            GatewayPathUnavailable => Some("Gateway path unavailable"),
+            GatewayTargetDevice => Some("Gateway target device failed to respond"),
+            Unknown(_) => None,
+        }
+    }
+
+    /// The slave may answer the same request later.
+    pub const fn is_transient(self) -> bool {
+        matches!(
+            self,
+            ExceptionCode::Acknowledge | ExceptionCode::SlaveDeviceBusy
+        )
+    }
+}
+
+impl fmt::Display for ExceptionCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.description() {
+            Some(description) => f.write_str(description),
+            None => write!(f, "Exception code {}", self.code()),
+        }
+    }
+}
+
+/// An exception response of a slave, e.g. code 2 for an illegal data
+/// address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExceptionResponse {
+    /// Function code of the request
+    pub function: u8,
+    pub code: ExceptionCode,
+}
+
+impl ExceptionResponse {
+    /// Recover an exception response from its message, i.e. from the
+    /// errors of `tokio-modbus` that hide the exception type.
+    pub fn parse(message: &str) -> Option<Self> {
+        let rest = message.strip_prefix("Modbus function ")?;
+        let (function, description) = rest.split_once(": ")?;
+        let function = function.parse().ok()?;
+        let code = match description.strip_prefix("Exception code ") {
+            Some(code) => ExceptionCode::from_code(code.parse().ok()?),
+            None => (0..=u8::MAX)
+                .map(ExceptionCode::from_code)
+                .find(|code| code.description() == Some(description))?,
+        };
+        Some(Self { function, code })
+    }
+}
+
+impl fmt::Display for ExceptionResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Modbus function {}: {}", self.function, self.code)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ExceptionResponse {}
+
 fn decode_be_u16_from_bytes(input: &[u8]) -> DecodeResult<(u16, &[u8])> {
     if input.len() < mem::size_of::<u16>() {
         return Err(DecodeError::InsufficientInput);
@@ -41,108 +185,722 @@ fn decode_be_u16_from_bytes(input: &[u8]) -> DecodeResult<(u16, &[u8])> {
     }
 }
 
-fn decode_be_u32_from_bytes(input: &[u8]) -> DecodeResult<(u32, &[u8])> {
-    if input.len() < mem::size_of::<u32>() {
-        return Err(DecodeError::InsufficientInput);
+/// Data type of a register as listed in the Modbus map, e.g. `F32` or `A16`.
+///
+/// The length of `Ascii` is given in characters, i.e. `A16` occupies 8 words.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RegType {
+    U8,
+    U16,
+    U24,
+    U32,
+    U64,
+    F32,
+    F64,
+    Ascii(u16),
+}
+
+impl RegType {
+    /// Number of 16-bit words that need to be read for a register of this type.
+    pub const fn word_count(self) -> u16 {
+        use RegType::*;
+        match self {
+            U8 | U16 => 1,
+            U24 | U32 | F32 => 2,
+            U64 | F64 => 4,
+            Ascii(len) => len.div_ceil(2),
+        }
     }
-    let (head, rest) = input.split_at(mem::size_of::<u32>());
-    if let Ok(bytes) = head.try_into() {
-        Ok((u32::from_be_bytes(bytes), rest))
-    } else {
-        Err(DecodeError::InvalidInput)
+}
+
+impl str::FromStr for RegType {
+    type Err = DecodeError;
+
+    fn from_str(s: &str) -> DecodeResult<Self> {
+        use RegType::*;
+        let s = s.trim();
+        let (kind, bits) = s.split_at(s.char_indices().nth(1).map_or(s.len(), |(i, _)| i));
+        let bits: u16 = bits.parse().map_err(|_| DecodeError::InvalidInput)?;
+        match (kind, bits) {
+            ("U" | "u", 8) => Ok(U8),
+            ("U" | "u", 16) => Ok(U16),
+            ("U" | "u", 24) => Ok(U24),
+            ("U" | "u", 32) => Ok(U32),
+            ("U" | "u", 64) => Ok(U64),
+            ("F" | "f", 32) => Ok(F32),
+            ("F" | "f", 64) => Ok(F64),
+            ("A" | "a", len) if len > 0 => Ok(Ascii(len)),
+            _ => Err(DecodeError::InvalidInput),
+        }
+    }
+}
+
+impl fmt::Display for RegType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use RegType::*;
+        match self {
+            U8 => write!(f, "U8"),
+            U16 => write!(f, "U16"),
+            U24 => write!(f, "U24"),
+            U32 => write!(f, "U32"),
+            U64 => write!(f, "U64"),
+            F32 => write!(f, "F32"),
+            F64 => write!(f, "F64"),
+            Ascii(len) => write!(f, "A{}", len),
+        }
+    }
+}
+
+/// The data tables of the Modbus data model.
+///
+/// Manuals refer to the objects by 5-digit references with the table
+/// as the first digit, e.g. 00015 for coil 15 or 40247 for holding
+/// register 247.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Table {
+    Coil,
+    DiscreteInput,
+    InputRegister,
+    #[default]
+    HoldingRegister,
+}
+
+impl Table {
+    /// The first digit of a reference.
+    pub const fn prefix(self) -> u8 {
+        match self {
+            Table::Coil => 0,
+            Table::DiscreteInput => 1,
+            Table::InputRegister => 3,
+            Table::HoldingRegister => 4,
+        }
+    }
+
+    pub fn from_prefix(prefix: u8) -> Option<Self> {
+        match prefix {
+            0 => Some(Table::Coil),
+            1 => Some(Table::DiscreteInput),
+            3 => Some(Table::InputRegister),
+            4 => Some(Table::HoldingRegister),
+            _ => None,
+        }
+    }
+
+    /// Coils and discrete inputs hold single bits instead of words.
+    pub const fn is_bit(self) -> bool {
+        matches!(self, Table::Coil | Table::DiscreteInput)
+    }
+
+    /// Only coils and holding registers can be written.
+    pub const fn is_writable(self) -> bool {
+        matches!(self, Table::Coil | Table::HoldingRegister)
+    }
+
+    /// Parse a reference with the table as the first digit, e.g. `00015`
+    /// for coil 15, `30001` for input register 1 or `410001` for holding
+    /// register 10001.
+    ///
+    /// References with 5 digits cover the numbers 1 to 9999 and
+    /// references with 6 digits all numbers up to 65535.
+    pub fn parse_reference(s: &str) -> DecodeResult<(Self, u16)> {
+        let s = s.trim();
+        if !(5..=6).contains(&s.len()) || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(DecodeError::InvalidInput);
+        }
+        let (prefix, number) = s.split_at(1);
+        let table =
+            Table::from_prefix(prefix.as_bytes()[0] - b'0').ok_or(DecodeError::InvalidInput)?;
+        match number.parse() {
+            Ok(0) | Err(_) => Err(DecodeError::InvalidInput),
+            Ok(addr) => Ok((table, addr)),
+        }
+    }
+
+    /// Parse an address of the Modbus map, either a plain register
+    /// number of the holding registers like `247` or `20001` or a
+    /// reference that cannot be a plain number, i.e. a reference with a
+    /// leading zero like `00015` or with 6 digits like `300001`.
+    ///
+    /// Other numbers with 5 digits that start with the digit of a table,
+    /// e.g. `10001`, are ambiguous and rejected. They have to be written
+    /// as references with 6 digits, e.g. `410001` or `100001`.
+    pub fn parse_address(s: &str) -> DecodeResult<(Self, u16)> {
+        let s = s.trim();
+        if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(DecodeError::InvalidInput);
+        }
+        let first = s.as_bytes()[0] - b'0';
+        match s.len() {
+            6 => Self::parse_reference(s),
+            5 if first == 0 => Self::parse_reference(s),
+            5 if Table::from_prefix(first).is_some() => Err(DecodeError::InvalidInput),
+            _ => match s.parse() {
+                Ok(0) | Err(_) => Err(DecodeError::InvalidInput),
+                Ok(addr) => Ok((Table::HoldingRegister, addr)),
+            },
+        }
     }
 }
 
-pub fn count_calc(c: &char, n: u16) -> u16 {
-    match c {
-        'A' => n / 2,
-        'U' => {
-            if n <= 16 {
-                1
-            } else {
-                2
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Table::*;
+        match self {
+            Coil => write!(f, "coil"),
+            DiscreteInput => write!(f, "discrete input"),
+            InputRegister => write!(f, "input register"),
+            HoldingRegister => write!(f, "holding register"),
+        }
+    }
+}
+
+/// Byte order of floating point registers, i.e. the order in which
+/// the bytes 1 (most significant) to 4 of an IEEE 754 float are
+/// transmitted.
+///
+/// Configured in register 521 of the transmitter. The same orders apply
+/// to F64 registers as configured in register 1344, i.e. 3-4-1-2 reverses
+/// the order of all words and 2-1-4-3 swaps the bytes within each word.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ByteOrder {
+    /// 1-2-3-4
+    B1234,
+    /// 3-4-1-2, the default
+    #[default]
+    B3412,
+    /// 2-1-4-3
+    B2143,
+    /// 4-3-2-1
+    B4321,
+}
+
+/// The register that selects the `ByteOrder` of all F32 registers.
+pub const BYTE_ORDER_REG: regs::RegDef<u16> =
+    regs::ENUMERATED_FLOATING_POINT_BYTE_ORDERING_TYPE_FOR_MODBUS;
+
+/// The register that selects the `ByteOrder` of all F64 registers.
+pub const DOUBLE_BYTE_ORDER_REG: regs::RegDef<u16> =
+    regs::MODBUS_DOUBLE_PRECISION_FLOATING_POINT_BYTE_ORDER;
+
+impl ByteOrder {
+    pub const ALL: [ByteOrder; 4] = [
+        ByteOrder::B1234,
+        ByteOrder::B3412,
+        ByteOrder::B2143,
+        ByteOrder::B4321,
+    ];
+
+    /// The value of `BYTE_ORDER_REG`.
+    pub const fn code(self) -> u16 {
+        self as u16
+    }
+
+    pub fn from_code(code: u16) -> DecodeResult<Self> {
+        ByteOrder::ALL
+            .get(usize::from(code))
+            .copied()
+            .ok_or(DecodeError::InvalidData)
+    }
+
+    /// The positions of the transmitted bytes in the float.
+    const fn positions(self) -> [usize; 4] {
+        use ByteOrder::*;
+        match self {
+            B1234 => [1, 2, 3, 4],
+            B3412 => [3, 4, 1, 2],
+            B2143 => [2, 1, 4, 3],
+            B4321 => [4, 3, 2, 1],
+        }
+    }
+
+    const fn swaps_words(self) -> bool {
+        matches!(self, ByteOrder::B3412 | ByteOrder::B4321)
+    }
+
+    const fn swaps_bytes(self) -> bool {
+        matches!(self, ByteOrder::B2143 | ByteOrder::B4321)
+    }
+
+    /// Reorder the bytes of `words` as transmitted into big-endian order.
+    ///
+    /// Reordering is symmetric, i.e. `bytes` can also be split into
+    /// words for transmission with `copy_words_from_be_bytes()`.
+    pub fn copy_be_bytes(self, words: &[u16], bytes: &mut [u8]) {
+        debug_assert_eq!(2 * words.len(), bytes.len());
+        let len = words.len();
+        for (i, word) in words.iter().enumerate() {
+            let j = if self.swaps_words() { len - 1 - i } else { i };
+            let mut pair = word.to_be_bytes();
+            if self.swaps_bytes() {
+                pair.swap(0, 1);
             }
+            bytes[2 * j..2 * j + 2].copy_from_slice(&pair);
+        }
+    }
+
+    /// Split big-endian bytes into words for transmission.
+    pub fn copy_words_from_be_bytes(self, bytes: &[u8], words: &mut [u16]) {
+        debug_assert_eq!(2 * words.len(), bytes.len());
+        let len = words.len();
+        for (i, word) in words.iter_mut().enumerate() {
+            let j = if self.swaps_words() { len - 1 - i } else { i };
+            let mut pair = [bytes[2 * j], bytes[2 * j + 1]];
+            if self.swaps_bytes() {
+                pair.swap(0, 1);
+            }
+            *word = u16::from_be_bytes(pair);
+        }
+    }
+
+    /// Reorder the bytes of two words as transmitted into big-endian order.
+    pub fn to_be_bytes(self, words: [u16; 2]) -> [u8; 4] {
+        let mut bytes = [0; 4];
+        self.copy_be_bytes(&words, &mut bytes);
+        bytes
+    }
+
+    /// Reorder big-endian bytes into two words for transmission.
+    pub fn from_be_bytes(self, bytes: [u8; 4]) -> [u16; 2] {
+        let mut words = [0; 2];
+        self.copy_words_from_be_bytes(&bytes, &mut words);
+        words
+    }
+}
+
+impl fmt::Display for ByteOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, b, c, d] = self.positions();
+        write!(f, "{}-{}-{}-{}", a, b, c, d)
+    }
+}
+
+impl str::FromStr for ByteOrder {
+    type Err = DecodeError;
+
+    fn from_str(s: &str) -> DecodeResult<Self> {
+        let digits = s.trim().chars().filter(|c| *c != '-');
+        ByteOrder::ALL
+            .iter()
+            .copied()
+            .find(|order| {
+                let positions = order.positions();
+                let positions = positions.iter().map(|pos| char::from(b'0' + *pos as u8));
+                positions.eq(digits.clone())
+            })
+            .ok_or(DecodeError::InvalidInput)
+    }
+}
+
+/// Transmission mode on the serial line. Transmitters that accept
+/// ASCII only support the slave addresses 1–15, 32–47, 64–79 and
+/// 96–110, see register 313.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "std", derive(serde::Deserialize, serde::Serialize))]
+pub enum Framing {
+    /// Binary frames separated by silent intervals, the default
+    #[default]
+    Rtu,
+    /// ':'-framed hex characters with an LRC, see `ascii`
+    Ascii,
+}
+
+/// Decode a float from the words of an F32 register.
+pub fn decode_f32(words: &[u16], byte_order: ByteOrder) -> DecodeResult<f32> {
+    match *words {
+        [first, second, ..] => Ok(f32::from_be_bytes(byte_order.to_be_bytes([first, second]))),
+        _ => Err(DecodeError::InsufficientInput),
+    }
+}
+
+/// Decode a double from the words of an F64 register.
+pub fn decode_f64(words: &[u16], byte_order: ByteOrder) -> DecodeResult<f64> {
+    let words = words.get(..4).ok_or(DecodeError::InsufficientInput)?;
+    let mut bytes = [0; 8];
+    byte_order.copy_be_bytes(words, &mut bytes);
+    Ok(f64::from_be_bytes(bytes))
+}
+
+/// Fold the first `count` words into an integer, most significant
+/// word first.
+fn decode_be_words(words: &[u16], count: usize) -> DecodeResult<u64> {
+    let words = words.get(..count).ok_or(DecodeError::InsufficientInput)?;
+    Ok(words
+        .iter()
+        .fold(0, |value, word| (value << 16) | u64::from(*word)))
+}
+
+/// Decode a U8 register from the low-order byte of its word.
+pub fn decode_u8(words: &[u16]) -> DecodeResult<u8> {
+    Ok(decode_be_words(words, 1)? as u8)
+}
+
+pub fn decode_u16(words: &[u16]) -> DecodeResult<u16> {
+    Ok(decode_be_words(words, 1)? as u16)
+}
+
+/// Decode a U24 register from the low-order 3 bytes of its 2 words.
+pub fn decode_u24(words: &[u16]) -> DecodeResult<u32> {
+    Ok(decode_be_words(words, 2)? as u32 & 0x00FF_FFFF)
+}
+
+pub fn decode_u32(words: &[u16]) -> DecodeResult<u32> {
+    Ok(decode_be_words(words, 2)? as u32)
+}
+
+pub fn decode_u64(words: &[u16]) -> DecodeResult<u64> {
+    decode_be_words(words, 4)
+}
+
+/// Decode a U16 register that holds a two's complement value.
+pub fn decode_i16(words: &[u16]) -> DecodeResult<i16> {
+    decode_u16(words).map(|value| value as i16)
+}
+
+/// Decode a U32 register that holds a two's complement value.
+pub fn decode_i32(words: &[u16]) -> DecodeResult<i32> {
+    decode_u32(words).map(|value| value as i32)
+}
+
+/// Decode a U64 register that holds a two's complement value.
+pub fn decode_i64(words: &[u16]) -> DecodeResult<i64> {
+    decode_u64(words).map(|value| value as i64)
+}
+
+/// Decode any unsigned integer register according to its type.
+pub fn decode_uint(words: &[u16], reg_type: RegType) -> DecodeResult<u64> {
+    use RegType::*;
+    match reg_type {
+        U8 => decode_u8(words).map(u64::from),
+        U16 => decode_u16(words).map(u64::from),
+        U24 => decode_u24(words).map(u64::from),
+        U32 => decode_u32(words).map(u64::from),
+        U64 => decode_u64(words),
+        F32 | F64 | Ascii(_) => Err(DecodeError::InvalidInput),
+    }
+}
+
+/// Character set of the text in ASCII registers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TextEncoding {
+    /// Only 7-bit ASCII is accepted.
+    #[default]
+    Ascii,
+    /// Bytes above 0x7F are decoded as ISO 8859-1, e.g. 0xB0 as `°`.
+    Latin1,
+    /// Bytes above 0x7F are replaced by U+FFFD.
+    Replace,
+}
+
+/// Error when decoding the text of an ASCII register.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TextError {
+    /// Fewer words than needed for the length of the register.
+    InsufficientInput,
+    /// A byte that is not valid in the selected encoding.
+    InvalidByte { offset: usize, byte: u8 },
+    /// A character that cannot be encoded in the selected encoding.
+    InvalidChar { offset: usize, ch: char },
+    /// More characters than the length of the register.
+    TooLong { len: usize, max: u16 },
+}
+
+impl fmt::Display for TextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextError::InsufficientInput => write!(f, "Insufficient input"),
+            TextError::InvalidByte { offset, byte } => {
+                write!(f, "Invalid byte 0x{:02X} at offset {}", byte, offset)
+            }
+            TextError::InvalidChar { offset, ch } => {
+                write!(f, "Invalid character {:?} at offset {}", ch, offset)
+            }
+            TextError::TooLong { len, max } => {
+                write!(f, "Text of {} characters exceeds {} characters", len, max)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TextError {}
+
+impl From<TextError> for DecodeError {
+    fn from(from: TextError) -> Self {
+        match from {
+            TextError::InsufficientInput => DecodeError::InsufficientInput,
+            TextError::InvalidByte { .. } => DecodeError::InvalidData,
+            TextError::InvalidChar { .. } | TextError::TooLong { .. } => DecodeError::InvalidInput,
         }
-        'F' => 2,
-        _ => n,
-    }
-}
-
-//convert u16 words from xmttr to float
-// TODO: extend this for all types of reads (anything > 16 ) this fn Byte Order 3-4-1-2
-pub fn decode_f32_reg(read_bytes: Vec<u16>) -> DecodeResult<Temperature> {
-    let msb_word: u16 = read_bytes[0];
-    let first_byte: u8 = (msb_word >> 8) as u8;
-    let second_byte: u8 = msb_word as u8;
-    let lsb_word: u16 = read_bytes[1];
-    let third_byte: u8 = (lsb_word >> 8) as u8;
-    let fourth_byte: u8 = lsb_word as u8;
-    //byte order is (3-4-1-2)
-    let new_bytes: [u8; 4] = [third_byte, fourth_byte, first_byte, second_byte];
-    //convert be_bytes to float
-    let float_value: f32 = f32::from_be_bytes(new_bytes);
-    Ok(Temperature::from_degree_celsius(float_value))
-}
-
-/// decode Generic register
-pub fn decode_generic_reg(read_bytes: Vec<u16>) -> DecodeResult<Generic> {
-    //setup the new u8 vec
-    //go to len /2 and split string at '\0L'
-    let mut vec_u8: Vec<u8> = Vec::with_capacity(read_bytes.len() / 2);
-    read_bytes.into_iter().for_each(|val| {
-        vec_u8.extend(&val.to_be_bytes());
-    });
-    // TODO: refactor, exception panics here
-    let split_string = String::from_utf8(vec_u8).expect("invalid utf-8 seq");
-    let s: Vec<&str> = split_string.split("\0L").collect();
-    Ok(Generic::from_generic(s[0].to_string()))
-}
-
-pub fn decode_f_reg(read_bytes: Vec<u16>) -> DecodeResult<Float> {
-    let msb_word: u16 = read_bytes[0];
-    let first_byte: u8 = (msb_word >> 8) as u8;
-    let second_byte: u8 = msb_word as u8;
-    let lsb_word: u16 = read_bytes[1];
-    let third_byte: u8 = (lsb_word >> 8) as u8;
-    let fourth_byte: u8 = lsb_word as u8;
-    //byte order is (3-4-1-2)
-    let new_bytes: [u8; 4] = [third_byte, fourth_byte, first_byte, second_byte];
-    //convert be_bytes to float
-    let float_value: f32 = f32::from_be_bytes(new_bytes);
-    //TODO: this is ugly
-    Ok(Float::from_string(float_value))
-}
-//u8 to u32
-pub fn decode_u_reg(read_bytes: Vec<u16>) -> DecodeResult<Register> {
-    //println!("read_bytes.len(): {:?}",read_bytes.len());
-    match read_bytes.len() {
-        1 => {
-            let msb_word: u16 = read_bytes[0];
-            let byte1: u8 = (msb_word >> 8) as u8;
-            let byte2: u8 = msb_word as u8;
-            //don't swap u8's!
-            let u16_word: u16 = (byte1 as u16) << 8 | byte2 as u16;
-            //println!("new_bytes: {:?}", u16_word);
-            let new_bytes = vec![u16_word];
-
-            Ok(Register::from_byte(new_bytes))
+    }
+}
+
+/// Maximum number of words of a single read request.
+pub const MAX_WORD_COUNT: usize = 125;
+
+/// Maximum number of words of a single _Write Multiple Registers_
+/// request.
+pub const MAX_WRITE_WORD_COUNT: usize = 123;
+
+/// Maximum number of coils or discrete inputs of a single read request.
+pub const MAX_BIT_COUNT: usize = 2000;
+
+/// The states of consecutive coils or discrete inputs, packed into
+/// bytes as transmitted, i.e. the first bit is the least significant
+/// bit of the first byte.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Bits {
+    bytes: heapless::Vec<u8, { MAX_BIT_COUNT / 8 }>,
+    len: u16,
+}
+
+impl Bits {
+    /// Take the first `len` bits of packed bytes.
+    ///
+    /// Fails with `InvalidInput` if the number of bytes does not match.
+    pub fn from_bytes(bytes: &[u8], len: u16) -> DecodeResult<Self> {
+        if bytes.len() != usize::from(len).div_ceil(8) {
+            return Err(DecodeError::InvalidInput);
         }
-        _ => {
-            //this works for len 2 or up to u16, not implementing u64 at this time, rarely used
-            let msb_word: u16 = read_bytes[0];
-            let first_byte: u8 = (msb_word >> 8) as u8;
-            let second_byte: u8 = msb_word as u8;
-            let lsb_word: u16 = read_bytes[1];
-            let third_byte: u8 = (lsb_word >> 8) as u8;
-            let fourth_byte: u8 = lsb_word as u8;
-            //byte order is not (3-4-1-2)
-            let new_bytes = vec![
-                (first_byte as u16) << 8 | second_byte as u16,
-                (third_byte as u16) << 8 | fourth_byte as u16,
-            ];
-            Ok(Register::from_byte(new_bytes))
+        let bytes = heapless::Vec::from_slice(bytes).map_err(|()| DecodeError::InvalidInput)?;
+        Ok(Self { bytes, len })
+    }
+
+    pub fn len(&self) -> usize {
+        usize::from(self.len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<bool> {
+        if index >= self.len() {
+            return None;
         }
+        Some(self.bytes[index / 8] & (1 << (index % 8)) != 0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = bool> + '_ {
+        (0..self.len()).map(move |index| self.bytes[index / 8] & (1 << (index % 8)) != 0)
+    }
+
+    /// The packed bytes including the unused high bits of the last byte.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+/// Maximum number of characters of an ASCII register, i.e. A242.
+pub const MAX_TEXT_LEN: usize = 242;
+
+/// The words of an encoded register.
+pub type Words = heapless::Vec<u16, MAX_WORD_COUNT>;
+
+/// The text of a decoded ASCII register. Every character takes up to
+/// 3 bytes of UTF-8, e.g. U+FFFD.
+pub type Text = heapless::String<{ 3 * MAX_TEXT_LEN }>;
+
+/// Borrow the ASCII text from the bytes of a register without copying.
+///
+/// The text ends at the first NUL character and trailing spaces are
+/// trimmed.
+pub fn decode_ascii_from_bytes(input: &[u8]) -> Result<&str, TextError> {
+    let end = input
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(input.len());
+    let input = &input[..end];
+    if let Some(offset) = input.iter().position(|byte| !byte.is_ascii()) {
+        let byte = input[offset];
+        return Err(TextError::InvalidByte { offset, byte });
+    }
+    // All bytes are ASCII and thus valid UTF-8
+    let text = str::from_utf8(input).map_err(|_| TextError::InvalidByte { offset: 0, byte: 0 })?;
+    Ok(text.trim_end_matches(' '))
+}
+
+/// Decode the text of an ASCII register with `len` characters, e.g. 8
+/// for an A8 register that occupies 4 words.
+///
+/// The text ends at the first NUL character and trailing spaces are
+/// trimmed. Bytes after the terminating NUL are ignored.
+pub fn decode_ascii(words: &[u16], len: u16, encoding: TextEncoding) -> Result<Text, TextError> {
+    if usize::from(len) > MAX_TEXT_LEN {
+        return Err(TextError::TooLong {
+            len: usize::from(len),
+            max: MAX_TEXT_LEN as u16,
+        });
+    }
+    let words = words
+        .get(..usize::from(RegType::Ascii(len).word_count()))
+        .ok_or(TextError::InsufficientInput)?;
+    let bytes = words
+        .iter()
+        .flat_map(|word| word.to_be_bytes())
+        .take(usize::from(len))
+        .take_while(|byte| *byte != 0);
+    let mut text = Text::new();
+    for (offset, byte) in bytes.enumerate() {
+        let c = match encoding {
+            _ if byte.is_ascii() => char::from(byte),
+            TextEncoding::Ascii => return Err(TextError::InvalidByte { offset, byte }),
+            TextEncoding::Latin1 => char::from(byte),
+            TextEncoding::Replace => char::REPLACEMENT_CHARACTER,
+        };
+        // Cannot overflow with at most 3 bytes per character
+        text.push(c).map_err(|()| TextError::TooLong {
+            len: usize::from(len),
+            max: MAX_TEXT_LEN as u16,
+        })?;
+    }
+    text.truncate(text.trim_end_matches(' ').len());
+    Ok(text)
+}
+
+/// Encode a float into the words of an F32 register.
+pub fn encode_f32(value: f32, byte_order: ByteOrder) -> [u16; 2] {
+    byte_order.from_be_bytes(value.to_be_bytes())
+}
+
+/// Encode a double into the words of an F64 register.
+pub fn encode_f64(value: f64, byte_order: ByteOrder) -> [u16; 4] {
+    let mut words = [0; 4];
+    byte_order.copy_words_from_be_bytes(&value.to_be_bytes(), &mut words);
+    words
+}
+
+/// Split an integer into the words of a register, most significant
+/// word first.
+fn encode_be_words(value: u64, words: &mut [u16]) {
+    let len = words.len();
+    for (i, word) in words.iter_mut().enumerate() {
+        *word = (value >> (16 * (len - 1 - i))) as u16;
+    }
+}
+
+/// Encode a U24 register into the low-order 3 bytes of 2 words.
+pub fn encode_u24(value: u32) -> DecodeResult<[u16; 2]> {
+    if value > 0x00FF_FFFF {
+        return Err(DecodeError::InvalidInput);
+    }
+    Ok(encode_u32(value))
+}
+
+pub fn encode_u32(value: u32) -> [u16; 2] {
+    let mut words = [0; 2];
+    encode_be_words(value.into(), &mut words);
+    words
+}
+
+pub fn encode_u64(value: u64) -> [u16; 4] {
+    let mut words = [0; 4];
+    encode_be_words(value, &mut words);
+    words
+}
+
+/// Encode any unsigned integer register according to its type.
+///
+/// Values that exceed the range of the type are rejected.
+pub fn encode_uint(value: u64, reg_type: RegType) -> DecodeResult<Words> {
+    use RegType::*;
+    let max = match reg_type {
+        U8 => u64::from(u8::MAX),
+        U16 => u64::from(u16::MAX),
+        U24 => 0x00FF_FFFF,
+        U32 => u64::from(u32::MAX),
+        U64 => u64::MAX,
+        F32 | F64 | Ascii(_) => return Err(DecodeError::InvalidInput),
+    };
+    if value > max {
+        return Err(DecodeError::InvalidInput);
+    }
+    let mut words = [0; 4];
+    let words = &mut words[..usize::from(reg_type.word_count())];
+    encode_be_words(value, words);
+    Words::from_slice(words).map_err(|()| DecodeError::InvalidInput)
+}
+
+/// Encode the text of an ASCII register with `len` characters.
+///
+/// Shorter texts are padded with spaces. Characters that cannot be
+/// encoded are rejected or replaced by `?` if `encoding` is `Replace`.
+pub fn encode_ascii(text: &str, len: u16, encoding: TextEncoding) -> Result<Words, TextError> {
+    let char_count = text.chars().count();
+    if char_count > usize::from(len) || usize::from(len) > MAX_TEXT_LEN {
+        return Err(TextError::TooLong {
+            len: char_count,
+            max: len.min(MAX_TEXT_LEN as u16),
+        });
+    }
+    let mut words = Words::new();
+    words
+        .resize(usize::from(RegType::Ascii(len).word_count()), 0x2020)
+        .map_err(|()| TextError::TooLong {
+            len: char_count,
+            max: len,
+        })?;
+    for (offset, ch) in text.chars().enumerate() {
+        let byte = match encoding {
+            _ if ch.is_ascii() => ch as u8,
+            TextEncoding::Latin1 if u32::from(ch) <= 0xFF => ch as u8,
+            TextEncoding::Replace => b'?',
+            TextEncoding::Ascii | TextEncoding::Latin1 => {
+                return Err(TextError::InvalidChar { offset, ch })
+            }
+        };
+        let word = &mut words[offset / 2];
+        *word = if offset % 2 == 0 {
+            (*word & 0x00FF) | u16::from(byte) << 8
+        } else {
+            (*word & 0xFF00) | u16::from(byte)
+        };
+    }
+    Ok(words)
+}
+
+/// Decode a float with the default byte order as a temperature, see
+/// `decode()` for all other registers.
+pub fn decode_f32_reg(words: &[u16]) -> DecodeResult<Temperature> {
+    decode_f32(words, ByteOrder::default()).map(Temperature::from_degree_celsius)
+}
+
+/// Decode an ASCII register of any length, see `decode_ascii()`.
+#[cfg(feature = "alloc")]
+pub fn decode_generic_reg(words: &[u16]) -> DecodeResult<Generic> {
+    use core::convert::TryFrom;
+
+    let len = u16::try_from(words.len() * 2).map_err(|_| DecodeError::InvalidInput)?;
+    let text = decode_ascii(words, len, TextEncoding::Ascii)?;
+    Ok(Generic::from_generic(text.as_str().into()))
+}
+
+/// Decode a float with the default byte order, see `decode_f32()`.
+pub fn decode_f_reg(words: &[u16]) -> DecodeResult<Float> {
+    decode_f32(words, ByteOrder::default()).map(Float::from_string)
+}
+
+/// Copy the first 2 words of an integer register, see `decode()` for
+/// decoding its value.
+#[cfg(feature = "alloc")]
+pub fn decode_u_reg(words: &[u16]) -> DecodeResult<Register> {
+    if words.is_empty() {
+        return Err(DecodeError::InsufficientInput);
     }
+    let len = words.len().min(2);
+    Ok(Register::from_byte(words[..len].into()))
+}
+
+pub const TEMPERATURE_REG_START: u16 = 0x0000;
+pub const TEMPERATURE_REG_COUNT: u16 = 0x0001;
+
+pub fn decode_temperature_from_u16(input: u16) -> DecodeResult<Temperature> {
+    let degree_celsius = f64::from(input) / 100f64 - 100f64;
+    Ok(Temperature::from_degree_celsius(degree_celsius as f32))
+}
+
+pub fn decode_temperature_from_bytes(input: &[u8]) -> DecodeResult<(Temperature, &[u8])> {
+    decode_be_u16_from_bytes(input)
+        .and_then(|(val, rest)| Ok((decode_temperature_from_u16(val)?, rest)))
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -220,10 +978,294 @@ pub fn decode_raw_counts_from_bytes(input: &[u8]) -> DecodeResult<(RawCounts, &[
 pub const BROADCAST_SLAVE_ADDR: u8 = 0x6F; //d111
 pub const BROADCAST_REG_ADDR: u16 = 0x0138; //d312
 
+/// Whether a response comes from the addressed slave. Requests to the
+/// broadcast address are answered with slave 0.
+pub fn is_response_from(slave: u8, response_slave: u8) -> bool {
+    response_slave == slave || (slave == BROADCAST_SLAVE_ADDR && response_slave == 0)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn parse_reg_type() {
+        assert_eq!(Ok(RegType::F32), "F32".parse());
+        assert_eq!(Ok(RegType::U16), "u16".parse());
+        assert_eq!(Ok(RegType::Ascii(242)), "A242".parse());
+        assert_eq!(121, RegType::Ascii(242).word_count());
+        assert_eq!(4, RegType::F64.word_count());
+        assert_eq!(2, RegType::U24.word_count());
+        assert!("F23".parse::<RegType>().is_err());
+        assert!("A0".parse::<RegType>().is_err());
+        assert!("".parse::<RegType>().is_err());
+        assert_eq!("A16", RegType::Ascii(16).to_string());
+    }
+
+    #[test]
+    fn exception_codes() {
+        for code in 0..=u8::MAX {
+            assert_eq!(code, ExceptionCode::from_code(code).code());
+        }
+        let exception = ExceptionResponse {
+            function: 3,
+            code: ExceptionCode::IllegalDataAddress,
+        };
+        assert_eq!(
+            "Modbus function 3: Illegal data address",
+            exception.to_string()
+        );
+        assert_eq!(
+            Some(exception),
+            ExceptionResponse::parse(&exception.to_string())
+        );
+        let unknown = ExceptionResponse {
+            function: 16,
+            code: ExceptionCode::Unknown(0x42),
+        };
+        assert_eq!("Modbus function 16: Exception code 66", unknown.to_string());
+        assert_eq!(
+            Some(unknown),
+            ExceptionResponse::parse(&unknown.to_string())
+        );
+        assert_eq!(None, ExceptionResponse::parse("Modbus function 3"));
+        assert!(ExceptionCode::SlaveDeviceBusy.is_transient());
+    }
+
+    #[test]
+    fn parse_references() {
+        assert_eq!(
+            Ok((Table::HoldingRegister, 247)),
+            Table::parse_reference("40247")
+        );
+        assert_eq!(Ok((Table::Coil, 15)), Table::parse_reference(" 00015"));
+        assert_eq!(
+            Ok((Table::DiscreteInput, 1)),
+            Table::parse_reference("10001")
+        );
+        assert_eq!(
+            Ok((Table::HoldingRegister, 10001)),
+            Table::parse_reference("410001")
+        );
+        assert_eq!(
+            Ok((Table::InputRegister, 65535)),
+            Table::parse_reference("365535")
+        );
+        for invalid in &["", "247", "00000", "20001", "465536", "x0001", "4000001"] {
+            assert!(Table::parse_reference(invalid).is_err(), "{}", invalid);
+        }
+
+        assert_eq!(
+            Ok((Table::HoldingRegister, 247)),
+            Table::parse_address("247")
+        );
+        assert_eq!(
+            Ok((Table::HoldingRegister, 20001)),
+            Table::parse_address("20001")
+        );
+        assert_eq!(Ok((Table::Coil, 15)), Table::parse_address("00015"));
+        assert_eq!(
+            Ok((Table::HoldingRegister, 10001)),
+            Table::parse_address("410001")
+        );
+        assert_eq!(
+            Ok((Table::DiscreteInput, 1)),
+            Table::parse_address("100001")
+        );
+        // Holding register 10001 or discrete input 1
+        for invalid in &[
+            "", "0", "10001", "30001", "40247", "65536", "x247", "-1", "é0001",
+        ] {
+            assert!(Table::parse_address(invalid).is_err(), "{}", invalid);
+        }
+        assert!(Table::Coil.is_bit() && Table::Coil.is_writable());
+        assert!(!Table::InputRegister.is_bit() && !Table::InputRegister.is_writable());
+        assert_eq!(Some(Table::DiscreteInput), Table::from_prefix(1));
+    }
+
+    #[test]
+    fn bits() {
+        let bits = Bits::from_bytes(&[0b1100_1101, 0b0000_0001], 9).unwrap();
+        assert_eq!(9, bits.len());
+        assert_eq!(Some(true), bits.get(0));
+        assert_eq!(Some(false), bits.get(1));
+        assert_eq!(Some(true), bits.get(8));
+        assert_eq!(None, bits.get(9));
+        let states: Vec<_> = bits.iter().collect();
+        assert_eq!(
+            vec![true, false, true, true, false, false, true, true, true],
+            states
+        );
+        assert_eq!(Err(DecodeError::InvalidInput), Bits::from_bytes(&[0], 9));
+        assert!(Bits::from_bytes(&[], 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn decode_byte_orders() {
+        // 1.5 = 0x3FC00000
+        let cases = [
+            (ByteOrder::B1234, [0x3FC0, 0x0000]),
+            (ByteOrder::B3412, [0x0000, 0x3FC0]),
+            (ByteOrder::B2143, [0xC03F, 0x0000]),
+            (ByteOrder::B4321, [0x0000, 0xC03F]),
+        ];
+        for (order, words) in cases.iter() {
+            assert_eq!(Ok(1.5), decode_f32(words, *order));
+            assert_eq!(*words, order.from_be_bytes(1.5f32.to_be_bytes()));
+            assert_eq!(Ok(*order), ByteOrder::from_code(order.code()));
+            assert_eq!(Ok(*order), order.to_string().parse());
+        }
+        assert_eq!(Ok(1.5), decode_f_reg(&[0x0000, 0x3FC0]).map(Float::to_f32));
+        assert_eq!(
+            Err(DecodeError::InsufficientInput),
+            decode_f32(&[0x3FC0], ByteOrder::B1234)
+        );
+        assert_eq!(Err(DecodeError::InvalidData), ByteOrder::from_code(4));
+        assert_eq!(Ok(ByteOrder::B2143), "2143".parse());
+        assert_eq!(521, BYTE_ORDER_REG.addr());
+    }
+
+    #[test]
+    fn decode_doubles() {
+        // 1234567.890625 = 0x4132D687_E4000000
+        let cases = [
+            (ByteOrder::B1234, [0x4132, 0xD687, 0xE400, 0x0000]),
+            (ByteOrder::B3412, [0x0000, 0xE400, 0xD687, 0x4132]),
+            (ByteOrder::B2143, [0x3241, 0x87D6, 0x00E4, 0x0000]),
+            (ByteOrder::B4321, [0x0000, 0x00E4, 0x87D6, 0x3241]),
+        ];
+        for (order, words) in cases.iter() {
+            assert_eq!(Ok(1_234_567.890_625), decode_f64(words, *order));
+            let mut bytes = [0; 8];
+            order.copy_be_bytes(words, &mut bytes);
+            let mut encoded = [0; 4];
+            order.copy_words_from_be_bytes(&bytes, &mut encoded);
+            assert_eq!(*words, encoded);
+        }
+        assert_eq!(
+            Err(DecodeError::InsufficientInput),
+            decode_f64(&[0; 2], ByteOrder::B1234)
+        );
+        assert_eq!(1344, DOUBLE_BYTE_ORDER_REG.addr());
+    }
+
+    #[test]
+    fn decode_integers() {
+        assert_eq!(Ok(0x34), decode_u8(&[0x0034]));
+        assert_eq!(Ok(0xABCD), decode_u16(&[0xABCD]));
+        assert_eq!(Ok(0x12_3456), decode_u24(&[0x0012, 0x3456]));
+        assert_eq!(Ok(0x1234_5678), decode_u32(&[0x1234, 0x5678]));
+        assert_eq!(
+            Ok(0x0123_4567_89AB_CDEF),
+            decode_u64(&[0x0123, 0x4567, 0x89AB, 0xCDEF])
+        );
+        assert_eq!(Ok(-2), decode_i16(&[0xFFFE]));
+        assert_eq!(Ok(-65536), decode_i32(&[0xFFFF, 0x0000]));
+        assert_eq!(Ok(-1), decode_i64(&[0xFFFF; 4]));
+        assert_eq!(Ok(0x12_3456), decode_uint(&[0x0012, 0x3456], RegType::U24));
+        assert_eq!(
+            Err(DecodeError::InsufficientInput),
+            decode_uint(&[0x1234], RegType::U32)
+        );
+        assert_eq!(
+            Err(DecodeError::InvalidInput),
+            decode_uint(&[0; 2], RegType::F32)
+        );
+    }
+
+    #[test]
+    fn decode_text() {
+        // "FT-101" padded with spaces and NULs
+        let tag = [
+            0x4654, 0x2D31, 0x3031, 0x2020, 0x0000, 0x0000, 0x0000, 0x0000,
+        ];
+        assert_eq!(
+            Ok("FT-101"),
+            decode_ascii(&tag, 16, TextEncoding::Ascii).as_deref()
+        );
+        // "degC" followed by the old "\0L" marker and garbage
+        let unit = [0x6465, 0x6743, 0x004C, 0xFFFF];
+        assert_eq!(
+            Ok("degC"),
+            decode_ascii(&unit, 8, TextEncoding::Ascii).as_deref()
+        );
+        assert_eq!(Ok("degC"), decode_ascii_from_bytes(b"degC\0L\xFF\xFF"));
+        assert_eq!(Ok("FT-101"), decode_ascii_from_bytes(b"FT-101  "));
+        // "°C" with a Latin-1 degree sign
+        let latin1 = [0xB043, 0x0000];
+        let invalid = TextError::InvalidByte {
+            offset: 0,
+            byte: 0xB0,
+        };
+        assert_eq!(
+            Err(&invalid),
+            decode_ascii(&latin1, 4, TextEncoding::Ascii).as_deref()
+        );
+        assert_eq!(Err(invalid), decode_ascii_from_bytes(b"\xB0C"));
+        assert_eq!(
+            Ok("°C"),
+            decode_ascii(&latin1, 4, TextEncoding::Latin1).as_deref()
+        );
+        assert_eq!(
+            Ok("\u{FFFD}C"),
+            decode_ascii(&latin1, 4, TextEncoding::Replace).as_deref()
+        );
+        // The 26th byte of an A25 register is not part of the text
+        let long = [0x4141; 13];
+        assert_eq!(
+            25,
+            decode_ascii(&long, 25, TextEncoding::Ascii).unwrap().len()
+        );
+        assert_eq!(
+            3 * 242,
+            decode_ascii(&[0xFFFF; 121], 242, TextEncoding::Replace)
+                .unwrap()
+                .len()
+        );
+        assert_eq!(
+            Err(&TextError::InsufficientInput),
+            decode_ascii(&[0x4141; 3], 8, TextEncoding::Ascii).as_deref()
+        );
+        assert_eq!(
+            Err(&TextError::TooLong { len: 250, max: 242 }),
+            decode_ascii(&[0x4141; 125], 250, TextEncoding::Ascii).as_deref()
+        );
+        assert_eq!(
+            Err(DecodeError::InvalidData),
+            decode_generic_reg(&[0x4142, 0xFF00])
+        );
+        assert_eq!(
+            "AB",
+            decode_generic_reg(&[0x4142, 0x0000]).unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn encode_text() {
+        let encode =
+            |text, len, encoding| encode_ascii(text, len, encoding).map(|words| words.to_vec());
+        assert_eq!(
+            Ok(vec![0x6465, 0x6743]),
+            encode("degC", 4, TextEncoding::Ascii)
+        );
+        assert_eq!(Ok(vec![0x4120]), encode("A", 1, TextEncoding::Ascii));
+        assert_eq!(
+            Err(TextError::InvalidChar {
+                offset: 0, ch: '°'
+            }),
+            encode("°C", 2, TextEncoding::Ascii)
+        );
+        assert_eq!(Ok(vec![0xB043]), encode("°C", 2, TextEncoding::Latin1));
+        assert_eq!(Ok(vec![0x3F43]), encode("€C", 2, TextEncoding::Replace));
+        assert_eq!(
+            Err(TextError::TooLong { len: 3, max: 2 }),
+            encode("abc", 2, TextEncoding::Ascii)
+        );
+        assert_eq!(Ok(vec![0x2020; 121]), encode("", 242, TextEncoding::Ascii));
+        assert_eq!(Ok([0x0012, 0x3456]), encode_u24(0x12_3456));
+        assert_eq!(Err(DecodeError::InvalidInput), encode_u24(0x100_0000));
+    }
+
     #[test]
     fn decode_temperature() {
         assert_eq!(
@@ -0,0 +1,159 @@
+//! Planning of block reads that fetch many registers with as few
+//! requests as possible.
+
+use super::{DecodeError, DecodeResult, MAX_WORD_COUNT};
+
+use alloc::vec::Vec;
+
+/// Consecutive registers that are read with a single request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReadBlock {
+    /// Register number of the first word
+    pub start: u16,
+    pub count: u16,
+    /// Index of each register in the plan with the offset and number of
+    /// its words within the block
+    regs: Vec<(usize, u16, u16)>,
+}
+
+impl ReadBlock {
+    pub fn protocol_addr(&self) -> u16 {
+        self.start - 1
+    }
+
+    fn end(&self) -> u32 {
+        u32::from(self.start) + u32::from(self.count)
+    }
+}
+
+/// Groups registers into the fewest block reads of at most
+/// `MAX_WORD_COUNT` words and splits the words of the blocks back into
+/// the words of each register.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReadPlan {
+    blocks: Vec<ReadBlock>,
+    len: usize,
+}
+
+impl ReadPlan {
+    /// Plan the reads of registers, given as register number and word
+    /// count, e.g. `(247, 2)` for the mass flow rate.
+    ///
+    /// Registers that are at most `max_gap` words apart are read with
+    /// the same request including the words in between. Some devices
+    /// reject reads of undefined registers, so the gap should only be
+    /// raised if all registers in between exist.
+    pub fn new(regs: &[(u16, u16)], max_gap: u16) -> Self {
+        let mut order: Vec<usize> = (0..regs.len()).collect();
+        order.sort_by_key(|&index| regs[index].0);
+        let mut blocks: Vec<ReadBlock> = Vec::new();
+        for index in order {
+            let (addr, count) = regs[index];
+            let end = u32::from(addr) + u32::from(count);
+            let block = match blocks.last_mut() {
+                Some(block)
+                    if u32::from(addr) <= block.end() + u32::from(max_gap)
+                        && end.max(block.end()) - u32::from(block.start)
+                            <= MAX_WORD_COUNT as u32 =>
+                {
+                    block.count = (end.max(block.end()) - u32::from(block.start)) as u16;
+                    block
+                }
+                _ => {
+                    blocks.push(ReadBlock {
+                        start: addr,
+                        count,
+                        regs: Vec::new(),
+                    });
+                    blocks.last_mut().expect("pushed")
+                }
+            };
+            block.regs.push((index, addr - block.start, count));
+        }
+        Self {
+            blocks,
+            len: regs.len(),
+        }
+    }
+
+    pub fn blocks(&self) -> &[ReadBlock] {
+        &self.blocks
+    }
+
+    /// The number of registers.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Split the words that have been read for each block into the
+    /// words of each register in the order of the plan.
+    ///
+    /// Fails with `InsufficientInput` if a block or its words are
+    /// missing.
+    pub fn split<'a, W: AsRef<[u16]>>(&self, block_words: &'a [W]) -> DecodeResult<Vec<&'a [u16]>> {
+        if block_words.len() < self.blocks.len() {
+            return Err(DecodeError::InsufficientInput);
+        }
+        let mut words = Vec::with_capacity(self.len);
+        words.resize(self.len, &[][..]);
+        for (block, block_words) in self.blocks.iter().zip(block_words) {
+            let block_words = block_words.as_ref();
+            for &(index, offset, count) in &block.regs {
+                let range = usize::from(offset)..usize::from(offset) + usize::from(count);
+                words[index] = block_words
+                    .get(range)
+                    .ok_or(DecodeError::InsufficientInput)?;
+            }
+        }
+        Ok(words)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contiguous_floats() {
+        // Mass flow rate (247) through volume inventory (265)
+        let regs: Vec<_> = (0..10).rev().map(|i| (247 + 2 * i, 2)).collect();
+        let plan = ReadPlan::new(&regs, 0);
+        assert_eq!(1, plan.blocks().len());
+        assert_eq!((247, 20), (plan.blocks()[0].start, plan.blocks()[0].count));
+        assert_eq!(246, plan.blocks()[0].protocol_addr());
+
+        let block_words = [(0..20).collect::<Vec<u16>>()];
+        let words = plan.split(&block_words).unwrap();
+        assert_eq!(10, words.len());
+        assert_eq!(&[18, 19], words[0]);
+        assert_eq!(&[0, 1], words[9]);
+        assert_eq!(Err(DecodeError::InsufficientInput), plan.split(&[[0; 19]]));
+    }
+
+    #[test]
+    fn gaps_and_limits() {
+        let regs = [(251, 2), (247, 2), (247, 2), (1200, 1)];
+        let plan = ReadPlan::new(&regs, 1);
+        let blocks: Vec<_> = plan.blocks().iter().map(|b| (b.start, b.count)).collect();
+        assert_eq!(vec![(247, 2), (251, 2), (1200, 1)], blocks);
+
+        let plan = ReadPlan::new(&regs, 2);
+        let blocks: Vec<_> = plan.blocks().iter().map(|b| (b.start, b.count)).collect();
+        assert_eq!(vec![(247, 6), (1200, 1)], blocks);
+        let block_words = [vec![1, 2, 3, 4, 5, 6], vec![7]];
+        let words = plan.split(&block_words).unwrap();
+        assert_eq!(vec![&[5, 6][..], &[1, 2], &[1, 2], &[7]], words);
+
+        // 70 floats of 140 words need two requests
+        let regs: Vec<_> = (0..70).map(|i| (1000 + 2 * i, 2)).collect();
+        let plan = ReadPlan::new(&regs, 0);
+        let blocks: Vec<_> = plan.blocks().iter().map(|b| (b.start, b.count)).collect();
+        assert_eq!(vec![(1000, 124), (1124, 16)], blocks);
+
+        assert!(ReadPlan::new(&[], 0).is_empty());
+    }
+}
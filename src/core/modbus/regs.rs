@@ -0,0 +1,125 @@
+//! Typed descriptors of all registers in `ModbusMap.csv`, generated at
+//! build time.
+//!
+//! The names are derived from the register descriptions, e.g.
+//! [`MASS_FLOW_RATE`] or [`DENSITY`]. If a name occurs more than once
+//! only the register with the lowest address keeps the plain name, all
+//! others are suffixed with their address, e.g. `DENSITY_3119`.
+
+use super::RegType;
+
+use core::{fmt, marker::PhantomData};
+
+/// Untyped descriptor of a register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RegInfo {
+    /// 1-based register number as listed in the map.
+    pub addr: u16,
+    pub reg_type: RegType,
+    pub description: &'static str,
+}
+
+impl RegInfo {
+    pub const fn word_count(&self) -> u16 {
+        self.reg_type.word_count()
+    }
+}
+
+/// Descriptor of a register whose value decodes into `T`.
+pub struct RegDef<T: ?Sized> {
+    info: RegInfo,
+    value: PhantomData<fn(&T)>,
+}
+
+impl<T: ?Sized> RegDef<T> {
+    pub const fn new(addr: u16, reg_type: RegType, description: &'static str) -> Self {
+        Self {
+            info: RegInfo {
+                addr,
+                reg_type,
+                description,
+            },
+            value: PhantomData,
+        }
+    }
+
+    /// 1-based register number as listed in the map.
+    pub const fn addr(&self) -> u16 {
+        self.info.addr
+    }
+
+    /// 0-based protocol address for Modbus requests.
+    pub const fn protocol_addr(&self) -> u16 {
+        self.info.addr - 1
+    }
+
+    pub const fn reg_type(&self) -> RegType {
+        self.info.reg_type
+    }
+
+    pub const fn word_count(&self) -> u16 {
+        self.info.reg_type.word_count()
+    }
+
+    pub const fn description(&self) -> &'static str {
+        self.info.description
+    }
+
+    pub const fn info(&self) -> RegInfo {
+        self.info
+    }
+}
+
+impl<T: ?Sized> Clone for RegDef<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ?Sized> Copy for RegDef<T> {}
+
+impl<T: ?Sized> PartialEq for RegDef<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.info == other.info
+    }
+}
+
+impl<T: ?Sized> Eq for RegDef<T> {}
+
+impl<T: ?Sized> fmt::Debug for RegDef<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("RegDef").field(&self.info).finish()
+    }
+}
+
+/// Look up the descriptor of a register by its 1-based address.
+pub fn find(addr: u16) -> Option<&'static RegInfo> {
+    ALL.binary_search_by_key(&addr, |info| info.addr)
+        .ok()
+        .map(|idx| &ALL[idx])
+}
+
+include!(concat!(env!("OUT_DIR"), "/regs.rs"));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_constants() {
+        assert_eq!(247, MASS_FLOW_RATE.addr());
+        assert_eq!(246, MASS_FLOW_RATE.protocol_addr());
+        assert_eq!(RegType::F32, MASS_FLOW_RATE.reg_type());
+        assert_eq!(249, DENSITY.addr());
+        assert_eq!(251, TEMPERATURE.addr());
+        let _: RegDef<str> = SENSOR_TYPE;
+        assert_eq!(RegType::Ascii(16), SENSOR_TYPE.reg_type());
+    }
+
+    #[test]
+    fn find_by_addr() {
+        assert_eq!(Some(&DENSITY.info()), find(249));
+        assert_eq!(None, find(0));
+        assert!(ALL.windows(2).all(|w| w[0].addr < w[1].addr));
+    }
+}
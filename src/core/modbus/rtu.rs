@@ -1,7 +1,603 @@
+//! Framing of the Modbus RTU transmission mode and the PDUs of the
+//! requests that the driver sends.
+//!
+//! A frame consists of the slave address, the PDU and the CRC-16. The
+//! PDUs are the same for all transmission modes. Frames are separated
+//! by a silent interval of at least 3.5 characters.
+
+use super::{
+    Bits, DecodeError, DecodeResult, ExceptionCode, ExceptionResponse, Words, MAX_BIT_COUNT,
+    MAX_WORD_COUNT, MAX_WRITE_WORD_COUNT,
+};
+
+use core::{fmt, time::Duration};
+
+#[cfg(feature = "rtu")]
 use serialport::{DataBits, FlowControl, Parity, StopBits};
 
+#[cfg(feature = "rtu")]
 pub const BAUD_RATE: u32 = 38400;
+#[cfg(feature = "rtu")]
 pub const DATA_BITS: DataBits = DataBits::Eight;
+#[cfg(feature = "rtu")]
 pub const STOP_BITS: StopBits = StopBits::One;
+#[cfg(feature = "rtu")]
 pub const PARITY: Parity = Parity::None;
+#[cfg(feature = "rtu")]
 pub const FLOW_CONTROL: FlowControl = FlowControl::None;
+
+/// Character format of the ASCII transmission mode. The parity must
+/// match the setting of the transmitter.
+#[cfg(feature = "rtu")]
+pub const ASCII_DATA_BITS: DataBits = DataBits::Seven;
+#[cfg(feature = "rtu")]
+pub const ASCII_PARITY: Parity = Parity::Even;
+
+pub const READ_COILS: u8 = 0x01;
+pub const READ_DISCRETE_INPUTS: u8 = 0x02;
+pub const READ_HOLDING_REGISTERS: u8 = 0x03;
+pub const READ_INPUT_REGISTERS: u8 = 0x04;
+pub const WRITE_SINGLE_COIL: u8 = 0x05;
+pub const WRITE_SINGLE_REGISTER: u8 = 0x06;
+pub const WRITE_MULTIPLE_REGISTERS: u8 = 0x10;
+
+/// The value that switches a coil on, 0 switches it off.
+pub const COIL_ON: u16 = 0xFF00;
+
+/// The bit of the function code that marks exception responses.
+pub const EXCEPTION_FLAG: u8 = 0x80;
+
+/// Maximum length of a PDU.
+pub const MAX_PDU_LEN: usize = 253;
+
+/// Maximum length of a frame with slave address, PDU and CRC.
+pub const MAX_FRAME_LEN: usize = 1 + MAX_PDU_LEN + 2;
+
+/// Length of the start of a response frame that determines the length
+/// of the whole frame: the slave address, the function code and either
+/// the byte count of reads or the exception code.
+pub const RESPONSE_HEADER_LEN: usize = 3;
+
+/// A character is transmitted as 11 bits: start bit, 8 data bits,
+/// parity or a second stop bit, and stop bit.
+const BITS_PER_CHAR: u64 = 11;
+
+/// Above this baud rate the timing is fixed.
+const MAX_TIMED_BAUD_RATE: u32 = 19200;
+
+/// CRC-16 of an RTU frame, transmitted with the low byte first.
+pub fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0xFFFF, |crc, b| {
+        (0..8).fold(crc ^ u16::from(*b), |crc, _| {
+            if crc & 1 == 0 {
+                crc >> 1
+            } else {
+                (crc >> 1) ^ 0xA001
+            }
+        })
+    })
+}
+
+fn char_times(baud_rate: u32, half_chars: u64) -> Duration {
+    let baud_rate = u64::from(baud_rate.max(1));
+    Duration::from_micros(half_chars * BITS_PER_CHAR * 1_000_000 / (2 * baud_rate))
+}
+
+/// The minimum silent interval of 3.5 characters between frames,
+/// fixed to 1.75 ms above 19200 baud.
+pub fn inter_frame_delay(baud_rate: u32) -> Duration {
+    if baud_rate > MAX_TIMED_BAUD_RATE {
+        Duration::from_micros(1750)
+    } else {
+        char_times(baud_rate, 7)
+    }
+}
+
+/// The maximum silent interval of 1.5 characters within a frame,
+/// fixed to 0.75 ms above 19200 baud.
+pub fn inter_char_timeout(baud_rate: u32) -> Duration {
+    if baud_rate > MAX_TIMED_BAUD_RATE {
+        Duration::from_micros(750)
+    } else {
+        char_times(baud_rate, 3)
+    }
+}
+
+/// A request to a slave at the 0-based protocol address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Request<'a> {
+    ReadCoils { addr: u16, count: u16 },
+    ReadDiscreteInputs { addr: u16, count: u16 },
+    ReadHoldingRegisters { addr: u16, count: u16 },
+    ReadInputRegisters { addr: u16, count: u16 },
+    WriteSingleCoil { addr: u16, state: bool },
+    WriteSingleRegister { addr: u16, word: u16 },
+    WriteMultipleRegisters { addr: u16, words: &'a [u16] },
+}
+
+impl<'a> Request<'a> {
+    /// Write a single word with function 6 and multiple words with
+    /// function 16.
+    pub fn write_registers(addr: u16, words: &'a [u16]) -> Self {
+        match words {
+            [word] => Request::WriteSingleRegister { addr, word: *word },
+            _ => Request::WriteMultipleRegisters { addr, words },
+        }
+    }
+
+    pub fn function(&self) -> u8 {
+        match self {
+            Request::ReadCoils { .. } => READ_COILS,
+            Request::ReadDiscreteInputs { .. } => READ_DISCRETE_INPUTS,
+            Request::ReadHoldingRegisters { .. } => READ_HOLDING_REGISTERS,
+            Request::ReadInputRegisters { .. } => READ_INPUT_REGISTERS,
+            Request::WriteSingleCoil { .. } => WRITE_SINGLE_COIL,
+            Request::WriteSingleRegister { .. } => WRITE_SINGLE_REGISTER,
+            Request::WriteMultipleRegisters { .. } => WRITE_MULTIPLE_REGISTERS,
+        }
+    }
+
+    /// The 0-based protocol address of the first register or coil.
+    pub fn addr(&self) -> u16 {
+        match *self {
+            Request::ReadCoils { addr, .. }
+            | Request::ReadDiscreteInputs { addr, .. }
+            | Request::ReadHoldingRegisters { addr, .. }
+            | Request::ReadInputRegisters { addr, .. }
+            | Request::WriteSingleCoil { addr, .. }
+            | Request::WriteSingleRegister { addr, .. }
+            | Request::WriteMultipleRegisters { addr, .. } => addr,
+        }
+    }
+
+    /// The number of registers or coils that are read or written.
+    pub fn count(&self) -> u16 {
+        match *self {
+            Request::ReadCoils { count, .. }
+            | Request::ReadDiscreteInputs { count, .. }
+            | Request::ReadHoldingRegisters { count, .. }
+            | Request::ReadInputRegisters { count, .. } => count,
+            Request::WriteSingleCoil { .. } | Request::WriteSingleRegister { .. } => 1,
+            Request::WriteMultipleRegisters { words, .. } => words.len() as u16,
+        }
+    }
+
+    /// Write the PDU into `output` and return its length.
+    ///
+    /// Fails with `InvalidInput` if no or more than `MAX_WORD_COUNT`
+    /// words, `MAX_WRITE_WORD_COUNT` words for writes or
+    /// `MAX_BIT_COUNT` bits are requested and with
+    /// `InsufficientInput` if `output` is too short.
+    pub fn encode_pdu(&self, output: &mut [u8]) -> DecodeResult<usize> {
+        let (addr, value, words): (u16, u16, &[u16]) = match *self {
+            Request::ReadCoils { addr, count } | Request::ReadDiscreteInputs { addr, count } => {
+                if count == 0 || usize::from(count) > MAX_BIT_COUNT {
+                    return Err(DecodeError::InvalidInput);
+                }
+                (addr, count, &[])
+            }
+            Request::ReadHoldingRegisters { addr, count }
+            | Request::ReadInputRegisters { addr, count } => {
+                if count == 0 || usize::from(count) > MAX_WORD_COUNT {
+                    return Err(DecodeError::InvalidInput);
+                }
+                (addr, count, &[])
+            }
+            Request::WriteSingleCoil { addr, state } => {
+                (addr, if state { COIL_ON } else { 0 }, &[])
+            }
+            Request::WriteSingleRegister { addr, word } => (addr, word, &[]),
+            Request::WriteMultipleRegisters { addr, words } => {
+                if words.is_empty() || words.len() > MAX_WRITE_WORD_COUNT {
+                    return Err(DecodeError::InvalidInput);
+                }
+                (addr, words.len() as u16, words)
+            }
+        };
+        let len = if words.is_empty() {
+            5
+        } else {
+            6 + 2 * words.len()
+        };
+        if output.len() < len {
+            return Err(DecodeError::InsufficientInput);
+        }
+        output[0] = self.function();
+        output[1..3].copy_from_slice(&addr.to_be_bytes());
+        output[3..5].copy_from_slice(&value.to_be_bytes());
+        if !words.is_empty() {
+            output[5] = 2 * words.len() as u8;
+            for (bytes, word) in output[6..len].chunks_mut(2).zip(words) {
+                bytes.copy_from_slice(&word.to_be_bytes());
+            }
+        }
+        Ok(len)
+    }
+
+    /// Write the frame of the request to `slave` into `output` and
+    /// return its length.
+    pub fn encode_frame(&self, slave: u8, output: &mut [u8]) -> DecodeResult<usize> {
+        if output.is_empty() {
+            return Err(DecodeError::InsufficientInput);
+        }
+        output[0] = slave;
+        let len = 1 + self.encode_pdu(&mut output[1..])?;
+        if output.len() < len + 2 {
+            return Err(DecodeError::InsufficientInput);
+        }
+        let crc = crc16(&output[..len]);
+        output[len..len + 2].copy_from_slice(&crc.to_le_bytes());
+        Ok(len + 2)
+    }
+}
+
+/// The length of a response frame to a request with `function` from
+/// the first `RESPONSE_HEADER_LEN` bytes of the frame, i.e. how many
+/// bytes to receive in total.
+///
+/// Fails with `InvalidData` if the function code of the response does
+/// not match.
+pub fn response_frame_len(function: u8, header: &[u8]) -> DecodeResult<usize> {
+    if header.len() < RESPONSE_HEADER_LEN {
+        return Err(DecodeError::InsufficientInput);
+    }
+    let data_len = match header[1] {
+        code if code == function | EXCEPTION_FLAG => 0,
+        0x01..=0x04 if header[1] == function => usize::from(header[2]),
+        0x05 | 0x06 | 0x0F | 0x10 if header[1] == function => 3,
+        _ => return Err(DecodeError::InvalidData),
+    };
+    Ok(RESPONSE_HEADER_LEN + data_len + 2)
+}
+
+/// Verify the CRC of a complete frame and return the slave address and
+/// the PDU.
+pub fn decode_frame(frame: &[u8]) -> DecodeResult<(u8, &[u8])> {
+    if frame.len() < 4 {
+        return Err(DecodeError::InsufficientInput);
+    }
+    let (adu, crc) = frame.split_at(frame.len() - 2);
+    if crc16(adu).to_le_bytes() != crc {
+        return Err(DecodeError::InvalidData);
+    }
+    Ok((adu[0], &adu[1..]))
+}
+
+/// The response to a `Request`.
+// Boxing the words would need an allocator
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Response {
+    ReadCoils(Bits),
+    ReadDiscreteInputs(Bits),
+    ReadHoldingRegisters(Words),
+    ReadInputRegisters(Words),
+    WriteSingleCoil { addr: u16, state: bool },
+    WriteSingleRegister { addr: u16, word: u16 },
+    WriteMultipleRegisters { addr: u16, count: u16 },
+}
+
+impl Response {
+    /// The words of a register read.
+    pub fn into_words(self) -> DecodeResult<Words> {
+        match self {
+            Response::ReadHoldingRegisters(words) | Response::ReadInputRegisters(words) => {
+                Ok(words)
+            }
+            _ => Err(DecodeError::InvalidData),
+        }
+    }
+
+    /// The bits of a coil or discrete input read.
+    pub fn into_bits(self) -> DecodeResult<Bits> {
+        match self {
+            Response::ReadCoils(bits) | Response::ReadDiscreteInputs(bits) => Ok(bits),
+            _ => Err(DecodeError::InvalidData),
+        }
+    }
+}
+
+/// A response that is either malformed or an exception.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResponseError {
+    Decode(DecodeError),
+    Exception(ExceptionResponse),
+}
+
+impl From<DecodeError> for ResponseError {
+    fn from(from: DecodeError) -> Self {
+        ResponseError::Decode(from)
+    }
+}
+
+impl fmt::Display for ResponseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResponseError::Decode(err) => err.fmt(f),
+            ResponseError::Exception(err) => err.fmt(f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ResponseError {}
+
+#[cfg(feature = "std")]
+impl From<ResponseError> for std::io::Error {
+    fn from(from: ResponseError) -> Self {
+        match from {
+            ResponseError::Decode(err) => err.into(),
+            ResponseError::Exception(err) => Self::other(err),
+        }
+    }
+}
+
+/// The data of a read response, i.e. the bytes after the byte count,
+/// if the byte count matches.
+fn read_data(pdu: &[u8], byte_count: usize) -> DecodeResult<&[u8]> {
+    match pdu[1..] {
+        [len, ref data @ ..] if usize::from(len) == data.len() && data.len() == byte_count => {
+            Ok(data)
+        }
+        _ => Err(DecodeError::InvalidData),
+    }
+}
+
+fn decode_words(data: &[u8]) -> Words {
+    data.chunks(2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .collect()
+}
+
+/// All write functions echo the address and the value or count.
+fn echo(addr: u16, value: u16) -> [u8; 4] {
+    let [a0, a1] = addr.to_be_bytes();
+    let [v0, v1] = value.to_be_bytes();
+    [a0, a1, v0, v1]
+}
+
+/// Decode the PDU of the response to `request`.
+///
+/// Fails with `InvalidData` if the response does not match the
+/// request, i.e. the word count of reads or the echo of writes differs.
+pub fn decode_response(request: &Request<'_>, pdu: &[u8]) -> Result<Response, ResponseError> {
+    let function = request.function();
+    match *pdu {
+        [code, exception] if code == function | EXCEPTION_FLAG => {
+            return Err(ResponseError::Exception(ExceptionResponse {
+                function,
+                code: ExceptionCode::from_code(exception),
+            }))
+        }
+        [code, ..] if code == function => {}
+        _ => return Err(DecodeError::InvalidData.into()),
+    }
+    let response = match *request {
+        Request::ReadCoils { count, .. } => {
+            let data = read_data(pdu, usize::from(count).div_ceil(8))?;
+            Response::ReadCoils(Bits::from_bytes(data, count)?)
+        }
+        Request::ReadDiscreteInputs { count, .. } => {
+            let data = read_data(pdu, usize::from(count).div_ceil(8))?;
+            Response::ReadDiscreteInputs(Bits::from_bytes(data, count)?)
+        }
+        Request::ReadHoldingRegisters { count, .. } => {
+            let data = read_data(pdu, 2 * usize::from(count))?;
+            Response::ReadHoldingRegisters(decode_words(data))
+        }
+        Request::ReadInputRegisters { count, .. } => {
+            let data = read_data(pdu, 2 * usize::from(count))?;
+            Response::ReadInputRegisters(decode_words(data))
+        }
+        Request::WriteSingleCoil { addr, state } => {
+            if pdu[1..] != echo(addr, if state { COIL_ON } else { 0 }) {
+                return Err(DecodeError::InvalidData.into());
+            }
+            Response::WriteSingleCoil { addr, state }
+        }
+        Request::WriteSingleRegister { addr, word } => {
+            if pdu[1..] != echo(addr, word) {
+                return Err(DecodeError::InvalidData.into());
+            }
+            Response::WriteSingleRegister { addr, word }
+        }
+        Request::WriteMultipleRegisters { addr, words } => {
+            let count = words.len() as u16;
+            if pdu[1..] != echo(addr, count) {
+                return Err(DecodeError::InvalidData.into());
+            }
+            Response::WriteMultipleRegisters { addr, count }
+        }
+    };
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc() {
+        assert_eq!(0xCDC5, crc16(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x0A]));
+    }
+
+    #[test]
+    fn encode_requests() {
+        let mut output = [0; MAX_FRAME_LEN];
+        let request = Request::ReadHoldingRegisters { addr: 0, count: 10 };
+        let len = request.encode_frame(1, &mut output).unwrap();
+        assert_eq!(
+            [0x01, 0x03, 0x00, 0x00, 0x00, 0x0A, 0xC5, 0xCD],
+            output[..len]
+        );
+
+        let request = Request::write_registers(154, &[1]);
+        assert_eq!(WRITE_SINGLE_REGISTER, request.function());
+        let len = request.encode_pdu(&mut output).unwrap();
+        assert_eq!([0x06, 0x00, 0x9A, 0x00, 0x01], output[..len]);
+
+        let request = Request::write_registers(0x0102, &[0x0304, 0x0506]);
+        let len = request.encode_pdu(&mut output).unwrap();
+        assert_eq!(
+            [0x10, 0x01, 0x02, 0x00, 0x02, 0x04, 0x03, 0x04, 0x05, 0x06],
+            output[..len]
+        );
+        assert_eq!(
+            Err(DecodeError::InsufficientInput),
+            request.encode_frame(1, &mut output[..11])
+        );
+
+        let words = [0; MAX_WRITE_WORD_COUNT];
+        let len = Request::write_registers(0, &words)
+            .encode_frame(1, &mut output)
+            .unwrap();
+        assert_eq!([0x00, 0x7B, 0xF6], output[4..7]);
+        assert_eq!(MAX_FRAME_LEN - 1, len);
+
+        let request = Request::WriteSingleCoil {
+            addr: 14,
+            state: true,
+        };
+        let len = request.encode_pdu(&mut output).unwrap();
+        assert_eq!([0x05, 0x00, 0x0E, 0xFF, 0x00], output[..len]);
+
+        let request = Request::ReadDiscreteInputs {
+            addr: 0,
+            count: MAX_BIT_COUNT as u16,
+        };
+        let len = request.encode_pdu(&mut output).unwrap();
+        assert_eq!([0x02, 0x00, 0x00, 0x07, 0xD0], output[..len]);
+
+        for request in &[
+            Request::ReadHoldingRegisters { addr: 0, count: 0 },
+            Request::ReadInputRegisters {
+                addr: 0,
+                count: MAX_WORD_COUNT as u16 + 1,
+            },
+            Request::ReadCoils {
+                addr: 0,
+                count: MAX_BIT_COUNT as u16 + 1,
+            },
+            Request::ReadHoldingRegisters {
+                addr: 0,
+                count: MAX_WORD_COUNT as u16 + 1,
+            },
+            Request::write_registers(0, &[]),
+            Request::write_registers(0, &[0; MAX_WRITE_WORD_COUNT + 1]),
+        ] {
+            assert_eq!(
+                Err(DecodeError::InvalidInput),
+                request.encode_pdu(&mut output)
+            );
+        }
+    }
+
+    #[test]
+    fn decode_responses() {
+        let request = Request::ReadHoldingRegisters {
+            addr: 245,
+            count: 2,
+        };
+        let frame = [0x6F, 0x03, 0x04, 0x00, 0x00, 0x42, 0xF6, 0x00, 0x00];
+        assert_eq!(Ok(frame.len()), response_frame_len(0x03, &frame[..3]));
+        let crc = crc16(&frame[..7]).to_le_bytes();
+        let frame = [&frame[..7], &crc[..]].concat();
+        let (slave, pdu) = decode_frame(&frame).unwrap();
+        assert_eq!(0x6F, slave);
+        assert_eq!(
+            Ok(Response::ReadHoldingRegisters(
+                Words::from_slice(&[0x0000, 0x42F6]).unwrap()
+            )),
+            decode_response(&request, pdu)
+        );
+        assert_eq!(
+            Err(ResponseError::Decode(DecodeError::InvalidData)),
+            decode_response(&request, &[0x03, 0x02, 0x00, 0x00])
+        );
+
+        let mut corrupted = frame.clone();
+        corrupted[3] ^= 1;
+        assert_eq!(Err(DecodeError::InvalidData), decode_frame(&corrupted));
+
+        assert_eq!(Ok(5), response_frame_len(0x03, &[0x6F, 0x83, 0x02]));
+        assert_eq!(
+            Err(ResponseError::Exception(ExceptionResponse {
+                function: 0x03,
+                code: ExceptionCode::IllegalDataAddress
+            })),
+            decode_response(&request, &[0x83, 0x02])
+        );
+        assert_eq!(
+            Err(DecodeError::InvalidData),
+            response_frame_len(0x03, &[0x6F, 0x06, 0x00])
+        );
+
+        let request = Request::write_registers(154, &[1]);
+        assert_eq!(Ok(8), response_frame_len(0x06, &[0x6F, 0x06, 0x00]));
+        assert_eq!(
+            Ok(Response::WriteSingleRegister { addr: 154, word: 1 }),
+            decode_response(&request, &[0x06, 0x00, 0x9A, 0x00, 0x01])
+        );
+        assert_eq!(
+            Err(ResponseError::Decode(DecodeError::InvalidData)),
+            decode_response(&request, &[0x06, 0x00, 0x9A, 0x00, 0x02])
+        );
+    }
+
+    #[test]
+    fn decode_bit_responses() {
+        let request = Request::ReadCoils {
+            addr: 14,
+            count: 10,
+        };
+        assert_eq!(Ok(7), response_frame_len(0x01, &[0x6F, 0x01, 0x02]));
+        let bits = Bits::from_bytes(&[0x03, 0x02], 10).unwrap();
+        assert_eq!(
+            Ok(Response::ReadCoils(bits)),
+            decode_response(&request, &[0x01, 0x02, 0x03, 0x02])
+        );
+        assert_eq!(
+            Err(ResponseError::Decode(DecodeError::InvalidData)),
+            decode_response(&request, &[0x01, 0x01, 0x03])
+        );
+
+        let request = Request::ReadDiscreteInputs { addr: 0, count: 1 };
+        match decode_response(&request, &[0x02, 0x01, 0x01]) {
+            Ok(Response::ReadDiscreteInputs(bits)) => assert_eq!(Some(true), bits.get(0)),
+            res => panic!("{:?}", res),
+        }
+
+        let request = Request::ReadInputRegisters { addr: 0, count: 1 };
+        assert_eq!(
+            Ok(Response::ReadInputRegisters(
+                Words::from_slice(&[0x1234]).unwrap()
+            )),
+            decode_response(&request, &[0x04, 0x02, 0x12, 0x34])
+        );
+
+        let request = Request::WriteSingleCoil {
+            addr: 14,
+            state: false,
+        };
+        assert_eq!(Ok(8), response_frame_len(0x05, &[0x6F, 0x05, 0x00]));
+        assert_eq!(
+            Ok(Response::WriteSingleCoil {
+                addr: 14,
+                state: false
+            }),
+            decode_response(&request, &[0x05, 0x00, 0x0E, 0x00, 0x00])
+        );
+        assert_eq!(
+            Err(ResponseError::Decode(DecodeError::InvalidData)),
+            decode_response(&request, &[0x05, 0x00, 0x0E, 0xFF, 0x00])
+        );
+    }
+
+    #[test]
+    fn timing() {
+        assert_eq!(Duration::from_micros(4010), inter_frame_delay(9600));
+        assert_eq!(Duration::from_micros(1718), inter_char_timeout(9600));
+        assert_eq!(Duration::from_micros(1750), inter_frame_delay(38400));
+        assert_eq!(Duration::from_micros(750), inter_char_timeout(38400));
+    }
+}
@@ -0,0 +1,170 @@
+//! Scaled integers of legacy transmitters, e.g. the RFT9739.
+//!
+//! The process variables in registers 2–11 are transmitted as
+//! unsigned integers that are converted with an offset and a scale
+//! factor per variable:
+//!
+//! `scaled integer = value × scale factor + offset`
+//!
+//! The maximum integer and all offsets and scale factors are stored
+//! in the consecutive registers 18–38, the _scaling block_.
+
+use super::{regs, DecodeError, DecodeResult};
+
+/// Protocol address of the scaling block starting at register 18.
+pub const SCALING_REG_START: u16 = regs::MAXIMUM_INTEGER.protocol_addr();
+
+/// Number of words of the scaling block, i.e. registers 18–38.
+pub const SCALING_REG_COUNT: u16 =
+    regs::VOLUME_INVENTORY_SCALE_FACTOR.addr() - regs::MAXIMUM_INTEGER.addr() + 1;
+
+/// A process variable that is available as a scaled integer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ScaledVar {
+    MassFlow,
+    Density,
+    Temperature,
+    VolumeFlow,
+    Viscosity,
+    Pressure,
+    MassTotal,
+    VolumeTotal,
+    MassInventory,
+    VolumeInventory,
+}
+
+impl ScaledVar {
+    pub const ALL: [ScaledVar; 10] = [
+        ScaledVar::MassFlow,
+        ScaledVar::Density,
+        ScaledVar::Temperature,
+        ScaledVar::VolumeFlow,
+        ScaledVar::Viscosity,
+        ScaledVar::Pressure,
+        ScaledVar::MassTotal,
+        ScaledVar::VolumeTotal,
+        ScaledVar::MassInventory,
+        ScaledVar::VolumeInventory,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    /// The 1-based register number of the scaled integer.
+    pub const fn addr(self) -> u16 {
+        regs::MASS_FLOW_RATE_SCALED_INT.addr() + self as u16
+    }
+
+    pub const fn protocol_addr(self) -> u16 {
+        self.addr() - 1
+    }
+
+    /// The variable of a scaled integer register (2–11).
+    pub fn from_addr(addr: u16) -> Option<Self> {
+        ScaledVar::ALL
+            .iter()
+            .copied()
+            .find(|var| var.addr() == addr)
+    }
+}
+
+/// Maximum integer, offsets and scale factors of all scaled integers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Scaling {
+    /// Larger integers indicate a value that is out of range.
+    pub max_integer: u16,
+    /// Offsets are signed integers.
+    pub offsets: [i16; 10],
+    pub scale_factors: [u16; 10],
+}
+
+impl Scaling {
+    /// Decode the words of the scaling block that have been read
+    /// from `SCALING_REG_START`.
+    pub fn decode(words: &[u16]) -> DecodeResult<Self> {
+        if words.len() < usize::from(SCALING_REG_COUNT) {
+            return Err(DecodeError::InsufficientInput);
+        }
+        let mut offsets = [0; 10];
+        let mut scale_factors = [0; 10];
+        for i in 0..10 {
+            offsets[i] = words[1 + i] as i16;
+            scale_factors[i] = words[11 + i];
+        }
+        Ok(Self {
+            max_integer: words[0],
+            offsets,
+            scale_factors,
+        })
+    }
+
+    /// Convert a scaled integer into the value of the process variable.
+    pub fn scale(&self, var: ScaledVar, raw: u16) -> DecodeResult<f32> {
+        if raw > self.max_integer {
+            return Err(DecodeError::InvalidData);
+        }
+        let scale_factor = self.scale_factors[var.index()];
+        if scale_factor == 0 {
+            return Err(DecodeError::InvalidData);
+        }
+        let offset = self.offsets[var.index()];
+        Ok(((f64::from(raw) - f64::from(offset)) / f64::from(scale_factor)) as f32)
+    }
+
+    /// Convert the words of a scaled integer register.
+    pub fn decode_value(&self, var: ScaledVar, words: &[u16]) -> DecodeResult<f32> {
+        let raw = words.first().ok_or(DecodeError::InsufficientInput)?;
+        self.scale(var, *raw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scaling() -> Scaling {
+        let mut words = [0u16; SCALING_REG_COUNT as usize];
+        words[0] = 65534;
+        // Density: offset 0, scale factor 10000 (g/cm³)
+        words[1 + ScaledVar::Density.index()] = 0;
+        words[11 + ScaledVar::Density.index()] = 10000;
+        // Temperature: offset 1000, scale factor 10 (°C)
+        words[1 + ScaledVar::Temperature.index()] = 1000;
+        words[11 + ScaledVar::Temperature.index()] = 10;
+        // Mass flow: offset -1000
+        words[1 + ScaledVar::MassFlow.index()] = (-1000i16) as u16;
+        words[11 + ScaledVar::MassFlow.index()] = 100;
+        Scaling::decode(&words).unwrap()
+    }
+
+    #[test]
+    fn scaling_block() {
+        assert_eq!(17, SCALING_REG_START);
+        assert_eq!(21, SCALING_REG_COUNT);
+        assert_eq!(2, ScaledVar::MassFlow.addr());
+        assert_eq!(11, ScaledVar::VolumeInventory.addr());
+        assert_eq!(Some(ScaledVar::Temperature), ScaledVar::from_addr(4));
+        assert_eq!(None, ScaledVar::from_addr(12));
+        assert_eq!(
+            Err(DecodeError::InsufficientInput),
+            Scaling::decode(&[0; 20])
+        );
+    }
+
+    #[test]
+    fn scale_values() {
+        let scaling = scaling();
+        assert_eq!(Ok(0.9982), scaling.scale(ScaledVar::Density, 9982));
+        assert_eq!(Ok(-20.5), scaling.scale(ScaledVar::Temperature, 795));
+        assert_eq!(Ok(12.5), scaling.decode_value(ScaledVar::MassFlow, &[250]));
+        assert_eq!(
+            Err(DecodeError::InvalidData),
+            scaling.scale(ScaledVar::Density, 65535)
+        );
+        assert_eq!(
+            Err(DecodeError::InvalidData),
+            scaling.scale(ScaledVar::Pressure, 1)
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! Unit code registers of the process variables.
+
+use super::{regs, scaled::ScaledVar};
+
+/// The unit code register that applies to the float or scaled integer
+/// register of a process variable, e.g. 39 for mass flow rate (247).
+pub fn unit_reg(addr: u16) -> Option<u16> {
+    let var = match ScaledVar::from_addr(addr) {
+        Some(var) => var,
+        None => FLOAT_VARS
+            .iter()
+            .find(|(float_addr, _)| *float_addr == addr)
+            .map(|(_, var)| *var)?,
+    };
+    let unit_reg = match var {
+        ScaledVar::MassFlow => regs::STANDARD_OR_SPECIAL_MASS_FLOW_RATE_UNIT,
+        ScaledVar::Density => regs::DENSITY_UNIT,
+        ScaledVar::Temperature => regs::TEMPERATURE_UNIT,
+        ScaledVar::VolumeFlow => regs::STANDARD_OR_SPECIAL_VOLUME_FLOW_RATE_UNIT,
+        ScaledVar::Viscosity => regs::VISCOSITY_UNIT,
+        ScaledVar::Pressure => regs::PRESSURE_UNIT,
+        ScaledVar::MassTotal | ScaledVar::MassInventory => {
+            regs::STANDARD_OR_SPECIAL_MASS_TOTAL_INV_UNIT_DERIVED
+        }
+        ScaledVar::VolumeTotal | ScaledVar::VolumeInventory => {
+            regs::STANDARD_OR_SPECIAL_VOLUME_TOTAL_INV_UNIT_DERIVED
+        }
+    };
+    Some(unit_reg.addr())
+}
+
+/// Float registers of the process variables that are also available
+/// as scaled integers.
+const FLOAT_VARS: [(u16, ScaledVar); 10] = [
+    (regs::MASS_FLOW_RATE.addr(), ScaledVar::MassFlow),
+    (regs::DENSITY.addr(), ScaledVar::Density),
+    (regs::TEMPERATURE.addr(), ScaledVar::Temperature),
+    (regs::VOLUME_FLOW_RATE.addr(), ScaledVar::VolumeFlow),
+    // Only defined for fork meters and thus missing in `regs`
+    (255, ScaledVar::Viscosity),
+    (
+        regs::INTERNALLY_DERIVED_PRESSURE.addr(),
+        ScaledVar::Pressure,
+    ),
+    (regs::MASS_TOTAL.addr(), ScaledVar::MassTotal),
+    (regs::VOLUME_TOTAL.addr(), ScaledVar::VolumeTotal),
+    (regs::MASS_INVENTORY.addr(), ScaledVar::MassInventory),
+    (regs::VOLUME_INVENTORY.addr(), ScaledVar::VolumeInventory),
+];
+
+/// The register with the label of a special unit (code 253) for a
+/// unit code register, e.g. 52 for the mass flow unit (39).
+pub fn special_unit_reg(unit_reg: u16) -> Option<regs::RegDef<str>> {
+    [
+        (
+            regs::STANDARD_OR_SPECIAL_MASS_FLOW_RATE_UNIT,
+            regs::SPECIAL_MASS_FLOW_UNIT_STRING,
+        ),
+        (
+            regs::STANDARD_OR_SPECIAL_VOLUME_FLOW_RATE_UNIT,
+            regs::SPECIAL_VOLUME_FLOW_UNIT_STRING,
+        ),
+        (
+            regs::STANDARD_OR_SPECIAL_MASS_TOTAL_INV_UNIT_DERIVED,
+            regs::SPECIAL_MASS_TOTAL_OR_INVENTORY_UNIT_STRING,
+        ),
+        (
+            regs::STANDARD_OR_SPECIAL_VOLUME_TOTAL_INV_UNIT_DERIVED,
+            regs::SPECIAL_VOLUME_TOTAL_OR_INVENTORY_UNIT_STRING,
+        ),
+    ]
+    .iter()
+    .find(|(code_reg, _)| code_reg.addr() == unit_reg)
+    .map(|(_, label_reg)| *label_reg)
+}
+
+/// The unit code register of the configurable total #1–7 (2935–2941).
+pub fn config_total_unit_reg(total: u8) -> Option<u16> {
+    match total {
+        1..=7 => Some(regs::CONFIG_TOTAL_1_UNITS_CODE_REGISTER.addr() + u16::from(total) - 1),
+        _ => None,
+    }
+}
+
+/// The unit code register of the configurable inventory #1–7 (2942–2948).
+pub fn config_inventory_unit_reg(inventory: u8) -> Option<u16> {
+    match inventory {
+        1..=7 => Some(regs::CONFIG_INV_1_UNITS_CODE_REGISTER.addr() + u16::from(inventory) - 1),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unit_registers() {
+        assert_eq!(Some(39), unit_reg(247));
+        assert_eq!(Some(39), unit_reg(2));
+        assert_eq!(Some(41), unit_reg(251));
+        assert_eq!(Some(45), unit_reg(263));
+        assert_eq!(Some(46), unit_reg(11));
+        assert_eq!(None, unit_reg(285));
+        assert_eq!(Some(52), special_unit_reg(39).map(|reg| reg.addr()));
+        assert_eq!(None, special_unit_reg(41));
+        assert_eq!(Some(2935), config_total_unit_reg(1));
+        assert_eq!(Some(2948), config_inventory_unit_reg(7));
+        assert_eq!(None, config_total_unit_reg(8));
+    }
+}
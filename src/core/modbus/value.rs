@@ -0,0 +1,332 @@
+//! Decoded values of all register types.
+
+use super::{
+    decode_ascii, decode_f32, decode_f64, decode_u16, decode_u24, decode_u32, decode_u64,
+    decode_u8, encode_ascii, encode_f32, encode_f64, encode_uint, ByteOrder, DecodeError,
+    DecodeResult, RegType, Text, TextEncoding, Words,
+};
+use core::{convert::TryFrom, fmt};
+
+/// The value of a register.
+///
+/// Text is stored inline to avoid allocations.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    U8(u8),
+    U16(u16),
+    /// The low-order 3 bytes of a U24 register.
+    U24(u32),
+    U32(u32),
+    U64(u64),
+    I16(i16),
+    I32(i32),
+    F32(f32),
+    F64(f64),
+    Text(Text),
+    /// The raw bits of a status or alarm register.
+    Bits(u64),
+}
+
+impl Value {
+    /// The value of an integer, boolean or bitfield register.
+    pub fn as_u64(&self) -> Option<u64> {
+        use Value::*;
+        match *self {
+            Bool(value) => Some(u64::from(value)),
+            U8(value) => Some(u64::from(value)),
+            U16(value) => Some(u64::from(value)),
+            U24(value) | U32(value) => Some(u64::from(value)),
+            U64(value) | Bits(value) => Some(value),
+            I16(value) if value >= 0 => Some(value as u64),
+            I32(value) if value >= 0 => Some(value as u64),
+            I16(_) | I32(_) | F32(_) | F64(_) | Text(_) => None,
+        }
+    }
+
+    /// The value of any numeric register.
+    pub fn as_f64(&self) -> Option<f64> {
+        use Value::*;
+        match *self {
+            I16(value) => Some(f64::from(value)),
+            I32(value) => Some(f64::from(value)),
+            F32(value) => Some(f64::from(value)),
+            F64(value) => Some(value),
+            Text(_) => None,
+            _ => self.as_u64().map(|value| value as f64),
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Parse the text representation of a value for a register of
+    /// the given type, e.g. for writing it. Negative integers are
+    /// parsed as `I32`.
+    pub fn parse(reg_type: RegType, s: &str) -> DecodeResult<Self> {
+        use RegType::*;
+        if let Ascii(_) = reg_type {
+            return Text::try_from(s)
+                .map(Value::Text)
+                .map_err(|()| DecodeError::InvalidInput);
+        }
+        let s = s.trim();
+        let value = match reg_type {
+            F32 => s.parse().map(Value::F32).ok(),
+            F64 => s.parse().map(Value::F64).ok(),
+            _ if s.starts_with('-') => s.parse().map(Value::I32).ok(),
+            U8 => s.parse().map(Value::U8).ok(),
+            U16 => s.parse().map(Value::U16).ok(),
+            U24 => s.parse().map(Value::U24).ok(),
+            U32 => s.parse().map(Value::U32).ok(),
+            U64 => s.parse().map(Value::U64).ok(),
+            Ascii(_) => None,
+        };
+        value.ok_or(DecodeError::InvalidInput)
+    }
+
+    /// Reinterpret a U16 or U32 register as a two's complement value.
+    pub fn to_signed(self) -> Self {
+        match self {
+            Value::U16(value) => Value::I16(value as i16),
+            Value::U32(value) => Value::I32(value as i32),
+            value => value,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Value::*;
+        match self {
+            Bool(value) => write!(f, "{}", value),
+            U8(value) => write!(f, "{}", value),
+            U16(value) => write!(f, "{}", value),
+            U24(value) | U32(value) => write!(f, "{}", value),
+            U64(value) => write!(f, "{}", value),
+            I16(value) => write!(f, "{}", value),
+            I32(value) => write!(f, "{}", value),
+            F32(value) => write!(f, "{}", value),
+            F64(value) => write!(f, "{}", value),
+            Text(text) => write!(f, "{}", text),
+            Bits(value) => write!(f, "{:#b}", value),
+        }
+    }
+}
+
+/// Decode the words of a register according to its type.
+///
+/// The number of words must match the type exactly. `byte_order` only
+/// applies to F32 and F64 registers, i.e. pass the order of register
+/// 1344 for F64 registers. Text is decoded as Latin-1 that never fails,
+/// see `decode_ascii()` for other encodings.
+pub fn decode(reg_type: RegType, words: &[u16], byte_order: ByteOrder) -> DecodeResult<Value> {
+    let word_count = usize::from(reg_type.word_count());
+    if words.len() < word_count {
+        return Err(DecodeError::InsufficientInput);
+    }
+    if words.len() > word_count {
+        return Err(DecodeError::InvalidInput);
+    }
+    use RegType::*;
+    let value = match reg_type {
+        U8 => Value::U8(decode_u8(words)?),
+        U16 => Value::U16(decode_u16(words)?),
+        U24 => Value::U24(decode_u24(words)?),
+        U32 => Value::U32(decode_u32(words)?),
+        U64 => Value::U64(decode_u64(words)?),
+        F32 => Value::F32(decode_f32(words, byte_order)?),
+        F64 => Value::F64(decode_f64(words, byte_order)?),
+        Ascii(len) => Value::Text(decode_ascii(words, len, TextEncoding::Latin1)?),
+    };
+    Ok(value)
+}
+
+/// Encode a value into the words of a register of the given type.
+///
+/// Integers must fit into the type and negative integers are encoded
+/// as two's complement. Float registers accept any numeric value, but
+/// integer registers reject floats. Text is encoded as Latin-1 and
+/// padded with spaces, see `encode_ascii()`.
+pub fn encode(reg_type: RegType, value: &Value, byte_order: ByteOrder) -> DecodeResult<Words> {
+    use RegType::*;
+    match (reg_type, value) {
+        (F32, _) => {
+            let value = value.as_f64().ok_or(DecodeError::InvalidInput)?;
+            words_from_slice(&encode_f32(value as f32, byte_order))
+        }
+        (F64, _) => {
+            let value = value.as_f64().ok_or(DecodeError::InvalidInput)?;
+            words_from_slice(&encode_f64(value, byte_order))
+        }
+        (Ascii(len), Value::Text(text)) => Ok(encode_ascii(text, len, TextEncoding::Latin1)?),
+        (Ascii(_), _) => Err(DecodeError::InvalidInput),
+        (_, Value::I16(value)) => encode_int(i64::from(*value), reg_type),
+        (_, Value::I32(value)) => encode_int(i64::from(*value), reg_type),
+        (_, _) => encode_uint(value.as_u64().ok_or(DecodeError::InvalidInput)?, reg_type),
+    }
+}
+
+fn words_from_slice(words: &[u16]) -> DecodeResult<Words> {
+    Words::from_slice(words).map_err(|()| DecodeError::InvalidInput)
+}
+
+/// Encode a signed integer as two's complement in the width of the
+/// register.
+fn encode_int(value: i64, reg_type: RegType) -> DecodeResult<Words> {
+    if value >= 0 {
+        return encode_uint(value as u64, reg_type);
+    }
+    let bits = match reg_type {
+        RegType::U8 => 8,
+        RegType::U24 => 24,
+        _ => 16 * u32::from(reg_type.word_count()),
+    };
+    if bits < 64 && value < -(1i64 << (bits - 1)) {
+        return Err(DecodeError::InvalidInput);
+    }
+    let mask = u64::MAX >> (64 - bits);
+    encode_uint(value as u64 & mask, reg_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str) -> Value {
+        Value::Text(Text::try_from(text).unwrap())
+    }
+
+    fn encode_vec(
+        reg_type: RegType,
+        value: &Value,
+        byte_order: ByteOrder,
+    ) -> DecodeResult<Vec<u16>> {
+        encode(reg_type, value, byte_order).map(|words| words.to_vec())
+    }
+
+    #[test]
+    fn decode_all_types() {
+        let order = ByteOrder::B1234;
+        assert_eq!(Ok(Value::U8(0x34)), decode(RegType::U8, &[0x0034], order));
+        assert_eq!(
+            Ok(Value::U24(0x12_3456)),
+            decode(RegType::U24, &[0xAB12, 0x3456], order)
+        );
+        assert_eq!(
+            Ok(Value::F32(1.0)),
+            decode(RegType::F32, &[0x0000, 0x3F80], ByteOrder::B3412)
+        );
+        assert_eq!(
+            Ok(Value::F64(1.0)),
+            decode(RegType::F64, &[0x3FF0, 0, 0, 0], order)
+        );
+        assert_eq!(
+            Ok(text("°C")),
+            decode(RegType::Ascii(4), &[0xB043, 0x0000], order)
+        );
+        assert_eq!(
+            Ok(Value::I16(-2)),
+            decode(RegType::U16, &[0xFFFE], order).map(Value::to_signed)
+        );
+    }
+
+    #[test]
+    fn validate_word_count() {
+        let order = ByteOrder::default();
+        assert_eq!(
+            Err(DecodeError::InsufficientInput),
+            decode(RegType::U32, &[0x1234], order)
+        );
+        assert_eq!(
+            Err(DecodeError::InsufficientInput),
+            decode(RegType::Ascii(8), &[0x4142; 3], order)
+        );
+        assert_eq!(
+            Err(DecodeError::InvalidInput),
+            decode(RegType::U16, &[0x1234, 0x5678], order)
+        );
+    }
+
+    #[test]
+    fn encode_all_types() {
+        let order = ByteOrder::B3412;
+        assert_eq!(
+            Ok(vec![0x0000, 0x3F80]),
+            encode_vec(RegType::F32, &Value::F32(1.0), order)
+        );
+        assert_eq!(
+            Ok(vec![0x0000, 0x4180]),
+            encode_vec(RegType::F32, &Value::U16(16), order)
+        );
+        assert_eq!(
+            Ok(vec![0, 0, 0, 0x3FF0]),
+            encode_vec(RegType::F64, &Value::F64(1.0), order)
+        );
+        assert_eq!(
+            Ok(vec![0x1234, 0x5678]),
+            encode_vec(RegType::U32, &Value::U32(0x1234_5678), order)
+        );
+        assert_eq!(
+            Ok(vec![0xFFFE]),
+            encode_vec(RegType::U16, &Value::I16(-2), order)
+        );
+        assert_eq!(
+            Ok(vec![0xFFFF, 0xFFFF]),
+            encode_vec(RegType::U32, &Value::I16(-1), order)
+        );
+        assert_eq!(
+            Err(DecodeError::InvalidInput),
+            encode_vec(RegType::U16, &Value::U32(0x1_0000), order)
+        );
+        assert_eq!(
+            Err(DecodeError::InvalidInput),
+            encode_vec(RegType::U16, &Value::I32(-40_000), order)
+        );
+        assert_eq!(
+            Err(DecodeError::InvalidInput),
+            encode_vec(RegType::U16, &Value::F32(1.0), order)
+        );
+        assert_eq!(
+            Ok(vec![0x4654, 0x2D31, 0x3031, 0x2020]),
+            encode_vec(RegType::Ascii(8), &text("FT-101"), order)
+        );
+        assert_eq!(
+            Err(DecodeError::InvalidInput),
+            encode_vec(RegType::Ascii(4), &text("FT-101"), order)
+        );
+        for (reg_type, value) in [
+            (RegType::U24, Value::U24(0x12_3456)),
+            (RegType::U64, Value::U64(u64::MAX)),
+            (RegType::F64, Value::F64(-0.125)),
+            (RegType::Ascii(3), text("°C")),
+        ] {
+            let words = encode_vec(reg_type, &value, order).unwrap();
+            assert_eq!(Ok(value), decode(reg_type, &words, order));
+        }
+    }
+
+    #[test]
+    fn convert_values() {
+        assert_eq!(Some(1), Value::Bool(true).as_u64());
+        assert_eq!(Some(0x42), Value::Bits(0x42).as_u64());
+        assert_eq!(None, Value::I32(-1).as_u64());
+        assert_eq!(Some(-1.0), Value::I32(-1).as_f64());
+        assert_eq!(Some(2.5), Value::F32(2.5).as_f64());
+        assert_eq!(Some("FT-101"), text("FT-101").as_str());
+        assert_eq!("0b1000010", Value::Bits(0x42).to_string());
+        assert_eq!(Ok(Value::F32(4.0)), Value::parse(RegType::F32, " 4"));
+        assert_eq!(Ok(Value::I32(-2)), Value::parse(RegType::U16, "-2"));
+        assert_eq!(Ok(Value::U8(7)), Value::parse(RegType::U8, "7"));
+        assert_eq!(
+            Err(DecodeError::InvalidInput),
+            Value::parse(RegType::U8, "256")
+        );
+        assert_eq!(Ok(text(" FT ")), Value::parse(RegType::Ascii(8), " FT "));
+    }
+}
@@ -0,0 +1,381 @@
+//! Engineering units of the process variables.
+//!
+//! The transmitters identify units by the numeric codes of the HART
+//! specification. Code 253 denotes a special unit with a user-defined
+//! label.
+
+use core::{fmt, str};
+
+/// Physical quantity of a unit. Only units of the same quantity can
+/// be converted into each other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Quantity {
+    MassFlow,
+    VolumeFlow,
+    Density,
+    Temperature,
+    Pressure,
+    Mass,
+    Volume,
+    DynamicViscosity,
+    KinematicViscosity,
+}
+
+/// A standard unit from the unit code table.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UnitDef {
+    pub code: u16,
+    pub symbol: &'static str,
+    pub quantity: Quantity,
+    /// Conversion into the SI unit of the quantity: `si = value × factor + offset`.
+    /// Units without a linear relation, e.g. °API, have no factor.
+    factor: Option<f64>,
+    offset: f64,
+}
+
+const fn unit(code: u16, symbol: &'static str, quantity: Quantity, factor: f64) -> UnitDef {
+    UnitDef {
+        code,
+        symbol,
+        quantity,
+        factor: Some(factor),
+        offset: 0.0,
+    }
+}
+
+const GALLON: f64 = 3.785_411_784e-3;
+const IMP_GALLON: f64 = 4.546_09e-3;
+const BARREL: f64 = 42.0 * GALLON;
+const CUBIC_FOOT: f64 = 0.028_316_846_592;
+const POUND: f64 = 0.453_592_37;
+const SHORT_TON: f64 = 2000.0 * POUND;
+const LONG_TON: f64 = 2240.0 * POUND;
+const MINUTE: f64 = 60.0;
+const HOUR: f64 = 3600.0;
+const DAY: f64 = 86400.0;
+
+/// All standard units in ascending order of their codes.
+pub static UNITS: [UnitDef; 88] = {
+    use Quantity::*;
+    [
+        unit(1, "inH2O@68F", Pressure, 248.84),
+        unit(2, "inHg", Pressure, 3386.389),
+        unit(3, "ftH2O@68F", Pressure, 2986.08),
+        unit(4, "mmH2O@68F", Pressure, 9.789_04),
+        unit(5, "mmHg", Pressure, 133.322_4),
+        unit(6, "psi", Pressure, 6_894.757_293_168),
+        unit(7, "bar", Pressure, 1e5),
+        unit(8, "mbar", Pressure, 100.0),
+        unit(9, "g/cm2", Pressure, 98.0665),
+        unit(10, "kg/cm2", Pressure, 98066.5),
+        unit(11, "Pa", Pressure, 1.0),
+        unit(12, "kPa", Pressure, 1e3),
+        unit(13, "torr", Pressure, 133.322_4),
+        unit(14, "atm", Pressure, 101_325.0),
+        unit(15, "ft3/min", VolumeFlow, CUBIC_FOOT / MINUTE),
+        unit(16, "gal/min", VolumeFlow, GALLON / MINUTE),
+        unit(17, "l/min", VolumeFlow, 1e-3 / MINUTE),
+        unit(18, "ImpGal/min", VolumeFlow, IMP_GALLON / MINUTE),
+        unit(19, "m3/h", VolumeFlow, 1.0 / HOUR),
+        unit(22, "gal/s", VolumeFlow, GALLON),
+        unit(23, "MMgal/d", VolumeFlow, 1e6 * GALLON / DAY),
+        unit(24, "l/s", VolumeFlow, 1e-3),
+        unit(25, "Ml/d", VolumeFlow, 1e3 / DAY),
+        unit(26, "ft3/s", VolumeFlow, CUBIC_FOOT),
+        unit(27, "ft3/d", VolumeFlow, CUBIC_FOOT / DAY),
+        unit(28, "m3/s", VolumeFlow, 1.0),
+        unit(29, "m3/d", VolumeFlow, 1.0 / DAY),
+        unit(30, "ImpGal/h", VolumeFlow, IMP_GALLON / HOUR),
+        unit(31, "ImpGal/d", VolumeFlow, IMP_GALLON / DAY),
+        UnitDef {
+            code: 32,
+            symbol: "degC",
+            quantity: Temperature,
+            factor: Some(1.0),
+            offset: 273.15,
+        },
+        UnitDef {
+            code: 33,
+            symbol: "degF",
+            quantity: Temperature,
+            factor: Some(5.0 / 9.0),
+            offset: 459.67 * 5.0 / 9.0,
+        },
+        unit(34, "degR", Temperature, 5.0 / 9.0),
+        unit(35, "K", Temperature, 1.0),
+        unit(40, "gal", Volume, GALLON),
+        unit(41, "l", Volume, 1e-3),
+        unit(42, "ImpGal", Volume, IMP_GALLON),
+        unit(43, "m3", Volume, 1.0),
+        unit(46, "bbl", Volume, BARREL),
+        unit(54, "cSt", KinematicViscosity, 1e-6),
+        unit(55, "cP", DynamicViscosity, 1e-3),
+        unit(60, "g", Mass, 1e-3),
+        unit(61, "kg", Mass, 1.0),
+        unit(62, "t", Mass, 1e3),
+        unit(63, "lb", Mass, POUND),
+        unit(64, "STon", Mass, SHORT_TON),
+        unit(65, "LTon", Mass, LONG_TON),
+        unit(70, "g/s", MassFlow, 1e-3),
+        unit(71, "g/min", MassFlow, 1e-3 / MINUTE),
+        unit(72, "g/h", MassFlow, 1e-3 / HOUR),
+        unit(73, "kg/s", MassFlow, 1.0),
+        unit(74, "kg/min", MassFlow, 1.0 / MINUTE),
+        unit(75, "kg/h", MassFlow, 1.0 / HOUR),
+        unit(76, "kg/d", MassFlow, 1.0 / DAY),
+        unit(77, "t/min", MassFlow, 1e3 / MINUTE),
+        unit(78, "t/h", MassFlow, 1e3 / HOUR),
+        unit(79, "t/d", MassFlow, 1e3 / DAY),
+        unit(80, "lb/s", MassFlow, POUND),
+        unit(81, "lb/min", MassFlow, POUND / MINUTE),
+        unit(82, "lb/h", MassFlow, POUND / HOUR),
+        unit(83, "lb/d", MassFlow, POUND / DAY),
+        unit(84, "STon/min", MassFlow, SHORT_TON / MINUTE),
+        unit(85, "STon/h", MassFlow, SHORT_TON / HOUR),
+        unit(86, "STon/d", MassFlow, SHORT_TON / DAY),
+        unit(87, "LTon/h", MassFlow, LONG_TON / HOUR),
+        unit(88, "LTon/d", MassFlow, LONG_TON / DAY),
+        UnitDef {
+            code: 90,
+            symbol: "SGU",
+            quantity: Density,
+            factor: None,
+            offset: 0.0,
+        },
+        unit(91, "g/cm3", Density, 1e3),
+        unit(92, "kg/m3", Density, 1.0),
+        unit(93, "lb/gal", Density, POUND / GALLON),
+        unit(94, "lb/ft3", Density, POUND / CUBIC_FOOT),
+        unit(95, "g/ml", Density, 1e3),
+        unit(96, "kg/l", Density, 1e3),
+        unit(97, "g/l", Density, 1.0),
+        unit(98, "lb/in3", Density, POUND / (CUBIC_FOOT / 1728.0)),
+        unit(99, "STon/yd3", Density, SHORT_TON / (27.0 * CUBIC_FOOT)),
+        UnitDef {
+            code: 104,
+            symbol: "degAPI",
+            quantity: Density,
+            factor: None,
+            offset: 0.0,
+        },
+        unit(112, "ft3", Volume, CUBIC_FOOT),
+        unit(130, "ft3/h", VolumeFlow, CUBIC_FOOT / HOUR),
+        unit(131, "m3/min", VolumeFlow, 1.0 / MINUTE),
+        unit(132, "bbl/s", VolumeFlow, BARREL),
+        unit(133, "bbl/min", VolumeFlow, BARREL / MINUTE),
+        unit(134, "bbl/h", VolumeFlow, BARREL / HOUR),
+        unit(135, "bbl/d", VolumeFlow, BARREL / DAY),
+        unit(136, "gal/h", VolumeFlow, GALLON / HOUR),
+        unit(137, "ImpGal/s", VolumeFlow, IMP_GALLON),
+        unit(138, "l/h", VolumeFlow, 1e-3 / HOUR),
+        unit(235, "gal/d", VolumeFlow, GALLON / DAY),
+        unit(237, "MPa", Pressure, 1e6),
+    ]
+};
+
+/// Unit code of a special unit with a user-defined label.
+pub const SPECIAL_UNIT_CODE: u16 = 253;
+
+/// Maximum length of the label of a special unit.
+pub const SPECIAL_UNIT_LEN: usize = 8;
+
+/// User-defined label of a special unit, e.g. `"bbl/min"`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SpecialUnit {
+    label: [u8; SPECIAL_UNIT_LEN],
+    len: u8,
+}
+
+impl SpecialUnit {
+    /// The label is truncated to `SPECIAL_UNIT_LEN` characters. Labels
+    /// that are not ASCII are rejected.
+    pub fn new(label: &str) -> Option<Self> {
+        if !label.is_ascii() {
+            return None;
+        }
+        let label = label.trim_matches(|c: char| c == '\0' || c.is_ascii_whitespace());
+        let len = label.len().min(SPECIAL_UNIT_LEN);
+        let mut bytes = [0; SPECIAL_UNIT_LEN];
+        bytes[..len].copy_from_slice(&label.as_bytes()[..len]);
+        Some(Self {
+            label: bytes,
+            len: len as u8,
+        })
+    }
+
+    /// Decode the label from the words of a unit string register, e.g. 52.
+    pub fn from_words(words: &[u16]) -> Option<Self> {
+        let mut bytes = [0; SPECIAL_UNIT_LEN];
+        for (i, word) in words.iter().take(SPECIAL_UNIT_LEN / 2).enumerate() {
+            bytes[2 * i..2 * i + 2].copy_from_slice(&word.to_be_bytes());
+        }
+        let len = (2 * words.len()).min(SPECIAL_UNIT_LEN);
+        Self::new(str::from_utf8(&bytes[..len]).ok()?)
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Only ASCII is accepted on construction
+        str::from_utf8(&self.label[..usize::from(self.len)]).unwrap_or_default()
+    }
+}
+
+impl fmt::Debug for SpecialUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SpecialUnit").field(&self.as_str()).finish()
+    }
+}
+
+/// The engineering unit of a value.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Unit {
+    Standard(&'static UnitDef),
+    /// Unit code 253 together with the label from the unit string register.
+    Special(SpecialUnit),
+    /// A code that is neither in the unit table nor a special unit.
+    Unknown(u16),
+}
+
+impl Unit {
+    /// Look up a unit code. Special units need to be created from
+    /// their label instead.
+    pub fn from_code(code: u16) -> Self {
+        UNITS
+            .binary_search_by_key(&code, |def| def.code)
+            .map(|idx| Unit::Standard(&UNITS[idx]))
+            .unwrap_or(Unit::Unknown(code))
+    }
+
+    /// Look up a standard unit by its symbol, e.g. `"lb/min"`.
+    ///
+    /// The symbols are case-sensitive, because "Ml/d" and "ml/d" are
+    /// different units. `°C` is accepted for `degC`.
+    pub fn from_symbol(symbol: &str) -> Option<Self> {
+        let symbol = symbol.trim();
+        let symbol = symbol.strip_prefix('°').unwrap_or(symbol);
+        UNITS
+            .iter()
+            .find(|def| {
+                def.symbol == symbol || def.symbol.strip_prefix("deg").is_some_and(|s| s == symbol)
+            })
+            .map(Unit::Standard)
+    }
+
+    pub fn code(&self) -> u16 {
+        match self {
+            Unit::Standard(def) => def.code,
+            Unit::Special(_) => SPECIAL_UNIT_CODE,
+            Unit::Unknown(code) => *code,
+        }
+    }
+
+    pub fn quantity(&self) -> Option<Quantity> {
+        match self {
+            Unit::Standard(def) => Some(def.quantity),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Unit::Standard(def) => write!(f, "{}", def.symbol),
+            Unit::Special(special) => write!(f, "{}", special.as_str()),
+            Unit::Unknown(code) => write!(f, "unit #{}", code),
+        }
+    }
+}
+
+impl str::FromStr for Unit {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_symbol(s).ok_or(())
+    }
+}
+
+/// A value tagged with its engineering unit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UnitValue {
+    pub value: f32,
+    pub unit: Unit,
+}
+
+impl UnitValue {
+    pub const fn new(value: f32, unit: Unit) -> Self {
+        Self { value, unit }
+    }
+
+    /// Convert into another standard unit of the same quantity.
+    ///
+    /// Returns `None` for special or unknown units, units of different
+    /// quantities and units without a linear conversion like °API.
+    pub fn convert(self, unit: Unit) -> Option<Self> {
+        let (from, to) = match (self.unit, unit) {
+            (Unit::Standard(from), Unit::Standard(to)) if from.quantity == to.quantity => {
+                (from, to)
+            }
+            _ => return None,
+        };
+        let si = f64::from(self.value) * from.factor? + from.offset;
+        let value = (si - to.offset) / to.factor?;
+        Some(Self::new(value as f32, unit))
+    }
+}
+
+impl fmt::Display for UnitValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.value, self.unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unit_table_is_sorted() {
+        assert!(UNITS.windows(2).all(|w| w[0].code < w[1].code));
+    }
+
+    #[test]
+    fn lookup_units() {
+        assert_eq!("kg/s", Unit::from_code(73).to_string());
+        assert_eq!("lb/min", Unit::from_code(81).to_string());
+        assert_eq!(Unit::Unknown(252), Unit::from_code(252));
+        assert_eq!(Some(Quantity::Temperature), Unit::from_code(32).quantity());
+        assert_eq!(Some(Unit::from_code(32)), Unit::from_symbol("°C"));
+        assert_eq!(Some(Unit::from_code(33)), "degF".parse().ok());
+        assert_eq!(Unit::from_code(25), "Ml/d".parse().unwrap());
+        assert!(Unit::from_symbol("furlong/fortnight").is_none());
+    }
+
+    #[test]
+    fn special_units() {
+        let special = SpecialUnit::from_words(&[0x6262, 0x6C2F, 0x6D69, 0x6E00]).unwrap();
+        assert_eq!("bbl/min", special.as_str());
+        assert_eq!("bbl/min", Unit::Special(special).to_string());
+        assert_eq!(SPECIAL_UNIT_CODE, Unit::Special(special).code());
+        assert_eq!("", SpecialUnit::from_words(&[0x2020]).unwrap().as_str());
+        assert!(SpecialUnit::new("°X").is_none());
+    }
+
+    #[test]
+    fn convert_units() {
+        let kg_per_s = UnitValue::new(1.0, Unit::from_code(73));
+        let lb_per_min = kg_per_s.convert("lb/min".parse().unwrap()).unwrap();
+        assert!((lb_per_min.value - 132.277_36).abs() < 1e-3);
+        assert_eq!("lb/min", lb_per_min.unit.to_string());
+
+        let deg_c = UnitValue::new(100.0, "degC".parse().unwrap());
+        let deg_f = deg_c.convert("degF".parse().unwrap()).unwrap();
+        assert!((deg_f.value - 212.0).abs() < 1e-3);
+        let kelvin = deg_f.convert("K".parse().unwrap()).unwrap();
+        assert!((kelvin.value - 373.15).abs() < 1e-3);
+
+        assert!(kg_per_s.convert("kg".parse().unwrap()).is_none());
+        let api = UnitValue::new(30.0, Unit::from_code(104));
+        assert!(api.convert("kg/m3".parse().unwrap()).is_none());
+        assert_eq!("1 kg/s", kg_per_s.to_string());
+    }
+}
@@ -0,0 +1,371 @@
+//! The causes of failed requests.
+//!
+//! `client::Client` and `blocking::Client` return an `Error`. The legacy
+//! `modbus::SlaveProxy` returns `std::io::Error`s like `tokio-modbus`
+//! does, which carry an `Error` as their inner error that is recovered
+//! with `Error::from()`. Errors of `tokio-modbus` itself are classified
+//! by their kind and message.
+
+use crate::{
+    buildmap::AccessError,
+    core::modbus::{rtu::Request, DecodeError, ExceptionCode, ExceptionResponse, Value},
+};
+
+use std::{fmt, io};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// The request that failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Target {
+    pub slave: u8,
+    pub function: u8,
+    /// 0-based protocol address of the first register or coil
+    pub addr: u16,
+    /// The number of registers or coils, 1 for single writes
+    pub count: u16,
+}
+
+impl Target {
+    pub fn new(slave: u8, request: &Request<'_>) -> Self {
+        Self {
+            slave,
+            function: request.function(),
+            addr: request.addr(),
+            count: request.count(),
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "slave {}, function {}, address {}, count {}",
+            self.slave, self.function, self.addr, self.count
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// Connecting, sending or receiving failed, e.g. the serial port
+    /// has been unplugged.
+    Transport {
+        target: Option<Target>,
+        source: io::Error,
+    },
+    /// No complete response has been received in time.
+    Timeout { target: Option<Target> },
+    /// The checksum of the response frame does not match.
+    Crc { target: Option<Target> },
+    /// The slave rejected the request.
+    Exception {
+        target: Option<Target>,
+        exception: ExceptionResponse,
+    },
+    /// The response does not match the request or the value of a
+    /// register is invalid.
+    Decode {
+        target: Option<Target>,
+        source: DecodeError,
+    },
+    /// The write has been refused before sending it.
+    Refused {
+        target: Option<Target>,
+        source: AccessError,
+    },
+    /// The request has not been sent, because its parameters are
+    /// invalid, e.g. a value that does not fit the register.
+    InvalidInput {
+        target: Option<Target>,
+        message: String,
+    },
+    /// Register `addr` reads back a different value than written.
+    Mismatch {
+        target: Option<Target>,
+        addr: u16,
+        written: Box<Value>,
+        read_back: Box<Value>,
+    },
+    /// The device does not provide the requested value.
+    Unsupported {
+        target: Option<Target>,
+        what: &'static str,
+    },
+}
+
+impl Error {
+    pub fn target(&self) -> Option<&Target> {
+        use Error::*;
+        match self {
+            Transport { target, .. }
+            | Timeout { target }
+            | Crc { target }
+            | Exception { target, .. }
+            | Decode { target, .. }
+            | Refused { target, .. }
+            | InvalidInput { target, .. }
+            | Mismatch { target, .. }
+            | Unsupported { target, .. } => target.as_ref(),
+        }
+    }
+
+    /// Attach the request unless the error already refers to one.
+    pub fn with_target(mut self, request: Target) -> Self {
+        use Error::*;
+        match &mut self {
+            Transport { target, .. }
+            | Timeout { target }
+            | Crc { target }
+            | Exception { target, .. }
+            | Decode { target, .. }
+            | Refused { target, .. }
+            | InvalidInput { target, .. }
+            | Mismatch { target, .. }
+            | Unsupported { target, .. } => {
+                target.get_or_insert(request);
+            }
+        }
+        self
+    }
+
+    pub fn exception_code(&self) -> Option<ExceptionCode> {
+        match self {
+            Error::Exception { exception, .. } => Some(exception.code),
+            _ => None,
+        }
+    }
+
+    /// Whether the connection needs to be re-established.
+    ///
+    /// The slave has answered exceptions and malformed responses on an
+    /// intact connection, and refused or invalid requests have not been
+    /// sent at all. After transport errors, timeouts and CRC failures
+    /// the next response might belong to the failed request.
+    pub fn needs_reconnect(&self) -> bool {
+        use Error::*;
+        match self {
+            Transport { .. } | Timeout { .. } | Crc { .. } => true,
+            Exception { .. }
+            | Decode { .. }
+            | Refused { .. }
+            | InvalidInput { .. }
+            | Mismatch { .. }
+            | Unsupported { .. } => false,
+        }
+    }
+
+    /// Whether the connection needs to be re-established after an I/O
+    /// error of a client, see `needs_reconnect()`.
+    ///
+    /// Errors that are not recognized as one of the other causes are
+    /// considered transport errors.
+    pub fn needs_reconnect_after(err: &io::Error) -> bool {
+        match err.get_ref().and_then(|inner| inner.downcast_ref::<Self>()) {
+            Some(err) => err.needs_reconnect(),
+            None => Self::classify(err).is_none_or(|err| err.needs_reconnect()),
+        }
+    }
+
+    /// The cause of errors that are not wrapped into an `Error`.
+    fn classify(err: &io::Error) -> Option<Self> {
+        if err.kind() == io::ErrorKind::TimedOut {
+            return Some(Error::Timeout { target: None });
+        }
+        let inner = err.get_ref()?;
+        if let Some(exception) = inner.downcast_ref::<ExceptionResponse>() {
+            return Some(Error::Exception {
+                target: None,
+                exception: *exception,
+            });
+        }
+        if let Some(source) = inner.downcast_ref::<DecodeError>() {
+            return Some(Error::Decode {
+                target: None,
+                source: *source,
+            });
+        }
+        if let Some(source) = inner.downcast_ref::<AccessError>() {
+            return Some(Error::Refused {
+                target: None,
+                source: *source,
+            });
+        }
+        // tokio-modbus hides the exception type
+        #[cfg(feature = "tokio-modbus-rtu")]
+        if err.kind() == io::ErrorKind::Other {
+            if let Some(exception) = ExceptionResponse::parse(&inner.to_string()) {
+                return Some(Error::Exception {
+                    target: None,
+                    exception,
+                });
+            }
+        }
+        None
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Error::*;
+        match self {
+            Transport { source, .. } => write!(f, "Transport error: {}", source)?,
+            Timeout { .. } => write!(f, "Modbus request timed out")?,
+            Crc { .. } => write!(f, "CRC mismatch")?,
+            Exception { exception, .. } => exception.fmt(f)?,
+            Decode { source, .. } => write!(f, "Decoding failed: {}", source)?,
+            Refused { source, .. } => source.fmt(f)?,
+            InvalidInput { message, .. } => f.write_str(message)?,
+            Mismatch {
+                addr,
+                written,
+                read_back,
+                ..
+            } => write!(
+                f,
+                "Register {} reads back {} instead of {}",
+                addr, read_back, written
+            )?,
+            Unsupported { what, .. } => write!(f, "{} is not available", what)?,
+        }
+        match self.target() {
+            Some(target) => write!(f, " ({})", target),
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Transport { source, .. } => Some(source),
+            Error::Exception { exception, .. } => Some(exception),
+            Error::Decode { source, .. } => Some(source),
+            Error::Refused { source, .. } => Some(source),
+            Error::Timeout { .. }
+            | Error::Crc { .. }
+            | Error::InvalidInput { .. }
+            | Error::Mismatch { .. }
+            | Error::Unsupported { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(from: io::Error) -> Self {
+        if from.get_ref().is_some_and(|inner| inner.is::<Self>()) {
+            let inner = from.into_inner().expect("inner error");
+            return *inner.downcast::<Self>().expect("crate error");
+        }
+        match Self::classify(&from) {
+            Some(err) => err,
+            None => Error::Transport {
+                target: None,
+                source: from,
+            },
+        }
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(from: DecodeError) -> Self {
+        Error::Decode {
+            target: None,
+            source: from,
+        }
+    }
+}
+
+impl From<AccessError> for Error {
+    fn from(from: AccessError) -> Self {
+        Error::Refused {
+            target: None,
+            source: from,
+        }
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(from: Error) -> Self {
+        use io::ErrorKind;
+        let kind = match &from {
+            Error::Transport { source, .. } => source.kind(),
+            Error::Timeout { .. } => ErrorKind::TimedOut,
+            Error::Crc { .. } => ErrorKind::InvalidData,
+            Error::Exception { .. } => ErrorKind::Other,
+            Error::Decode { source, .. } => io::Error::from(*source).kind(),
+            Error::Refused { source, .. } => io::Error::from(*source).kind(),
+            Error::InvalidInput { .. } => ErrorKind::InvalidInput,
+            Error::Mismatch { .. } => ErrorKind::InvalidData,
+            Error::Unsupported { .. } => ErrorKind::Unsupported,
+        };
+        io::Error::new(kind, from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_io_errors() {
+        let target = Target::new(
+            1,
+            &Request::ReadHoldingRegisters {
+                addr: 246,
+                count: 2,
+            },
+        );
+        let exception = ExceptionResponse {
+            function: 3,
+            code: ExceptionCode::IllegalDataAddress,
+        };
+        let err = io::Error::other(exception);
+        assert!(!Error::needs_reconnect_after(&err));
+        let err = Error::from(err).with_target(target);
+        assert_eq!(
+            Some(ExceptionCode::IllegalDataAddress),
+            err.exception_code()
+        );
+        assert_eq!(
+            "Modbus function 3: Illegal data address (slave 1, function 3, address 246, count 2)",
+            err.to_string()
+        );
+
+        // The crate error survives the round trip through io::Error
+        let err = io::Error::from(err);
+        assert_eq!(io::ErrorKind::Other, err.kind());
+        assert!(!Error::needs_reconnect_after(&err));
+        let err = Error::from(err);
+        assert_eq!(Some(&target), err.target());
+        assert!(!err.needs_reconnect());
+
+        let err = Error::from(io::Error::new(io::ErrorKind::TimedOut, "reading timed out"));
+        assert!(matches!(err, Error::Timeout { target: None }));
+        assert!(err.needs_reconnect());
+        let err = Error::from(io::Error::from(DecodeError::InvalidData));
+        assert!(matches!(err, Error::Decode { .. }));
+        assert!(!err.needs_reconnect());
+        let err = Error::from(io::Error::from(AccessError::ReadOnly));
+        assert!(matches!(err, Error::Refused { .. }));
+        let err = io::Error::new(io::ErrorKind::BrokenPipe, "unplugged");
+        assert!(Error::needs_reconnect_after(&err));
+        assert!(matches!(Error::from(err), Error::Transport { .. }));
+    }
+
+    #[test]
+    #[cfg(feature = "tokio-modbus-rtu")]
+    fn classify_tokio_modbus_exceptions() {
+        let exception = ExceptionResponse {
+            function: 3,
+            code: ExceptionCode::SlaveDeviceBusy,
+        };
+        let err = io::Error::other(exception.to_string());
+        assert!(!Error::needs_reconnect_after(&err));
+        assert_eq!(
+            Some(ExceptionCode::SlaveDeviceBusy),
+            Error::from(err).exception_code()
+        );
+    }
+}
@@ -1,13 +1,27 @@
 #![cfg_attr(not(feature = "std"), no_std)]
-/// The no_std enclave
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(all(feature = "std", feature = "modbus"))]
 pub mod buildmap;
+/// The no_std enclave
 pub mod core;
+#[cfg(all(feature = "std", feature = "modbus"))]
+pub mod error;
+#[cfg(all(feature = "std", feature = "modbus"))]
 pub mod setup;
 pub use self::core::*;
 
 #[cfg(feature = "tokio-modbus-rtu")]
 pub mod modbus;
 
+#[cfg(all(feature = "std", feature = "modbus", feature = "rtu"))]
+pub mod blocking;
+
+#[cfg(feature = "async")]
+pub mod client;
+
 #[cfg(feature = "tokio-mock")]
 pub mod mock;
 
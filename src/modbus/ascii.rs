@@ -0,0 +1,237 @@
+//! Modbus ASCII transport, e.g. for radios and modems that only pass
+//! 7-bit characters.
+//!
+//! The framing is implemented in `core::modbus::ascii`, the PDUs are
+//! encoded and decoded by `tokio-modbus`.
+
+use crate::core::modbus::{ascii::*, ExceptionCode, ExceptionResponse};
+
+use bytes::{Bytes, BytesMut};
+use futures::{future, Future, Sink, Stream};
+use std::{
+    cell::RefCell,
+    convert::TryFrom,
+    io::{Error, ErrorKind, Result},
+    rc::Rc,
+};
+use tokio::codec::{Decoder, Encoder, Framed};
+use tokio_io::{AsyncRead, AsyncWrite};
+use tokio_modbus::{
+    client::{Client, Context as ClientContext},
+    prelude::{Request, Response, Slave, SlaveContext, SlaveId},
+};
+
+/// Splits the received characters into ADUs and encodes outgoing ADUs
+/// as frames.
+///
+/// Characters in front of the start of a frame are discarded.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AsciiCodec;
+
+impl Decoder for AsciiCodec {
+    type Item = Vec<u8>;
+    type Error = Error;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>> {
+        let end = match buf.windows(FRAME_END.len()).position(|w| w == FRAME_END) {
+            Some(pos) => pos + FRAME_END.len(),
+            None if buf.len() > MAX_FRAME_LEN => {
+                buf.clear();
+                return Err(Error::new(ErrorKind::InvalidData, "Frame too long"));
+            }
+            None => return Ok(None),
+        };
+        let frame = buf.split_to(end);
+        let start = match frame.iter().rposition(|b| *b == FRAME_START) {
+            Some(start) => start,
+            None => return Err(Error::new(ErrorKind::InvalidData, "Missing start of frame")),
+        };
+        let mut adu = vec![0; MAX_ADU_LEN];
+        let len = decode_frame(&frame[start..], &mut adu)?;
+        adu.truncate(len);
+        Ok(Some(adu))
+    }
+}
+
+impl Encoder for AsciiCodec {
+    type Item = Vec<u8>;
+    type Error = Error;
+
+    fn encode(&mut self, adu: Self::Item, buf: &mut BytesMut) -> Result<()> {
+        let mut frame = [0; MAX_FRAME_LEN];
+        let len = encode_frame(&adu, &mut frame)?;
+        buf.extend_from_slice(&frame[..len]);
+        Ok(())
+    }
+}
+
+/// A request is in flight while the transport is taken out of the
+/// client. It is not put back after I/O errors and timeouts, so that
+/// late responses cannot be mistaken for the next one. Reconnect the
+/// `SharedContext` to continue.
+struct AsciiClient<T: AsyncRead + AsyncWrite + 'static> {
+    transport: Rc<RefCell<Option<Framed<T, AsciiCodec>>>>,
+    slave_id: SlaveId,
+}
+
+impl<T: AsyncRead + AsyncWrite + 'static> AsciiClient<T> {
+    fn call(&self, req: Request) -> impl Future<Item = Response, Error = Error> {
+        let framed = match self.transport.borrow_mut().take() {
+            Some(framed) => framed,
+            None if req == Request::Disconnect => {
+                return future::Either::A(future::err(Error::from(ErrorKind::NotConnected)))
+            }
+            None => {
+                return future::Either::A(future::err(Error::new(
+                    ErrorKind::NotConnected,
+                    "Modbus ASCII transport is busy or disconnected",
+                )))
+            }
+        };
+        if req == Request::Disconnect {
+            // Dropping the transport closes the port
+            return future::Either::A(future::err(Error::from(ErrorKind::NotConnected)));
+        }
+        let slave_id = self.slave_id;
+        let mut req_adu = vec![slave_id];
+        req_adu.extend_from_slice(&Bytes::from(req));
+        let function = req_adu[1];
+        let transport = Rc::clone(&self.transport);
+        future::Either::B(
+            framed
+                .send(req_adu)
+                .and_then(|framed| framed.into_future().map_err(|(err, _)| err))
+                .and_then(move |(rsp_adu, framed)| {
+                    transport.replace(Some(framed));
+                    let rsp_adu = rsp_adu.ok_or_else(|| Error::from(ErrorKind::UnexpectedEof))?;
+                    decode_response(slave_id, function, rsp_adu)
+                }),
+        )
+    }
+}
+
+fn decode_response(slave_id: SlaveId, function: u8, rsp_adu: Vec<u8>) -> Result<Response> {
+    match rsp_adu.as_slice() {
+        [id, ..] if *id != slave_id => Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "Invalid response header: expected slave {}, actual slave {}",
+                slave_id, id
+            ),
+        )),
+        [_, code, exception] if *code == function | 0x80 => Err(Error::other(ExceptionResponse {
+            function,
+            code: ExceptionCode::from_code(*exception),
+        })),
+        [_, code, ..] if *code == function => Response::try_from(Bytes::from(&rsp_adu[1..])),
+        _ => Err(Error::new(ErrorKind::InvalidData, "Invalid response")),
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite + 'static> SlaveContext for AsciiClient<T> {
+    fn set_slave(&mut self, slave: Slave) {
+        self.slave_id = slave.into();
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite + 'static> Client for AsciiClient<T> {
+    fn call(&self, req: Request) -> Box<dyn Future<Item = Response, Error = Error>> {
+        Box::new(self.call(req))
+    }
+}
+
+/// Create a Modbus ASCII client context on top of a transport, e.g. a
+/// serial port with 7 data bits.
+pub fn connect_slave<T: AsyncRead + AsyncWrite + 'static>(
+    transport: T,
+    slave: Slave,
+) -> impl Future<Item = ClientContext, Error = Error> {
+    let client: Box<dyn Client> = Box::new(AsciiClient {
+        transport: Rc::new(RefCell::new(Some(Framed::new(transport, AsciiCodec)))),
+        slave_id: slave.into(),
+    });
+    future::ok(client.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        thread,
+    };
+    use tokio_core::{net::TcpStream, reactor::Core};
+    use tokio_modbus::prelude::*;
+
+    #[test]
+    fn decode_frames() {
+        let mut codec = AsciiCodec;
+        let mut buf = BytesMut::from(&b"\x00\r\n:1103006B00037E\r\n:110"[..]);
+        assert_eq!(
+            ErrorKind::InvalidData,
+            codec.decode(&mut buf).unwrap_err().kind()
+        );
+        assert_eq!(
+            Some(vec![0x11, 0x03, 0x00, 0x6B, 0x00, 0x03]),
+            codec.decode(&mut buf).unwrap()
+        );
+        assert_eq!(None, codec.decode(&mut buf).unwrap());
+        buf.extend_from_slice(b"3006B00037E\r\n");
+        assert!(codec.decode(&mut buf).unwrap().is_some());
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn read_and_write() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let socket_addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut stream = stream;
+            let mut requests = Vec::new();
+            for rsp in &[
+                ":6F0304000042F652\r\n",
+                ":6F83020C\r\n",
+                ":6F06009B0001EF\r\n",
+            ] {
+                let mut req = String::new();
+                reader.read_line(&mut req).unwrap();
+                requests.push(req);
+                stream.write_all(rsp.as_bytes()).unwrap();
+            }
+            requests
+        });
+
+        let mut core = Core::new().unwrap();
+        let handle = core.handle();
+        let context = core
+            .run(
+                TcpStream::connect(&socket_addr, &handle)
+                    .and_then(|stream| connect_slave(stream, Slave(111))),
+            )
+            .unwrap();
+        assert_eq!(
+            vec![0x0000, 0x42F6],
+            core.run(context.read_holding_registers(246, 2)).unwrap()
+        );
+        assert_eq!(
+            "Modbus function 3: Illegal data address",
+            core.run(context.read_holding_registers(0xFFFE, 1))
+                .unwrap_err()
+                .to_string()
+        );
+        core.run(context.write_single_register(155, 1)).unwrap();
+
+        assert_eq!(
+            vec![
+                ":6F0300F6000296\r\n",
+                ":6F03FFFE000190\r\n",
+                ":6F06009B0001EF\r\n",
+            ],
+            server.join().unwrap()
+        );
+    }
+}
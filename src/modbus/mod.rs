@@ -1,13 +1,31 @@
-use super::*;
-
+pub mod ascii;
 #[cfg(feature = "rtu")]
 pub mod rtu;
+mod scheduler;
+pub mod tcp;
+
+pub use self::scheduler::{Priority, Scheduler};
 
-use crate::core::modbus::*;
+use crate::{
+    buildmap::{BitDef, Confirm, DeviceProfile, RegisterDef},
+    core::{
+        modbus::{
+            alarms::{AlarmStatus, STATUS_REGS},
+            plan::ReadPlan,
+            rtu::Request,
+            scaled::{ScaledVar, Scaling, SCALING_REG_COUNT, SCALING_REG_START},
+            units::{special_unit_reg, unit_reg},
+            *,
+        },
+        units::{SpecialUnit, Unit, UnitValue, SPECIAL_UNIT_CODE},
+    },
+    error::Target,
+};
 
 use futures::Future;
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
+    collections::HashMap,
     io::{Error, ErrorKind, Result},
     rc::Rc,
     time::Duration,
@@ -19,16 +37,6 @@ use tokio_modbus::{
     prelude::*,
 };
 
-impl From<DecodeError> for Error {
-    fn from(from: DecodeError) -> Self {
-        use DecodeError::*;
-        match from {
-            InsufficientInput | InvalidInput => Self::new(ErrorKind::InvalidInput, from),
-            InvalidData => Self::new(ErrorKind::InvalidData, from),
-        }
-    }
-}
-
 /// The fixed broadcast address of all sensors that cannot be altered.
 ///
 /// Warning: This address should only be used for configuration purposes,
@@ -47,27 +55,23 @@ pub fn broadcast_slave(
     context.write_single_register(BROADCAST_REG_ADDR, u16::from(slave_id))
 }
 
-//generics
-// give it a u16 reg start and reg count argument
+/// Read the raw words of a register, the number of words is derived
+/// from the register type.
 pub fn read_generic(
     context: &mut client::Context,
     reg_start: u16,
-    reg_count: u16,
-    reg_type: char,
+    reg_type: RegType,
 ) -> impl Future<Item = Vec<u16>, Error = Error> {
-    context
-        // match on reg_type and decode accordingly
-        .read_holding_registers(reg_start, reg_count)
+    context.read_holding_registers(reg_start, reg_type.word_count())
 }
 
 pub fn read_generic_with_timeout(
     context: &mut client::Context,
     timeout: Duration,
     reg_start: u16,
-    reg_count: u16,
-    reg_type: char,
+    reg_type: RegType,
 ) -> impl Future<Item = Vec<u16>, Error = Error> {
-    read_generic(context, reg_start, reg_count, reg_type)
+    read_generic(context, reg_start, reg_type)
         .timeout(timeout)
         .map_err(move |err| {
             err.into_inner().unwrap_or_else(|| {
@@ -79,9 +83,104 @@ pub fn read_generic_with_timeout(
         })
 }
 
+/// Read the maximum integer, offsets and scale factors of all scaled
+/// integers.
+pub fn read_scaling(context: &mut client::Context) -> impl Future<Item = Scaling, Error = Error> {
+    context
+        .read_holding_registers(SCALING_REG_START, SCALING_REG_COUNT)
+        .and_then(|words| Ok(Scaling::decode(&words)?))
+}
+
+/// Write the raw words of a register.
+///
+/// A single word is written with _Write Single Register_ (0x06) and
+/// multiple words with _Write Multiple Registers_ (0x10). Writes are
+/// only checked by `SlaveProxy::write_register()`.
+fn write_generic(
+    context: &mut client::Context,
+    reg_start: u16,
+    words: &[u16],
+) -> impl Future<Item = (), Error = Error> {
+    match *words {
+        [word] => future::Either::A(context.write_single_register(reg_start, word)),
+        _ => future::Either::B(context.write_multiple_registers(reg_start, words)),
+    }
+}
+
+fn write_generic_with_timeout(
+    context: &mut client::Context,
+    timeout: Duration,
+    reg_start: u16,
+    words: &[u16],
+) -> impl Future<Item = (), Error = Error> {
+    write_generic(context, reg_start, words)
+        .timeout(timeout)
+        .map_err(move |err| {
+            err.into_inner().unwrap_or_else(|| {
+                Error::new(
+                    ErrorKind::TimedOut,
+                    String::from("writing generic timed out"),
+                )
+            })
+        })
+}
+
+fn with_timeout<T>(
+    future: impl Future<Item = T, Error = Error>,
+    timeout: Option<Duration>,
+    message: &'static str,
+) -> impl Future<Item = T, Error = Error> {
+    if let Some(timeout) = timeout {
+        future::Either::A(future.timeout(timeout).map_err(move |err| {
+            err.into_inner()
+                .unwrap_or_else(|| Error::new(ErrorKind::TimedOut, String::from(message)))
+        }))
+    } else {
+        future::Either::B(future)
+    }
+}
+
+/// Attach the failed request to errors, see `error::Error`.
+fn with_target<T>(
+    future: impl Future<Item = T, Error = Error>,
+    slave: Slave,
+    request: &Request<'_>,
+) -> impl Future<Item = T, Error = Error> {
+    let target = Target::new(slave.into(), request);
+    future.map_err(move |err| crate::error::Error::from(err).with_target(target).into())
+}
+
+/// The 0-based protocol address of the 1-based register `addr`.
+fn protocol_addr(addr: u16) -> Result<u16> {
+    addr.checked_sub(1)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Register 0 does not exist"))
+}
+
+fn log_byte_order(reg_type: &str, res: Result<ByteOrder>, byte_order: ByteOrder) {
+    match res {
+        Ok(_) => log::info!("Using {} byte order {}", reg_type, byte_order),
+        Err(err) => log::warn!(
+            "Using default {} byte order {} after reading it failed: {}",
+            reg_type,
+            byte_order,
+            err
+        ),
+    }
+}
+
+/// Clones share both the Modbus context and the cached scaling block
+/// and units.
+#[derive(Clone)]
 pub struct SlaveProxy {
     slave: Slave,
     shared_context: Rc<RefCell<SharedContext>>,
+    scheduler: Option<Scheduler>,
+    priority: Priority,
+    profile: Option<DeviceProfile>,
+    scaling: Rc<Cell<Option<Scaling>>>,
+    units: Rc<RefCell<HashMap<u16, Unit>>>,
+    byte_order: Rc<Cell<ByteOrder>>,
+    double_byte_order: Rc<Cell<ByteOrder>>,
 }
 
 impl SlaveProxy {
@@ -89,16 +188,154 @@ impl SlaveProxy {
         Self {
             slave,
             shared_context,
+            scheduler: None,
+            priority: Priority::default(),
+            profile: None,
+            scaling: Rc::new(Cell::new(None)),
+            units: Default::default(),
+            byte_order: Default::default(),
+            double_byte_order: Default::default(),
         }
     }
 
+    /// Select the byte order of F32 registers instead of reading it
+    /// from the device on `connect()`.
+    pub fn with_byte_order(self, byte_order: ByteOrder) -> Self {
+        self.byte_order.set(byte_order);
+        self
+    }
+
+    pub fn byte_order(&self) -> ByteOrder {
+        self.byte_order.get()
+    }
+
+    /// Select the byte order of F64 registers instead of reading it
+    /// from the device on `connect()`.
+    pub fn with_double_byte_order(self, byte_order: ByteOrder) -> Self {
+        self.double_byte_order.set(byte_order);
+        self
+    }
+
+    pub fn double_byte_order(&self) -> ByteOrder {
+        self.double_byte_order.get()
+    }
+
+    /// Select the device profile that determines the access mode of
+    /// registers on writing.
+    pub fn with_profile(mut self, profile: Option<DeviceProfile>) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Send all requests through a scheduler that shares the context
+    /// with the proxies of other slaves on the bus, see
+    /// `Scheduler::proxy()`.
+    ///
+    /// Timeouts only apply once it is the turn of a request, the time
+    /// waiting for the bus is not included.
+    pub fn with_scheduler(mut self, scheduler: Scheduler) -> Self {
+        self.scheduler = Some(scheduler);
+        self
+    }
+
+    /// Select the priority of all requests on a scheduler.
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
     pub fn slave(&self) -> Slave {
         self.slave
     }
 
+    pub fn priority(&self) -> Priority {
+        self.priority
+    }
+
+    pub fn profile(&self) -> Option<DeviceProfile> {
+        self.profile
+    }
+
     /// Reconnect a new, shared Modbus context to recover from communication errors.
     pub fn reconnect(&self) -> impl Future<Item = (), Error = Error> {
-        reconnect_shared_context(&self.shared_context)
+        match &self.scheduler {
+            Some(scheduler) => future::Either::A(scheduler.reconnect()),
+            None => future::Either::B(reconnect_shared_context(&self.shared_context)),
+        }
+    }
+
+    /// Connect and configure the byte orders of F32 and F64 registers
+    /// as selected in the device, see `read_byte_orders()`.
+    pub fn connect(&self, timeout: Option<Duration>) -> impl Future<Item = (), Error = Error> {
+        let proxy = self.clone();
+        self.reconnect()
+            .and_then(move |()| proxy.read_byte_orders(timeout))
+    }
+
+    /// Configure the byte orders of F32 and F64 registers as selected
+    /// in the device, e.g. for another slave on a connected bus.
+    ///
+    /// The default byte orders are kept if they cannot be read, e.g. if
+    /// the device does not support registers 521 or 1344.
+    pub fn read_byte_orders(
+        &self,
+        timeout: Option<Duration>,
+    ) -> impl Future<Item = (), Error = Error> {
+        let proxy = self.clone();
+        let double_proxy = self.clone();
+        self.read_byte_order(timeout)
+            .then(move |res| {
+                log_byte_order("F32", res, proxy.byte_order());
+                Ok(())
+            })
+            .and_then(move |()| {
+                double_proxy
+                    .read_double_byte_order(timeout)
+                    .then(move |res| {
+                        log_byte_order("F64", res, double_proxy.double_byte_order());
+                        Ok(())
+                    })
+            })
+    }
+
+    /// Read the byte order of F32 registers from the device and use it
+    /// for all following reads.
+    pub fn read_byte_order(
+        &self,
+        timeout: Option<Duration>,
+    ) -> impl Future<Item = ByteOrder, Error = Error> {
+        self.read_byte_order_reg(timeout, BYTE_ORDER_REG.protocol_addr(), &self.byte_order)
+    }
+
+    /// Read the byte order of F64 registers from the device and use it
+    /// for all following reads.
+    pub fn read_double_byte_order(
+        &self,
+        timeout: Option<Duration>,
+    ) -> impl Future<Item = ByteOrder, Error = Error> {
+        self.read_byte_order_reg(
+            timeout,
+            DOUBLE_BYTE_ORDER_REG.protocol_addr(),
+            &self.double_byte_order,
+        )
+    }
+
+    fn read_byte_order_reg(
+        &self,
+        timeout: Option<Duration>,
+        reg_start: u16,
+        byte_order: &Rc<Cell<ByteOrder>>,
+    ) -> impl Future<Item = ByteOrder, Error = Error> {
+        let byte_order = Rc::clone(byte_order);
+        self.read_words(timeout, reg_start, 1)
+            .and_then(|words| {
+                let code = *words.first().ok_or(DecodeError::InsufficientInput)?;
+                Ok(ByteOrder::from_code(code)?)
+            })
+            .map(move |order| {
+                byte_order.set(order);
+                order
+            })
     }
 
     fn shared_context(&self) -> Result<Rc<RefCell<client::Context>>> {
@@ -109,43 +346,457 @@ impl SlaveProxy {
         }
     }
 
-    /// Switch the Modbus slave address of all connected devices.
-    pub fn broadcast_slave(&self) -> impl Future<Item = (), Error = Error> {
+    /// Send a request to the slave of this proxy, through the scheduler
+    /// if there is one.
+    fn transaction<T, F, Fut>(&self, f: F) -> impl Future<Item = T, Error = Error>
+    where
+        T: 'static,
+        F: FnOnce(&mut client::Context) -> Fut + 'static,
+        Fut: Future<Item = T, Error = Error> + 'static,
+    {
+        if let Some(scheduler) = &self.scheduler {
+            return future::Either::A(scheduler.transaction(self.slave, self.priority, f));
+        }
         match self.shared_context() {
-            Ok(shared_context) => future::Either::A(self::broadcast_slave(
-                &mut shared_context.borrow_mut(),
-                self.slave,
-            )),
-            Err(err) => future::Either::B(future::err(err)),
+            Ok(shared_context) => {
+                let mut context = shared_context.borrow_mut();
+                context.set_slave(self.slave);
+                future::Either::B(future::Either::A(f(&mut context)))
+            }
+            Err(err) => future::Either::B(future::Either::B(future::err(err))),
         }
     }
 
+    /// Switch the Modbus slave address of all connected devices.
+    pub fn broadcast_slave(&self) -> impl Future<Item = (), Error = Error> {
+        let slave = self.slave;
+        let request = Request::WriteSingleRegister {
+            addr: BROADCAST_REG_ADDR,
+            word: u16::from(SlaveId::from(slave)),
+        };
+        let future = self.transaction(move |context| self::broadcast_slave(context, slave));
+        with_target(future, slave, &request)
+    }
+
     pub fn read_generic(
+        &self,
+        timeout: Option<Duration>,
+        reg_start: u16,
+        reg_type: RegType,
+    ) -> impl Future<Item = Vec<u16>, Error = Error> {
+        self.read_words(timeout, reg_start, reg_type.word_count())
+    }
+
+    fn read_words(
         &self,
         timeout: Option<Duration>,
         reg_start: u16,
         reg_count: u16,
-        reg_type: char,
     ) -> impl Future<Item = Vec<u16>, Error = Error> {
-        match self.shared_context() {
-            Ok(shared_context) => {
-                let mut context = shared_context.borrow_mut();
-                //context.set_slave(self.slave);
-
-                future::Either::A(if let Some(timeout) = timeout {
-                    future::Either::A(read_generic_with_timeout(
-                        &mut context,
-                        timeout,
-                        reg_start,
-                        reg_count,
-                        reg_type,
-                    ))
-                } else {
-                    future::Either::B(read_generic(&mut context, reg_start, reg_count, reg_type))
+        self.read_table_words(timeout, Table::HoldingRegister, reg_start, reg_count)
+    }
+
+    fn read_table_words(
+        &self,
+        timeout: Option<Duration>,
+        table: Table,
+        reg_start: u16,
+        reg_count: u16,
+    ) -> impl Future<Item = Vec<u16>, Error = Error> {
+        let request = match table {
+            Table::InputRegister => Request::ReadInputRegisters {
+                addr: reg_start,
+                count: reg_count,
+            },
+            _ => Request::ReadHoldingRegisters {
+                addr: reg_start,
+                count: reg_count,
+            },
+        };
+        let future = self.transaction(move |context| {
+            let words = match table {
+                Table::InputRegister => context.read_input_registers(reg_start, reg_count),
+                _ => context.read_holding_registers(reg_start, reg_count),
+            };
+            with_timeout(words, timeout, "reading timed out")
+        });
+        with_target(future, self.slave, &request)
+    }
+
+    /// Read input registers starting at the 0-based protocol address.
+    pub fn read_input_registers(
+        &self,
+        timeout: Option<Duration>,
+        reg_start: u16,
+        reg_count: u16,
+    ) -> impl Future<Item = Vec<u16>, Error = Error> {
+        self.read_table_words(timeout, Table::InputRegister, reg_start, reg_count)
+    }
+
+    /// Read coils starting at the 0-based protocol address.
+    pub fn read_coils(
+        &self,
+        timeout: Option<Duration>,
+        start: u16,
+        count: u16,
+    ) -> impl Future<Item = Vec<bool>, Error = Error> {
+        self.read_bits(timeout, Table::Coil, start, count)
+    }
+
+    /// Read discrete inputs starting at the 0-based protocol address.
+    pub fn read_discrete_inputs(
+        &self,
+        timeout: Option<Duration>,
+        start: u16,
+        count: u16,
+    ) -> impl Future<Item = Vec<bool>, Error = Error> {
+        self.read_bits(timeout, Table::DiscreteInput, start, count)
+    }
+
+    fn read_bits(
+        &self,
+        timeout: Option<Duration>,
+        table: Table,
+        start: u16,
+        count: u16,
+    ) -> impl Future<Item = Vec<bool>, Error = Error> {
+        let request = match table {
+            Table::DiscreteInput => Request::ReadDiscreteInputs { addr: start, count },
+            _ => Request::ReadCoils { addr: start, count },
+        };
+        let future = self.transaction(move |context| {
+            let bits = match table {
+                Table::DiscreteInput => context.read_discrete_inputs(start, count),
+                _ => context.read_coils(start, count),
+            };
+            with_timeout(bits, timeout, "reading timed out")
+        });
+        with_target(future, self.slave, &request)
+    }
+
+    /// Read the state of a coil or discrete input.
+    pub fn read_bit(
+        &self,
+        timeout: Option<Duration>,
+        def: &BitDef,
+    ) -> impl Future<Item = bool, Error = Error> {
+        self.read_bits(timeout, def.table, def.protocol_addr(), 1)
+            .and_then(|bits| Ok(*bits.first().ok_or(DecodeError::InsufficientInput)?))
+    }
+
+    /// Switch a coil on or off, e.g. to start the zero calibration or
+    /// to reset the totals.
+    ///
+    /// Writes to discrete inputs are refused and writes with a
+    /// destructive side effect must be confirmed explicitly.
+    pub fn write_coil(
+        &self,
+        timeout: Option<Duration>,
+        def: &BitDef,
+        state: bool,
+        confirm: Confirm,
+    ) -> impl Future<Item = (), Error = Error> {
+        let request = match def.write_request(self.profile, state, confirm) {
+            Ok(request) => request,
+            Err(err) => return future::Either::B(future::err(err.into())),
+        };
+        let start = def.protocol_addr();
+        let future = self.transaction(move |context| {
+            with_timeout(
+                context.write_single_coil(start, state),
+                timeout,
+                "writing timed out",
+            )
+        });
+        future::Either::A(with_target(future, self.slave, &request))
+    }
+
+    /// Read the scaling block of the scaled integers once and
+    /// return the cached values afterwards.
+    pub fn read_scaling(
+        &self,
+        timeout: Option<Duration>,
+    ) -> impl Future<Item = Scaling, Error = Error> {
+        if let Some(scaling) = self.scaling.get() {
+            return future::Either::A(future::ok(scaling));
+        }
+        let cache = Rc::clone(&self.scaling);
+        future::Either::B(
+            self.read_words(timeout, SCALING_REG_START, SCALING_REG_COUNT)
+                .and_then(|words| Ok(Scaling::decode(&words)?))
+                .map(move |scaling| {
+                    cache.set(Some(scaling));
+                    scaling
+                }),
+        )
+    }
+
+    /// Discard the cached scaling block and units, e.g. after the
+    /// configuration of the device has been changed.
+    pub fn invalidate_cache(&self) {
+        self.scaling.set(None);
+        self.units.borrow_mut().clear();
+    }
+
+    /// Read a scaled integer and convert it into the value of the
+    /// process variable.
+    pub fn read_scaled(
+        &self,
+        timeout: Option<Duration>,
+        var: ScaledVar,
+    ) -> impl Future<Item = f32, Error = Error> {
+        let proxy = self.clone();
+        self.read_scaling(timeout).and_then(move |scaling| {
+            proxy
+                .read_words(timeout, var.protocol_addr(), 1)
+                .and_then(move |words| Ok(scaling.decode_value(var, &words)?))
+        })
+    }
+
+    /// Read the unit from a unit code register, e.g. 39 for the mass
+    /// flow rate, once and return the cached unit afterwards.
+    ///
+    /// The label of a special unit is read from the corresponding unit
+    /// string register.
+    pub fn read_unit(
+        &self,
+        timeout: Option<Duration>,
+        unit_reg: u16,
+    ) -> impl Future<Item = Unit, Error = Error> {
+        if let Some(unit) = self.units.borrow().get(&unit_reg) {
+            return future::Either::A(future::ok(*unit));
+        }
+        let reg_start = match protocol_addr(unit_reg) {
+            Ok(reg_start) => reg_start,
+            Err(err) => return future::Either::A(future::err(err)),
+        };
+        let proxy = self.clone();
+        let cache = Rc::clone(&self.units);
+        future::Either::B(
+            self.read_words(timeout, reg_start, 1)
+                .and_then(move |words| {
+                    let code = *words.first().ok_or(DecodeError::InsufficientInput)?;
+                    Ok(Unit::from_code(code))
+                })
+                .and_then(move |unit| match (unit, special_unit_reg(unit_reg)) {
+                    (Unit::Unknown(SPECIAL_UNIT_CODE), Some(label_reg)) => future::Either::A(
+                        proxy
+                            .read_words(timeout, label_reg.protocol_addr(), label_reg.word_count())
+                            .and_then(|words| {
+                                SpecialUnit::from_words(&words)
+                                    .map(Unit::Special)
+                                    .ok_or_else(|| DecodeError::InvalidData.into())
+                            }),
+                    ),
+                    (unit, _) => future::Either::B(future::ok(unit)),
                 })
+                .map(move |unit| {
+                    cache.borrow_mut().insert(unit_reg, unit);
+                    unit
+                }),
+        )
+    }
+
+    /// Read the float or scaled integer register of a process variable
+    /// together with its unit, e.g. mass flow rate (247) in kg/s.
+    pub fn read_measurement(
+        &self,
+        timeout: Option<Duration>,
+        addr: u16,
+    ) -> impl Future<Item = UnitValue, Error = Error> {
+        let reg_type = if ScaledVar::from_addr(addr).is_some() {
+            RegType::U16
+        } else {
+            RegType::F32
+        };
+        let reg_start = match protocol_addr(addr) {
+            Ok(reg_start) => reg_start,
+            Err(err) => return future::Either::B(future::err(err)),
+        };
+        let proxy = self.clone();
+        future::Either::A(
+            self.read_words(timeout, reg_start, reg_type.word_count())
+                .and_then(move |words| proxy.decode_measurement(timeout, addr, &words)),
+        )
+    }
+
+    /// Decode the words of a process variable, e.g. from `read_plan()`,
+    /// and tag the value with its unit, see `read_measurement()`.
+    ///
+    /// The scaling block and the unit are read unless they are cached.
+    pub fn decode_measurement(
+        &self,
+        timeout: Option<Duration>,
+        addr: u16,
+        words: &[u16],
+    ) -> impl Future<Item = UnitValue, Error = Error> {
+        let unit_reg = match unit_reg(addr) {
+            Some(unit_reg) => unit_reg,
+            None => {
+                return future::Either::B(future::err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Register {} has no unit", addr),
+                )))
             }
-            Err(err) => future::Either::B(future::err(err)),
-        }
+        };
+        let value = if let Some(var) = ScaledVar::from_addr(addr) {
+            let words = words.to_vec();
+            future::Either::A(
+                self.read_scaling(timeout)
+                    .and_then(move |scaling| Ok(scaling.decode_value(var, &words)?)),
+            )
+        } else {
+            future::Either::B(future::result(
+                decode_f32(words, self.byte_order()).map_err(Error::from),
+            ))
+        };
+        let proxy = self.clone();
+        future::Either::A(value.and_then(move |value| {
+            proxy
+                .read_unit(timeout, unit_reg)
+                .map(move |unit| UnitValue::new(value, unit))
+        }))
+    }
+
+    /// Read all status registers for a snapshot of the active alarms.
+    pub fn read_alarms(
+        &self,
+        timeout: Option<Duration>,
+    ) -> impl Future<Item = AlarmStatus, Error = Error> {
+        // Consecutive status registers are read at once, one request
+        // after the other: 419–423, 433–434 and 2661
+        let proxy = self.clone();
+        stream::iter_ok(vec![(0, 5), (5, 2), (7, 1)])
+            .and_then(move |(index, count)| {
+                proxy
+                    .read_words(timeout, STATUS_REGS[index] - 1, count)
+                    .map(move |words| (index, words))
+            })
+            .fold(AlarmStatus::default(), |mut status, (index, words)| {
+                for (i, word) in words.into_iter().enumerate() {
+                    status.set(STATUS_REGS[index + i], word)?;
+                }
+                Ok::<_, Error>(status)
+            })
+    }
+
+    /// Read and decode a holding or input register with the byte order
+    /// of the device, see `RegisterDef::decode()`.
+    pub fn read_value(
+        &self,
+        timeout: Option<Duration>,
+        def: &RegisterDef,
+    ) -> impl Future<Item = Value, Error = Error> {
+        let proxy = self.clone();
+        let def = def.clone();
+        self.read_table_words(timeout, def.table, def.protocol_addr(), def.word_count())
+            .and_then(move |words| proxy.decode_value(&def, &words))
+    }
+
+    /// Decode the words of a register, e.g. from `read_plan()`, with the
+    /// byte order of the device.
+    pub fn decode_value(&self, def: &RegisterDef, words: &[u16]) -> Result<Value> {
+        let byte_order = match def.reg_type {
+            RegType::F64 => self.double_byte_order(),
+            _ => self.byte_order(),
+        };
+        Ok(def.decode(words, byte_order)?)
+    }
+
+    /// Read the words of all registers of a plan with one request per
+    /// block, in the order of the registers of the plan.
+    pub fn read_plan(
+        &self,
+        timeout: Option<Duration>,
+        plan: &ReadPlan,
+    ) -> impl Future<Item = Vec<Vec<u16>>, Error = Error> {
+        let blocks: Vec<_> = plan
+            .blocks()
+            .iter()
+            .map(|block| (block.protocol_addr(), block.count))
+            .collect();
+        let proxy = self.clone();
+        let plan = plan.clone();
+        stream::iter_ok(blocks)
+            .and_then(move |(reg_start, reg_count)| proxy.read_words(timeout, reg_start, reg_count))
+            .collect()
+            .and_then(move |block_words| {
+                let words = plan.split(&block_words)?;
+                Ok(words.into_iter().map(<[u16]>::to_vec).collect())
+            })
+    }
+
+    /// Write the raw words of a register.
+    ///
+    /// Writes to read-only registers are refused and writes with a
+    /// destructive side effect, e.g. clearing a total, must be
+    /// confirmed explicitly.
+    pub fn write_register(
+        &self,
+        timeout: Option<Duration>,
+        def: &RegisterDef,
+        words: &[u16],
+        confirm: Confirm,
+    ) -> impl Future<Item = (), Error = Error> {
+        let request = match def.write_request(self.profile, words, confirm) {
+            Ok(request) => request,
+            Err(err) => return future::Either::B(future::err(err.into())),
+        };
+        let reg_start = def.protocol_addr();
+        let slave = self.slave;
+        let words = words.to_vec();
+        let future = self.transaction(move |context| {
+            if let Some(timeout) = timeout {
+                future::Either::A(write_generic_with_timeout(
+                    context, timeout, reg_start, &words,
+                ))
+            } else {
+                future::Either::B(write_generic(context, reg_start, &words))
+            }
+        });
+        future::Either::A(with_target(future, slave, &request))
+    }
+
+    /// Encode and write the value of a register with the byte order of
+    /// the device, see `write_register()`.
+    ///
+    /// The register is read back afterwards to verify the written value
+    /// unless it is write-only or writing has a side effect, e.g. a
+    /// command. Resolves to the value read back.
+    pub fn write_value(
+        &self,
+        timeout: Option<Duration>,
+        def: &RegisterDef,
+        value: &Value,
+        confirm: Confirm,
+    ) -> impl Future<Item = Value, Error = Error> {
+        let byte_order = match def.reg_type {
+            RegType::F64 => self.double_byte_order(),
+            _ => self.byte_order(),
+        };
+        let words = match def.encode(value, byte_order) {
+            Ok(words) => words,
+            Err(err) => return future::Either::B(future::err(err.into())),
+        };
+        let verify = def.verifies_write(self.profile);
+        let proxy = self.clone();
+        let def = def.clone();
+        let value = value.clone();
+        future::Either::A(
+            self.write_register(timeout, &def, &words, confirm)
+                .and_then(move |()| {
+                    if !verify {
+                        return future::Either::B(future::ok(value));
+                    }
+                    future::Either::A(
+                        proxy
+                            .read_words(timeout, def.protocol_addr(), def.word_count())
+                            .and_then(move |read_back| {
+                                Ok(def.verify_write(&words, &read_back, byte_order)?)
+                            }),
+                    )
+                }),
+        )
     }
 }
 
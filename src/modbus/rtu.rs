@@ -5,10 +5,9 @@ use crate::core::modbus::rtu::*;
 use futures::{future, Future};
 use serialport::SerialPort;
 use std::{io::Error, path::Path, time::Duration};
-use tokio_core::reactor::{Core, Handle};
+use tokio_core::reactor::Handle;
 use tokio_io::{AsyncRead, AsyncWrite};
 use tokio_modbus::client::{rtu::connect_slave, Context as ClientContext};
-use tokio_modbus::prelude::*;
 use tokio_serial::{Serial, SerialPortSettings};
 
 pub const SERIAL_PORT_SETTINGS: SerialPortSettings = SerialPortSettings {
@@ -20,6 +19,19 @@ pub const SERIAL_PORT_SETTINGS: SerialPortSettings = SerialPortSettings {
     timeout: Duration::from_secs(0),
 };
 
+pub const ASCII_SERIAL_PORT_SETTINGS: SerialPortSettings = SerialPortSettings {
+    data_bits: ASCII_DATA_BITS,
+    parity: ASCII_PARITY,
+    ..SERIAL_PORT_SETTINGS
+};
+
+pub fn serial_port_settings(framing: Framing) -> &'static SerialPortSettings {
+    match framing {
+        Framing::Rtu => &SERIAL_PORT_SETTINGS,
+        Framing::Ascii => &ASCII_SERIAL_PORT_SETTINGS,
+    }
+}
+
 pub fn connect<T: AsyncRead + AsyncWrite + 'static>(
     handle: &Handle,
     transport: T,
@@ -27,16 +39,38 @@ pub fn connect<T: AsyncRead + AsyncWrite + 'static>(
     connect_slave(handle, transport, BROADCAST_SLAVE)
 }
 
+pub fn connect_ascii<T: AsyncRead + AsyncWrite + 'static>(
+    transport: T,
+) -> impl Future<Item = ClientContext, Error = Error> {
+    super::ascii::connect_slave(transport, BROADCAST_SLAVE)
+}
+
 pub fn connect_path(
     handle: &Handle,
     path: impl AsRef<Path>,
 ) -> Box<dyn Future<Item = ClientContext, Error = Error>> {
-    log::info!("Connecting to serial port {}", path.as_ref().display());
-    match Serial::from_path_with_handle(path, &SERIAL_PORT_SETTINGS, handle.new_tokio_handle()) {
+    connect_path_with_framing(handle, path, Framing::Rtu)
+}
+
+pub fn connect_path_with_framing(
+    handle: &Handle,
+    path: impl AsRef<Path>,
+    framing: Framing,
+) -> Box<dyn Future<Item = ClientContext, Error = Error>> {
+    log::info!(
+        "Connecting to serial port {} ({:?})",
+        path.as_ref().display(),
+        framing
+    );
+    let settings = serial_port_settings(framing);
+    match Serial::from_path_with_handle(path, settings, handle.new_tokio_handle()) {
         Ok(mut serial) => {
             //set the DTR pin
             serial.write_data_terminal_ready(true).unwrap();
-            Box::new(connect(handle, serial))
+            match framing {
+                Framing::Rtu => Box::new(connect(handle, serial)),
+                Framing::Ascii => Box::new(connect_ascii(serial)),
+            }
         }
         Err(err) => Box::new(future::err(err)),
     }
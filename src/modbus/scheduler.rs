@@ -0,0 +1,310 @@
+//! Scheduler for the transactions of multiple slaves on a single bus.
+//!
+//! All transmitters on an RS-485 segment share one Modbus context. The
+//! scheduler sends one transaction at a time and switches the slave of
+//! the context before each one. Transactions of a higher priority are
+//! always sent first, and transactions of the same priority take turns
+//! between the slaves, so that a slave with many pending requests
+//! cannot delay the others.
+
+use super::SlaveProxy;
+
+use futures::{future, unsync::oneshot, Future};
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    io::{Error, ErrorKind},
+    rc::Rc,
+};
+use tokio_core::reactor::Handle;
+use tokio_modbus::{
+    client::{
+        self,
+        util::{reconnect_shared_context, SharedContext},
+    },
+    prelude::*,
+};
+
+/// The priority of a transaction, e.g. `High` for writes and commands
+/// that must not wait for the cyclic reads of all slaves.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl Priority {
+    fn index(self) -> usize {
+        match self {
+            Priority::High => 0,
+            Priority::Normal => 1,
+            Priority::Low => 2,
+        }
+    }
+}
+
+type Job = Box<dyn FnOnce(&Rc<RefCell<SharedContext>>) -> Box<dyn Future<Item = (), Error = ()>>>;
+
+/// The pending transactions of a single priority, grouped by slave.
+/// Transactions for the whole bus, i.e. reconnects, have no slave.
+#[derive(Default)]
+struct Queue {
+    slaves: VecDeque<(Option<SlaveId>, VecDeque<Job>)>,
+}
+
+impl Queue {
+    fn push(&mut self, slave: Option<SlaveId>, job: Job) {
+        match self.slaves.iter_mut().find(|(id, _)| *id == slave) {
+            Some((_, jobs)) => jobs.push_back(job),
+            None => self
+                .slaves
+                .push_back((slave, Some(job).into_iter().collect())),
+        }
+    }
+
+    /// Take the next transaction of the slave at the front and move the
+    /// slave to the back.
+    fn pop(&mut self) -> Option<Job> {
+        let (slave, mut jobs) = self.slaves.pop_front()?;
+        let job = jobs.pop_front();
+        if !jobs.is_empty() {
+            self.slaves.push_back((slave, jobs));
+        }
+        job
+    }
+
+    fn len(&self) -> usize {
+        self.slaves.iter().map(|(_, jobs)| jobs.len()).sum()
+    }
+}
+
+#[derive(Default)]
+struct State {
+    queues: [Queue; 3],
+    busy: bool,
+}
+
+/// Clones share the context and the queue of pending transactions.
+///
+/// The transactions are spawned on the event loop of the `Handle`, one
+/// after the other.
+#[derive(Clone)]
+pub struct Scheduler {
+    handle: Handle,
+    shared_context: Rc<RefCell<SharedContext>>,
+    state: Rc<RefCell<State>>,
+}
+
+impl Scheduler {
+    pub fn new(handle: Handle, shared_context: Rc<RefCell<SharedContext>>) -> Self {
+        Self {
+            handle,
+            shared_context,
+            state: Default::default(),
+        }
+    }
+
+    pub fn shared_context(&self) -> &Rc<RefCell<SharedContext>> {
+        &self.shared_context
+    }
+
+    /// Create a proxy for a slave on the bus that sends all requests
+    /// through this scheduler.
+    pub fn proxy(&self, slave: Slave) -> SlaveProxy {
+        SlaveProxy::new(slave, Rc::clone(&self.shared_context)).with_scheduler(self.clone())
+    }
+
+    /// The number of transactions that wait for the bus.
+    pub fn pending(&self) -> usize {
+        self.state.borrow().queues.iter().map(Queue::len).sum()
+    }
+
+    /// Queue a transaction with a slave.
+    ///
+    /// The closure is invoked with the context once it is the turn of
+    /// the transaction, and the bus is blocked until the returned future
+    /// completes. Timeouts should therefore be applied inside the
+    /// closure. Transactions are skipped if the returned future has
+    /// been dropped before.
+    pub fn transaction<T, F, Fut>(
+        &self,
+        slave: Slave,
+        priority: Priority,
+        f: F,
+    ) -> impl Future<Item = T, Error = Error>
+    where
+        T: 'static,
+        F: FnOnce(&mut client::Context) -> Fut + 'static,
+        Fut: Future<Item = T, Error = Error> + 'static,
+    {
+        self.submit(
+            Some(slave.into()),
+            priority,
+            move |shared_context| match shared_context.borrow().share_context() {
+                Some(context) => {
+                    let mut context = context.borrow_mut();
+                    context.set_slave(slave);
+                    future::Either::A(f(&mut context))
+                }
+                None => future::Either::B(future::err(Error::new(
+                    ErrorKind::NotConnected,
+                    "No shared context",
+                ))),
+            },
+        )
+    }
+
+    /// Reconnect the shared context as soon as the transaction in flight
+    /// has completed, before all other pending transactions.
+    pub fn reconnect(&self) -> impl Future<Item = (), Error = Error> {
+        self.submit(None, Priority::High, reconnect_shared_context)
+    }
+
+    fn submit<T, F, Fut>(
+        &self,
+        slave: Option<SlaveId>,
+        priority: Priority,
+        f: F,
+    ) -> impl Future<Item = T, Error = Error>
+    where
+        T: 'static,
+        F: FnOnce(&Rc<RefCell<SharedContext>>) -> Fut + 'static,
+        Fut: Future<Item = T, Error = Error> + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        let job: Job = Box::new(move |shared_context| {
+            if tx.is_canceled() {
+                return Box::new(future::ok(()));
+            }
+            Box::new(f(shared_context).then(move |res| {
+                let _ = tx.send(res);
+                Ok(())
+            }))
+        });
+        self.state.borrow_mut().queues[priority.index()].push(slave, job);
+        self.dispatch();
+        rx.map_err(|_| Error::other("Transaction dropped by the scheduler"))
+            .and_then(|res| res)
+    }
+
+    /// Spawn the next transaction unless the bus is busy.
+    fn dispatch(&self) {
+        let job = {
+            let mut state = self.state.borrow_mut();
+            if state.busy {
+                return;
+            }
+            match state.queues.iter_mut().find_map(Queue::pop) {
+                Some(job) => {
+                    state.busy = true;
+                    job
+                }
+                None => return,
+            }
+        };
+        let scheduler = self.clone();
+        self.handle.spawn(job(&self.shared_context).then(move |_| {
+            scheduler.state.borrow_mut().busy = false;
+            scheduler.dispatch();
+            Ok(())
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio_core::reactor::Core;
+    use tokio_modbus::client::{util::NewContext, Client};
+
+    type Log = Rc<RefCell<Vec<(SlaveId, Request)>>>;
+
+    /// Answers every read with the slave id and records the requests.
+    struct MockClient {
+        slave: SlaveId,
+        log: Log,
+    }
+
+    impl SlaveContext for MockClient {
+        fn set_slave(&mut self, slave: Slave) {
+            self.slave = slave.into();
+        }
+    }
+
+    impl Client for MockClient {
+        fn call(&self, req: Request) -> Box<dyn Future<Item = Response, Error = Error>> {
+            self.log.borrow_mut().push((self.slave, req.clone()));
+            let response = match req {
+                Request::ReadHoldingRegisters(_, _) => {
+                    Response::ReadHoldingRegisters(vec![u16::from(self.slave)])
+                }
+                Request::WriteSingleRegister(addr, word) => {
+                    Response::WriteSingleRegister(addr, word)
+                }
+                req => {
+                    return Box::new(future::err(Error::other(format!(
+                        "Unsupported request {:?}",
+                        req
+                    ))))
+                }
+            };
+            Box::new(future::ok(response))
+        }
+    }
+
+    struct MockConnector(Log);
+
+    impl NewContext for MockConnector {
+        fn new_context(&self) -> Box<dyn Future<Item = client::Context, Error = Error>> {
+            let client: Box<dyn Client> = Box::new(MockClient {
+                slave: 0,
+                log: Rc::clone(&self.0),
+            });
+            Box::new(future::ok(client.into()))
+        }
+    }
+
+    #[test]
+    fn priority_and_fairness() {
+        let mut core = Core::new().unwrap();
+        let log = Log::default();
+        let shared_context = Rc::new(RefCell::new(SharedContext::new(
+            None,
+            Box::new(MockConnector(Rc::clone(&log))),
+        )));
+        let scheduler = Scheduler::new(core.handle(), shared_context);
+        core.run(scheduler.reconnect()).unwrap();
+
+        let read = |slave, addr| {
+            scheduler.transaction(Slave(slave), Priority::Normal, move |context| {
+                context.read_holding_registers(addr, 1)
+            })
+        };
+        let reads = vec![read(1, 0), read(1, 1), read(1, 2), read(2, 0)];
+        let write = scheduler
+            .proxy(Slave(3))
+            .with_priority(Priority::High)
+            .broadcast_slave();
+        assert_eq!(4, scheduler.pending());
+
+        assert_eq!(
+            vec![vec![1], vec![1], vec![1], vec![2]],
+            core.run(future::join_all(reads)).unwrap()
+        );
+        core.run(write).unwrap();
+        assert_eq!(0, scheduler.pending());
+        assert_eq!(
+            vec![
+                (1, Request::ReadHoldingRegisters(0, 1)),
+                (3, Request::WriteSingleRegister(312, 3)),
+                (1, Request::ReadHoldingRegisters(1, 1)),
+                (2, Request::ReadHoldingRegisters(0, 1)),
+                (1, Request::ReadHoldingRegisters(2, 1)),
+            ],
+            *log.borrow()
+        );
+    }
+}
@@ -0,0 +1,160 @@
+//! Modbus TCP transport, e.g. for transmitters with an Ethernet
+//! interface or behind an RS-485 to Ethernet gateway.
+
+use futures::Future;
+use std::{
+    io::{Error, ErrorKind, Result},
+    net::{SocketAddr, ToSocketAddrs},
+};
+use tokio_core::reactor::Handle;
+use tokio_modbus::{
+    client::{tcp::connect_slave, util::NewContext, Context as ClientContext},
+    prelude::Slave,
+};
+
+/// The registered TCP port of Modbus.
+pub const DEFAULT_PORT: u16 = 502;
+
+/// Resolve a host name or IP address with an optional port, e.g.
+/// `192.168.1.10` or `gateway:5020`.
+pub fn resolve(addr: &str) -> Result<SocketAddr> {
+    let addrs = match addr.parse::<SocketAddr>() {
+        Ok(socket_addr) => return Ok(socket_addr),
+        Err(_) if addr.contains(':') => addr.to_socket_addrs()?,
+        Err(_) => (addr, DEFAULT_PORT).to_socket_addrs()?,
+    };
+    addrs.into_iter().next().ok_or_else(|| {
+        Error::new(
+            ErrorKind::AddrNotAvailable,
+            format!("No address found for {}", addr),
+        )
+    })
+}
+
+/// Connect to a transmitter with the given unit identifier.
+///
+/// Gateways forward the requests to the serial device with the Modbus
+/// address of the unit identifier.
+pub fn connect(
+    handle: &Handle,
+    socket_addr: SocketAddr,
+    slave: Slave,
+) -> impl Future<Item = ClientContext, Error = Error> {
+    log::info!("Connecting to {} with unit id {}", socket_addr, slave.0);
+    connect_slave(handle, socket_addr, slave)
+}
+
+/// Establishes a new TCP connection whenever a `SharedContext` is
+/// reconnected.
+#[derive(Debug, Clone)]
+pub struct TcpConnector {
+    handle: Handle,
+    socket_addr: SocketAddr,
+    slave: Slave,
+}
+
+impl TcpConnector {
+    pub fn new(handle: Handle, socket_addr: SocketAddr, slave: Slave) -> Self {
+        Self {
+            handle,
+            socket_addr,
+            slave,
+        }
+    }
+
+    pub fn socket_addr(&self) -> SocketAddr {
+        self.socket_addr
+    }
+}
+
+impl NewContext for TcpConnector {
+    fn new_context(&self) -> Box<dyn Future<Item = ClientContext, Error = Error>> {
+        Box::new(connect(&self.handle, self.socket_addr, self.slave))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{core::modbus::RegType, modbus::SlaveProxy};
+
+    use std::{
+        cell::RefCell,
+        io::{Read, Write},
+        net::TcpListener,
+        rc::Rc,
+        thread,
+    };
+    use tokio_core::reactor::Core;
+    use tokio_modbus::client::util::SharedContext;
+
+    /// Answer read requests for holding registers with the register
+    /// numbers, and drop each connection after `requests` requests.
+    fn serve(
+        listener: TcpListener,
+        connections: usize,
+        requests: usize,
+    ) -> thread::JoinHandle<Vec<u8>> {
+        thread::spawn(move || {
+            let mut unit_ids = Vec::new();
+            for stream in listener.incoming().take(connections) {
+                let mut stream = stream.unwrap();
+                for _ in 0..requests {
+                    let mut req = [0; 12];
+                    stream.read_exact(&mut req).unwrap();
+                    unit_ids.push(req[6]);
+                    assert_eq!(0x03, req[7]);
+                    let addr = u16::from_be_bytes([req[8], req[9]]);
+                    let count = u16::from_be_bytes([req[10], req[11]]);
+                    let mut rsp = req[..8].to_vec();
+                    rsp[4..6].copy_from_slice(&(3 + 2 * count).to_be_bytes());
+                    rsp.push(2 * count as u8);
+                    for i in 0..count {
+                        rsp.extend_from_slice(&(addr + i + 1).to_be_bytes());
+                    }
+                    stream.write_all(&rsp).unwrap();
+                }
+            }
+            unit_ids
+        })
+    }
+
+    #[test]
+    fn resolve_addresses() {
+        assert_eq!(
+            "127.0.0.1:502".parse::<SocketAddr>().unwrap(),
+            resolve("127.0.0.1").unwrap()
+        );
+        assert_eq!(5020, resolve("127.0.0.1:5020").unwrap().port());
+        assert_eq!(502, resolve("localhost").unwrap().port());
+    }
+
+    #[test]
+    fn read_and_reconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let socket_addr = listener.local_addr().unwrap();
+        let server = serve(listener, 2, 1);
+
+        let mut core = Core::new().unwrap();
+        let connector = TcpConnector::new(core.handle(), socket_addr, Slave(111));
+        let shared_context = Rc::new(RefCell::new(SharedContext::new(None, Box::new(connector))));
+        let proxy = SlaveProxy::new(Slave(111), shared_context);
+
+        core.run(proxy.reconnect()).unwrap();
+        let words = core
+            .run(proxy.read_generic(None, 246, RegType::F32))
+            .unwrap();
+        assert_eq!(vec![247, 248], words);
+
+        // The server drops the connection after each request
+        assert!(core
+            .run(proxy.read_generic(None, 246, RegType::F32))
+            .is_err());
+        core.run(proxy.reconnect()).unwrap();
+        let words = core.run(proxy.read_generic(None, 0, RegType::U16)).unwrap();
+        assert_eq!(vec![1], words);
+
+        assert_eq!(vec![111, 111], server.join().unwrap());
+    }
+}
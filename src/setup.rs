@@ -1,13 +1,105 @@
+use crate::{buildmap::DeviceProfile, core::modbus::Framing};
+
 use serde::{Deserialize, Serialize};
 use std::{fs::File, io::Read};
 
+#[allow(non_snake_case)]
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Config {
+    #[serde(default)]
     pub ComPort: Vec<String>,
+    /// Serial ports with their own slaves and registers, polled in
+    /// parallel with those in `ComPort`
+    #[serde(default)]
+    pub Ports: Vec<PortConfig>,
+    /// Host and port of a Modbus TCP device or gateway, used instead of
+    /// the serial port if present
+    #[serde(default)]
+    pub TcpAddress: Option<String>,
+    /// Transmission mode on the serial port, `Rtu` or `Ascii`
+    #[serde(default)]
+    pub Framing: Framing,
     pub ModbusAddress: u8,
+    /// Further transmitters on the same bus that are read with the
+    /// same registers
+    #[serde(default)]
+    pub ModbusAddresses: Vec<u8>,
     pub Regs: Vec<u16>,
     pub cycle_time: u64,
     pub timeout: u64,
+    /// Device profile for registers with multiple definitions in the map
+    #[serde(default)]
+    pub Profile: Option<DeviceProfile>,
+    /// Convert the scaled integers in registers 2–11 into floats
+    #[serde(default)]
+    pub ScaledInt: bool,
+    /// Maximum number of unused registers that are read in between to
+    /// read more registers with the same request
+    #[serde(default)]
+    pub MaxReadGap: u16,
+}
+
+/// A serial port with the transmitters on its bus.
+#[allow(non_snake_case)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PortConfig {
+    pub ComPort: String,
+    /// Modbus addresses of the transmitters, those of the top level if
+    /// empty
+    #[serde(default)]
+    pub ModbusAddresses: Vec<u8>,
+    /// Registers to read, those of the top level if absent
+    #[serde(default)]
+    pub Regs: Option<Vec<u16>>,
+}
+
+impl Config {
+    /// The Modbus addresses of all transmitters at the top level,
+    /// without duplicates.
+    pub fn modbus_addresses(&self) -> Vec<u8> {
+        let mut addresses = vec![self.ModbusAddress];
+        for addr in &self.ModbusAddresses {
+            if !addresses.contains(addr) {
+                addresses.push(*addr);
+            }
+        }
+        addresses
+    }
+
+    /// All serial ports with their Modbus addresses and registers.
+    ///
+    /// The ports in `ComPort` read the registers of the top level from
+    /// all transmitters of the top level. The missing settings of
+    /// `Ports` are taken from the top level.
+    pub fn ports(&self) -> Vec<PortConfig> {
+        let top_level = PortConfig {
+            ComPort: String::new(),
+            ModbusAddresses: self.modbus_addresses(),
+            Regs: Some(self.Regs.clone()),
+        };
+        let ports = self.ComPort.iter().map(|com_port| PortConfig {
+            ComPort: com_port.clone(),
+            ..top_level.clone()
+        });
+        let ports = ports.chain(self.Ports.iter().map(|port| PortConfig {
+            ComPort: port.ComPort.clone(),
+            ModbusAddresses: if port.ModbusAddresses.is_empty() {
+                top_level.ModbusAddresses.clone()
+            } else {
+                port.ModbusAddresses.clone()
+            },
+            Regs: port.Regs.clone().or_else(|| top_level.Regs.clone()),
+        }));
+        let mut unique: Vec<PortConfig> = Vec::new();
+        for port in ports {
+            if unique.iter().any(|other| other.ComPort == port.ComPort) {
+                log::warn!("Ignoring duplicate port {}", port.ComPort);
+                continue;
+            }
+            unique.push(port);
+        }
+        unique
+    }
 }
 
 pub fn read_config() -> Config {
@@ -24,7 +116,11 @@ pub fn read_config() -> Config {
 
     // Extract the Regs field and print it
     let regs: &Vec<u16> = config.Regs.as_ref();
-    let com: &Vec<String> = config.ComPort.as_ref();
+    let com: Vec<String> = config
+        .ports()
+        .into_iter()
+        .map(|port| port.ComPort)
+        .collect();
     let interval = config.cycle_time;
     let timeout = config.timeout;
     println!(
@@ -33,3 +129,49 @@ pub fn read_config() -> Config {
     );
     config
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ports() {
+        let config: Config = serde_yaml::from_str(
+            "
+ComPort: [COM4]
+Ports:
+  - ComPort: COM5
+    ModbusAddresses: [1, 2]
+    Regs: [247]
+  - ComPort: COM6
+  - ComPort: COM4
+ModbusAddress: 111
+ModbusAddresses: [3, 111]
+Regs: [1200, 315]
+cycle_time: 1
+timeout: 500
+",
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                PortConfig {
+                    ComPort: "COM4".into(),
+                    ModbusAddresses: vec![111, 3],
+                    Regs: Some(vec![1200, 315]),
+                },
+                PortConfig {
+                    ComPort: "COM5".into(),
+                    ModbusAddresses: vec![1, 2],
+                    Regs: Some(vec![247]),
+                },
+                PortConfig {
+                    ComPort: "COM6".into(),
+                    ModbusAddresses: vec![111, 3],
+                    Regs: Some(vec![1200, 315]),
+                },
+            ],
+            config.ports()
+        );
+    }
+}